@@ -53,6 +53,7 @@ xdr_enum!(AuthFlavor {
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct OpaqueAuth {
   pub flavor: AuthFlavor,
+  #[serde(with = "serde_xdr::opaque")]
   pub body: Vec<u8>,
 }
 
@@ -1,34 +1,27 @@
 #![cfg_attr(feature="clippy", feature(plugin))]
 #![cfg_attr(feature="clippy", plugin(clippy))]
 
-#[macro_use]
-extern crate nom;
 #[macro_use]
 extern crate serde_xdr;
 
 extern crate rustc_serialize;
 extern crate serde;
 extern crate clap;
+extern crate xdrgen;
 
-use std::env;
 use std::fs::File;
-use std::str;
-use std::io::{self, Write, Read};
+use std::io::{self, Read, Write};
+use std::process;
 use clap::*;
 
-mod parser;
-mod codegen;
-mod code_writer;
-mod function_writer;
-
-use code_writer::CodeWriter;
-use codegen::CodeGen;
+use xdrgen::{BuildError, Builder, ParseError, Preprocessor, doc};
 
 fn main() {
     let app  = App::new("rust-xdr")
         .version("0.0.1")
         .author("Ben Brittain")
         .about("Rust Generator for XDR services")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("service")
              .help("Generate service definitions in addition to types")
              .long("service")
@@ -41,6 +34,24 @@ fn main() {
              .short("i")
              .multiple(true)
              .takes_value(true)
+             .required(true))
+        .args(&preprocessor_args())
+        .arg(Arg::with_name("opaque-bytes")
+             .help("Generate bytes::Bytes for opaque<> (needs serde_xdr's bytes feature)")
+             .long("opaque-bytes")
+             .takes_value(false)
+             .required(false))
+        .arg(Arg::with_name("snippet-prefix")
+             .help("Copy `%` lines starting with PREFIX into prot.rs [default: #[rust]]")
+             .long("snippet-prefix")
+             .value_name("PREFIX")
+             .takes_value(true)
+             .required(false))
+        .arg(Arg::with_name("emit-ir")
+             .help("Write the resolved schema as JSON to FILE")
+             .long("emit-ir")
+             .value_name("FILE")
+             .takes_value(true)
              .required(false))
         .arg(Arg::with_name("output")
              .help("Output directory")
@@ -48,39 +59,205 @@ fn main() {
              .short("o")
              .multiple(false)
              .takes_value(true)
-             .required(true))
+             .required_unless("emit-ir"))
+        .subcommand(SubCommand::with_name("fmt")
+             .about("Rewrite XDR definition files in the canonical style")
+             .arg(Arg::with_name("check")
+                  .help("List the files that aren't formatted instead of rewriting them")
+                  .long("check")
+                  .takes_value(false)
+                  .required(false))
+             .arg(Arg::with_name("files")
+                  .help("XDR definition files")
+                  .multiple(true)
+                  .required(true)))
+        .subcommand(SubCommand::with_name("compat")
+             .about("Report changes between two versions of a schema that break the wire format")
+             .args(&preprocessor_args())
+             .arg(Arg::with_name("old")
+                  .help("The previous version of the XDR definition file")
+                  .required(true))
+             .arg(Arg::with_name("new")
+                  .help("The new version of the XDR definition file")
+                  .required(true)))
+        .subcommand(SubCommand::with_name("doc")
+             .about("Write reference documentation for XDR definition files")
+             .args(&preprocessor_args())
+             .arg(Arg::with_name("format")
+                  .help("Output format")
+                  .long("format")
+                  .takes_value(true)
+                  .possible_values(&["markdown", "html"])
+                  .default_value("markdown"))
+             .arg(Arg::with_name("output")
+                  .help("File to write instead of standard output")
+                  .long("output")
+                  .short("o")
+                  .takes_value(true)
+                  .required(false))
+             .arg(Arg::with_name("files")
+                  .help("XDR definition files")
+                  .multiple(true)
+                  .required(true)))
         .get_matches();
 
+    if let Some(fmt) = app.subcommand_matches("fmt") {
+        format_files(fmt.values_of("files").unwrap().collect(), fmt.is_present("check"));
+    }
+    if let Some(compat) = app.subcommand_matches("compat") {
+        check_compat(compat);
+    }
+    if let Some(doc) = app.subcommand_matches("doc") {
+        write_doc(doc);
+    }
+
     let files: Vec<&str> = app.values_of("input").unwrap().collect();
-    let out_dir: String = app.values_of("output").unwrap().collect();
 
-    let mut source = String::new();
+    let mut builder = Builder::new();
     for file in files.iter() {
-        let mut fin = File::open(file).expect("input file does not exist.");
-        let _ = fin.read_to_string(&mut source);
+        builder.input(file);
+    }
+    builder.preprocessor(preprocessor(&app));
+    if let Some(prefix) = app.value_of("snippet-prefix") {
+        builder.snippet_prefix(prefix);
+    }
+    builder.opaque_bytes(app.is_present("opaque-bytes"))
+        .cargo_metadata(false);
+
+    if let Some(file) = app.value_of("emit-ir") {
+        let ir = match builder.ir() {
+            Ok(ir) => ir,
+            Err(e) => fail(e),
+        };
+        if let Err(e) = File::create(file).and_then(|mut fout| fout.write_all(ir.as_bytes())) {
+            let _ = writeln!(io::stderr(), "error: {}: {}", file, e);
+            process::exit(1);
+        }
     }
+    if let Some(out_dir) = app.value_of("output") {
+        if let Err(e) = builder.out_dir(out_dir).generate() {
+            fail(e);
+        }
+    }
+}
+
+// `-I` and `-D`, taken by every command that preprocesses its input
+fn preprocessor_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+    [Arg::with_name("include-dir")
+         .help("Directory to search for #include'd files")
+         .long("include-dir")
+         .short("I")
+         .multiple(true)
+         .number_of_values(1)
+         .takes_value(true)
+         .required(false),
+     Arg::with_name("define")
+         .help("Predefine a preprocessor macro, as NAME or NAME=VALUE")
+         .long("define")
+         .short("D")
+         .multiple(true)
+         .number_of_values(1)
+         .takes_value(true)
+         .required(false)]
+}
 
-    let mut types_buffer = Vec::new();
-    let mut codec_buffer = Vec::new();
-    let mut service_buffer = Vec::new();
-    {
-        let mut types_wr = CodeWriter::new(&mut types_buffer);
-        let mut codec_wr = CodeWriter::new(&mut codec_buffer);
-        let mut service_wr = CodeWriter::new(&mut service_buffer);
+fn preprocessor(args: &ArgMatches) -> Preprocessor {
+    let mut preprocessor = Preprocessor::new();
+    for dir in args.values_of("include-dir").into_iter().flat_map(|dirs| dirs) {
+        preprocessor.include_path(dir);
+    }
+    for define in args.values_of("define").into_iter().flat_map(|defines| defines) {
+        match define.find('=') {
+            Some(eq) => preprocessor.define(&define[..eq], &define[eq + 1..]),
+            None => preprocessor.define(define, "1"),
+        }
+    }
+    preprocessor
+}
 
-        let mut cg = CodeGen::new(&mut types_wr, &mut codec_wr, &mut service_wr);
-        cg.compile(source, false).expect("XDR->Rust codegen failed");
+fn fail(e: BuildError) -> ! {
+    match e {
+        BuildError::Parse(e) => report(e),
+        e => {
+            let _ = writeln!(io::stderr(), "error: {}", e);
+            process::exit(1);
+        },
     }
+}
 
-    let mut types_fout = File::create(out_dir.clone() + "/prot.rs")
-        .expect("error creating prot.rs");
-    let _ = types_fout.write(types_buffer.as_ref());
+fn report(e: ParseError) -> ! {
+    let count = e.diagnostics.len();
+    let _ = writeln!(io::stderr(), "{}\n\nerror: aborting due to {} previous error{}",
+                     e, count, if count == 1 { "" } else { "s" });
+    process::exit(1);
+}
+
+fn format_files(files: Vec<&str>, check: bool) -> ! {
+    let mut failed = false;
+    for file in files {
+        let mut source = String::new();
+        if let Err(e) = File::open(file).and_then(|mut fin| fin.read_to_string(&mut source)) {
+            let _ = writeln!(io::stderr(), "error: {}: {}", file, e);
+            failed = true;
+            continue;
+        }
+        let formatted = match xdrgen::format(file, &source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                let _ = writeln!(io::stderr(), "{}\n", e);
+                failed = true;
+                continue;
+            },
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", file);
+            failed = true;
+        } else if let Err(e) = File::create(file).and_then(|mut fout| fout.write_all(formatted.as_bytes())) {
+            let _ = writeln!(io::stderr(), "error: {}: {}", file, e);
+            failed = true;
+        }
+    }
+    process::exit(if failed { 1 } else { 0 });
+}
 
-    let mut service_fout = File::create(out_dir.clone() + "/service.rs")
-        .expect("error creating service.rs");
-    let _ = service_fout.write(service_buffer.as_ref());
+fn check_compat(args: &ArgMatches) -> ! {
+    let changes = match xdrgen::compat::check(&preprocessor(args), args.value_of("old").unwrap(),
+                                              args.value_of("new").unwrap()) {
+        Ok(changes) => changes,
+        Err(e) => fail(e),
+    };
+    // Breaking changes first, then the safe ones
+    for change in changes.iter().filter(|c| c.breaking).chain(changes.iter().filter(|c| !c.breaking)) {
+        println!("{}", change);
+    }
+    process::exit(if changes.iter().any(|c| c.breaking) { 1 } else { 0 });
+}
 
-    let mut codec_fout = File::create(out_dir.clone() + "/codec.rs")
-        .expect("error creating codec.rs");
-    let _ = codec_fout.write(codec_buffer.as_ref());
+fn write_doc(args: &ArgMatches) -> ! {
+    let mut builder = Builder::new();
+    for file in args.values_of("files").unwrap() {
+        builder.input(file);
+    }
+    builder.preprocessor(preprocessor(args));
+    let format = match args.value_of("format") {
+        Some("html") => doc::Format::Html,
+        _ => doc::Format::Markdown,
+    };
+    let doc = match builder.doc(format) {
+        Ok(doc) => doc,
+        Err(e) => fail(e),
+    };
+    let written = match args.value_of("output") {
+        Some(file) => File::create(file).and_then(|mut fout| fout.write_all(doc.as_bytes()))
+            .map_err(|e| format!("{}: {}", file, e)),
+        None => io::stdout().write_all(doc.as_bytes()).map_err(|e| e.to_string()),
+    };
+    if let Err(e) = written {
+        let _ = writeln!(io::stderr(), "error: {}", e);
+        process::exit(1);
+    }
+    process::exit(0);
 }
@@ -0,0 +1,52 @@
+//! Fixed length arrays of any size, for use as `#[serde(with = "serde_xdr::array")]`
+//! on `[T; N]` fields. serde only implements its traits for short arrays.
+//!
+//! The elements follow each other with no length in front.
+use std::convert::TryInto;
+use std::fmt;
+use std::marker::PhantomData;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::ser::SerializeSeq;
+use serde::de::{self, Visitor, SeqVisitor};
+
+pub fn serialize<T, S, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where T: Serialize, S: Serializer
+{
+    let mut seq = try!(serializer.serialize_seq_fixed_size(N));
+    for elem in array.iter() {
+        try!(seq.serialize_element(elem));
+    }
+    seq.end()
+}
+
+pub fn deserialize<T, D, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where T: Deserialize, D: Deserializer
+{
+    deserializer.deserialize_seq_fixed_size(N, ArrayVisitor { marker: PhantomData })
+}
+
+struct ArrayVisitor<T, const N: usize> {
+    marker: PhantomData<T>,
+}
+
+impl<T: Deserialize, const N: usize> Visitor for ArrayVisitor<T, N> {
+    type Value = [T; N];
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an array of {} elements", N)
+    }
+
+    fn visit_seq<V>(self, mut visitor: V) -> Result<[T; N], V::Error> where V: SeqVisitor {
+        let mut elems = Vec::with_capacity(N);
+        for i in 0..N {
+            match try!(visitor.visit()) {
+                Some(elem) => elems.push(elem),
+                None => return Err(de::Error::invalid_length(i, &self)),
+            }
+        }
+        match elems.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!(),
+        }
+    }
+}
@@ -1,10 +1,18 @@
 use std::io::{self, Read};
+#[cfg(feature = "bytes")]
+use std::cmp;
 use byteorder::{BigEndian, ReadBytesExt};
 use serde::de::{self, EnumVisitor, Visitor, Deserialize};
 use serde::bytes::ByteBuf;
 
 use std::result;
 use error::{DecoderResult, EncoderError};
+use lazy::LAZY_SYMBOL;
+use opaque::FIXED_SYMBOL;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+#[cfg(feature = "bytes")]
+use shared::{SharedBytes, SHARED_SYMBOL};
 use serde::de::value::ValueDeserializer;
 
 macro_rules! not_implemented {
@@ -16,32 +24,172 @@ macro_rules! not_implemented {
 }
 
 macro_rules! impl_num {
-    ($ty:ty, $deserialize_method:ident, $visitor_method:ident, $read_method:ident, $byte_size:expr) => {
+    ($ty:ty, $deserialize_method:ident, $visitor_method:ident, $read_method:ident) => {
         fn $deserialize_method<V>(self, mut visitor: V) -> DecoderResult<V::Value>
             where V: de::Visitor, {
-                let res = visitor.$visitor_method(self.$read_method::<BigEndian>()?);
-                self.bytes_consumed += $byte_size;
-                res
+                visitor.$visitor_method(self.$read_method::<BigEndian>()?)
         }
     }
 }
 
 pub struct Deserializer<R: Read> {
     reader: R,
-    bytes_consumed: usize
+    // Counted as bytes come out of the reader, see the Read impl below
+    bytes_consumed: usize,
+    // Every byte read is also appended to each active capture (see Lazy<T>)
+    captures: Vec<Vec<u8>>,
+    // Reject non-zero padding after strings and opaques
+    strict_padding: bool,
+    // When decoding from a shared buffer bytes come from here, not the reader
+    #[cfg(feature = "bytes")]
+    shared: Option<Bytes>,
 }
 
 impl<R: Read> Deserializer<R> {
     pub fn new(reader: R) -> Deserializer<R> {
         Deserializer {
             reader: reader,
-            bytes_consumed: 0
+            bytes_consumed: 0,
+            captures: Vec::new(),
+            strict_padding: false,
+            #[cfg(feature = "bytes")]
+            shared: None,
         }
     }
 
-   pub fn get_bytes_consumed(&self) -> usize {
+    /// RFC 4506 requires the padding after strings and opaques to be zero.
+    /// By default it is skipped unchecked, in strict mode non-zero padding
+    /// is a decode error.
+    pub fn strict_padding(mut self, strict: bool) -> Self {
+        self.strict_padding = strict;
+        self
+    }
+
+    /// Number of bytes consumed from the reader so far
+    pub fn position(&self) -> usize {
         self.bytes_consumed
-   }
+    }
+
+    /// Decode the next value, for reading several values back to back
+    pub fn decode<T: Deserialize>(&mut self) -> DecoderResult<T> {
+        Deserialize::deserialize(self)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // Consume the padding that brings a `len` byte item up to a multiple of 4
+    fn read_padding(&mut self, len: usize) -> DecoderResult<()> {
+        let mut padding = [0u8; 3];
+        let extra_bytes = (4 - len % 4) % 4;
+        self.read_exact(&mut padding[..extra_bytes])?;
+        if self.strict_padding && padding.iter().any(|b| *b != 0) {
+            return Err(EncoderError::Unknown(format!("non-zero XDR padding at offset {}", self.position())));
+        }
+        Ok(())
+    }
+
+    fn read_opaque(&mut self) -> DecoderResult<Vec<u8>> {
+        let count = self.read_u32::<BigEndian>()? as usize;
+        self.read_opaque_body(count)
+    }
+
+    fn read_opaque_body(&mut self, count: usize) -> DecoderResult<Vec<u8>> {
+        let mut bytes = vec![0u8; count];
+        self.read_exact(&mut bytes)?;
+        self.read_padding(count)?;
+        Ok(bytes)
+    }
+
+    fn check_end(&self, remaining: usize) -> DecoderResult<()> {
+        match remaining {
+            0 => Ok(()),
+            n => Err(EncoderError::Unknown(format!("{} trailing bytes after XDR value at offset {}", n, self.position())))
+        }
+    }
+
+    #[cfg(not(feature = "bytes"))]
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+
+    #[cfg(feature = "bytes")]
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.shared {
+            Some(ref src) => {
+                let rest = &src[self.bytes_consumed..];
+                let n = cmp::min(rest.len(), buf.len());
+                buf[..n].copy_from_slice(&rest[..n]);
+                Ok(n)
+            },
+            None => self.reader.read(buf)
+        }
+    }
+
+    // Opaque data declared with serde_xdr::shared. From a shared buffer it is
+    // handed over as a slice of that buffer, otherwise it is read as usual.
+    #[cfg(feature = "bytes")]
+    fn deserialize_shared<V: Visitor>(&mut self, visitor: V) -> DecoderResult<V::Value> {
+        let count = self.read_u32::<BigEndian>()? as usize;
+        let slice = match self.shared {
+            Some(ref src) => {
+                let start = self.bytes_consumed;
+                if src.len() - start < count {
+                    return Err(EncoderError::Io(io::Error::new(io::ErrorKind::UnexpectedEof,
+                        "opaque data runs past the end of the buffer")));
+                }
+                src.slice(start, start + count)
+            },
+            None => {
+                let bytes = self.read_opaque_body(count)?;
+                return visitor.visit_byte_buf(bytes);
+            }
+        };
+        self.bytes_consumed += count;
+        for capture in self.captures.iter_mut() {
+            capture.extend_from_slice(&slice);
+        }
+        self.read_padding(count)?;
+        visitor.visit_newtype_struct(SharedBytes(slice))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Deserializer<io::Empty> {
+    /// Decode from a shared buffer. Opaque fields declared with
+    /// `serde_xdr::shared` come out as slices of `buf` instead of copies.
+    pub fn from_shared(buf: Bytes) -> Deserializer<io::Empty> {
+        let mut de = Deserializer::new(io::empty());
+        de.shared = Some(buf);
+        de
+    }
+
+    /// Number of bytes left in the buffer
+    pub fn remaining(&self) -> usize {
+        self.shared.as_ref().map_or(0, |src| src.len() - self.bytes_consumed)
+    }
+
+    /// Fail if anything is left in the buffer
+    pub fn end(&self) -> DecoderResult<()> {
+        self.check_end(self.remaining())
+    }
+}
+
+impl<'a> Deserializer<&'a [u8]> {
+    pub fn from_slice(bytes: &'a [u8]) -> Deserializer<&'a [u8]> {
+        Deserializer::new(bytes)
+    }
+
+    /// Number of bytes left in the buffer
+    pub fn remaining(&self) -> usize {
+        self.reader.len()
+    }
+
+    /// Fail if anything is left in the buffer
+    pub fn end(&self) -> DecoderResult<()> {
+        self.check_end(self.remaining())
+    }
 }
 
 
@@ -55,31 +203,47 @@ impl<'a, R: Read> de::Deserializer for &'a mut Deserializer<R> {
     type Error = EncoderError;
 
     // Implementing all the numbers that use the simple read_TYPE syntax
-    impl_num!(u16, deserialize_u16, visit_u16, read_u16, 2);
-    impl_num!(u32, deserialize_u32, visit_u32, read_u32, 4);
-    impl_num!(u64, deserialize_u64, visit_u64, read_u64, 8);
+    impl_num!(u16, deserialize_u16, visit_u16, read_u16);
+    impl_num!(u32, deserialize_u32, visit_u32, read_u32);
+    impl_num!(u64, deserialize_u64, visit_u64, read_u64);
 
-    impl_num!(i16, deserialize_i16, visit_i16, read_i16, 2);
-    impl_num!(i32, deserialize_i32, visit_i32, read_i32, 4);
-    impl_num!(i64, deserialize_i64, visit_i64, read_i64, 8);
+    impl_num!(i16, deserialize_i16, visit_i16, read_i16);
+    impl_num!(i32, deserialize_i32, visit_i32, read_i32);
+    impl_num!(i64, deserialize_i64, visit_i64, read_i64);
 
-    impl_num!(f32, deserialize_f32, visit_f32, read_f32, 4);
-    impl_num!(f64, deserialize_f64, visit_f64, read_f64, 8);
+    impl_num!(f32, deserialize_f32, visit_f32, read_f32);
+    impl_num!(f64, deserialize_f64, visit_f64, read_f64);
 
     not_implemented!(
         deserialize_char();
         deserialize_str();
         deserialize_unit();
-        deserialize_option();
-        deserialize_bytes();
         deserialize_map();
         deserialize_unit_struct(_name: &'static str,);
-        deserialize_tuple_struct(_name: &'static str, _len: usize,);
         deserialize_tuple(_len: usize,);
         deserialize_ignored_any();
     );
 
 
+    fn deserialize_option<V>(self, visitor: V) -> DecoderResult<V::Value> where V: de::Visitor {
+        match self.read_u32::<BigEndian>()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            flag => Err(EncoderError::Unknown(format!("invalid optional data flag {}, 0 or 1 needed", flag))),
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(self, name: &'static str, len: usize, visitor: V) -> DecoderResult<V::Value> where V: de::Visitor {
+        if name == LAZY_SYMBOL {
+            return visitor.visit_seq(CaptureVisitor::new(self));
+        }
+        if name == FIXED_SYMBOL {
+            let bytes = self.read_opaque_body(len)?;
+            return visitor.visit_byte_buf(bytes);
+        }
+        Err(EncoderError::Unknown(String::from("XDR deserialize not implemented for deserialize_tuple_struct")))
+    }
+
     fn deserialize_struct_field<V>(self, visitor: V) -> DecoderResult<V::Value> where V: de::Visitor {
         self.deserialize(visitor)
     }
@@ -87,12 +251,11 @@ impl<'a, R: Read> de::Deserializer for &'a mut Deserializer<R> {
 
     fn deserialize_string<V>(self, mut visitor: V) -> DecoderResult<V::Value> where V: de::Visitor {
         let count: u32 = self.read_u32::<BigEndian>()?;
-        let extra_bytes = 4 - count % 4;
         let mut accum = String::new();
         for c in 0 .. count {
             accum.push(self.read_u8()? as char);
         }
-        self.bytes_consumed += (extra_bytes + count + 4) as usize;
+        self.read_padding(count as usize)?;
         return visitor.visit_string(accum);
     }
 
@@ -105,8 +268,14 @@ impl<'a, R: Read> de::Deserializer for &'a mut Deserializer<R> {
         }
     }
 
+    fn deserialize_bytes<V:Visitor>(self, visitor: V) ->  DecoderResult<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    // Variable length opaque data
     fn deserialize_byte_buf<V:Visitor>(self, mut visitor: V) ->  DecoderResult<V::Value> {
-        Err(EncoderError::Unknown(String::from("not done implementing")))
+        let bytes = self.read_opaque()?;
+        visitor.visit_byte_buf(bytes)
     }
 
     fn deserialize<V: Visitor>(self, mut visitor: V) -> DecoderResult<V::Value> {
@@ -123,15 +292,11 @@ impl<'a, R: Read> de::Deserializer for &'a mut Deserializer<R> {
     }
 
     fn deserialize_u8<V: Visitor>(self, mut visitor: V) -> DecoderResult<V::Value> {
-        let res = visitor.visit_u8(self.read_u8()?);
-        self.bytes_consumed += 1;
-        res
+        visitor.visit_u8(self.read_u8()?)
     }
 
     fn deserialize_i8<V: Visitor>(self, mut visitor: V) -> DecoderResult<V::Value> {
-        let res = visitor.visit_i8(self.read_i8()?);
-        self.bytes_consumed += 1;
-        res
+        visitor.visit_i8(self.read_i8()?)
     }
 
     fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], mut visitor: V) -> DecoderResult<V::Value> where V: de::Visitor {
@@ -142,6 +307,12 @@ impl<'a, R: Read> de::Deserializer for &'a mut Deserializer<R> {
     fn deserialize_newtype_struct<V>(self,
                                      name: &'static str,
                                      mut visitor: V) -> DecoderResult<V::Value> where V: de::Visitor {
+        #[cfg(feature = "bytes")]
+        {
+            if name == SHARED_SYMBOL {
+                return self.deserialize_shared(visitor);
+            }
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -156,7 +327,50 @@ impl<'a, R: Read> de::Deserializer for &'a mut Deserializer<R> {
 
 impl<R: Read> Read for Deserializer<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.reader.read(buf)
+        let n = self.read_source(buf)?;
+        self.bytes_consumed += n;
+        for capture in self.captures.iter_mut() {
+            capture.extend_from_slice(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+// Yields the value being skipped, followed by the raw bytes it was decoded from
+struct CaptureVisitor<'a, R: Read + 'a> {
+    deserializer: &'a mut Deserializer<R>,
+    captured: Option<Vec<u8>>,
+    index: usize,
+}
+
+impl<'a, R: Read + 'a> CaptureVisitor<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
+        CaptureVisitor {
+            deserializer: de,
+            captured: None,
+            index: 0,
+        }
+    }
+}
+
+impl<'a, R: Read + 'a> de::SeqVisitor for CaptureVisitor<'a, R> {
+    type Error = EncoderError;
+
+    fn visit_seed<T>(&mut self, seed: T) -> DecoderResult<Option<T::Value>> where T: de::DeserializeSeed {
+        self.index += 1;
+        match self.index {
+            1 => {
+                self.deserializer.captures.push(Vec::new());
+                let value = seed.deserialize(&mut *self.deserializer);
+                self.captured = self.deserializer.captures.pop();
+                value.map(Some)
+            },
+            2 => {
+                let bytes = ByteBuf::from(self.captured.take().unwrap_or_default());
+                seed.deserialize(bytes.into_deserializer()).map(Some)
+            },
+            _ => Ok(None)
+        }
     }
 }
 
@@ -253,16 +467,20 @@ impl<'a, R: Read + 'a> de::EnumVisitor for VariantVisitor<'a, R> {
         match self.style {
             xdr_enum_type::Union => {
                 let enum_index: u32 = Deserialize::deserialize(&mut *self.de)?;
-                let mut union_index: u32 = (self.variants.len() - 1) as u32;
-                if enum_index < self.variants.len() as u32 {
-                    let ids = self.variants.iter().map(|x| x.parse::<u32>().unwrap()).position(|x| x == enum_index);
-                    union_index = match ids {
-                        Some(idx) => idx as u32,
-                        None => {
-                            return Err(EncoderError::Unknown(format!("Bad Index for Union, the codegen annotations are broken probably" )));
-                        }
-                    };
-                }
+                // Arms are renamed to their case value, signed or not, and the
+                // default arm is the one that isn't a number
+                let is_case = |name: &str| match name.parse::<i64>() {
+                    Ok(val) => val == enum_index as i64 || val == enum_index as i32 as i64,
+                    Err(_) => false,
+                };
+                let arm = self.variants.iter().position(|name| is_case(name))
+                    .or_else(|| self.variants.iter().position(|name| name.parse::<i64>().is_err()));
+                let union_index = match arm {
+                    Some(idx) => idx as u32,
+                    None => {
+                        return Err(EncoderError::Unknown(format!("no union arm for discriminant {}", enum_index)));
+                    }
+                };
                 let mut des = union_index.into_deserializer();
                 let val: Result<V::Value, de::value::Error> = seed.deserialize(des);
                 Ok((val.unwrap(), self))
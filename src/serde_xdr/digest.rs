@@ -0,0 +1,49 @@
+//! Feed the canonical XDR encoding of a value straight into a hasher or
+//! digest, without building the encoding in memory first.
+use std::io;
+use std::hash::Hasher;
+use serde::Serialize;
+
+use error::EncoderResult;
+use super::to_writer;
+
+struct HasherWriter<'a, H: Hasher + 'a>(&'a mut H);
+
+impl<'a, H: Hasher> io::Write for HasherWriter<'a, H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct SinkWriter<F: FnMut(&[u8])>(F);
+
+impl<F: FnMut(&[u8])> io::Write for SinkWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (self.0)(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write the encoding of `value` into a `std::hash::Hasher`
+pub fn to_hasher<T, H>(value: &T, hasher: &mut H) -> EncoderResult<()>
+    where T: Serialize, H: Hasher
+{
+    to_writer(value, HasherWriter(hasher))
+}
+
+/// Hand the encoding of `value` to `sink` piece by piece, for digests that
+/// take input through an update method, e.g. `|b| sha.input(b)`
+pub fn to_sink<T, F>(value: &T, sink: F) -> EncoderResult<()>
+    where T: Serialize, F: FnMut(&[u8])
+{
+    to_writer(value, SinkWriter(sink))
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::marker::PhantomData;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor, SeqVisitor};
+use serde::bytes::{Bytes, ByteBuf};
+
+use error::{EncoderResult, DecoderResult};
+use super::{to_bytes, from_slice_exact};
+
+// Magic names recognised by the XDR (de)serializer, in the same spirit as
+// __UNION_SYMBOL__ for discriminant unions
+pub const LAZY_SYMBOL: &'static str = "__LAZY_SYMBOL__";
+pub const RAW_SYMBOL: &'static str = "__RAW_SYMBOL__";
+
+/// A value whose XDR encoding is kept as raw bytes and only decoded on demand.
+///
+/// When decoding, the bytes of the wrapped value are captured verbatim. XDR is
+/// not self describing, so the extent of the value is found by walking it once
+/// with `T`'s own `Deserialize` impl. When encoding, the captured bytes are
+/// written back out untouched, which makes `Lazy<T>` suitable for proxies
+/// that only inspect a message header and forward the rest.
+#[derive(PartialEq, Debug)]
+pub struct Lazy<T> {
+    bytes: Vec<u8>,
+    marker: PhantomData<T>,
+}
+
+impl<T> Lazy<T> {
+    /// Wrap bytes that are already the XDR encoding of a `T`
+    pub fn from_encoded(bytes: Vec<u8>) -> Lazy<T> {
+        Lazy {
+            bytes: bytes,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl<T: Serialize> Lazy<T> {
+    /// Encode `value` up front and keep only its bytes
+    pub fn new(value: &T) -> EncoderResult<Lazy<T>> {
+        let mut bytes = Vec::new();
+        try!(to_bytes(value, &mut bytes));
+        Ok(Lazy::from_encoded(bytes))
+    }
+}
+
+impl<T: Deserialize> Lazy<T> {
+    /// Decode the captured bytes into a `T`
+    pub fn decode(&self) -> DecoderResult<T> {
+        from_slice_exact(&self.bytes)
+    }
+}
+
+impl<T> Serialize for Lazy<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_newtype_struct(RAW_SYMBOL, &Bytes::from(&self.bytes))
+    }
+}
+
+impl<T: Deserialize> Deserialize for Lazy<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer {
+        // Element 0 is the value itself, element 1 the bytes it was read from
+        deserializer.deserialize_tuple_struct(LAZY_SYMBOL, 2, LazyVisitor { marker: PhantomData })
+    }
+}
+
+struct LazyVisitor<T> {
+    marker: PhantomData<T>,
+}
+
+impl<T: Deserialize> Visitor for LazyVisitor<T> {
+    type Value = Lazy<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a lazily decoded XDR value")
+    }
+
+    fn visit_seq<V>(self, mut visitor: V) -> Result<Lazy<T>, V::Error> where V: SeqVisitor {
+        // Decoding the value is only done to skip over it
+        let skipped: Option<T> = try!(visitor.visit());
+        if skipped.is_none() {
+            return Err(de::Error::invalid_length(0, &self));
+        }
+        match try!(visitor.visit::<ByteBuf>()) {
+            Some(bytes) => Ok(Lazy::from_encoded(bytes.into())),
+            None => Err(de::Error::invalid_length(1, &self)),
+        }
+    }
+}
+
+/// Bytes that are already XDR encoded and get embedded verbatim when
+/// serializing, with no length prefix or padding added.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RawXdr(pub Vec<u8>);
+
+impl From<Vec<u8>> for RawXdr {
+    fn from(bytes: Vec<u8>) -> RawXdr {
+        RawXdr(bytes)
+    }
+}
+
+impl<T> From<Lazy<T>> for RawXdr {
+    fn from(lazy: Lazy<T>) -> RawXdr {
+        RawXdr(lazy.into_bytes())
+    }
+}
+
+impl Serialize for RawXdr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_newtype_struct(RAW_SYMBOL, &Bytes::from(&self.0))
+    }
+}
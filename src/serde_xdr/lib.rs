@@ -1,18 +1,34 @@
 #![cfg_attr(test, feature(custom_attribute, custom_derive, plugin))]
 
+#[cfg_attr(feature = "bytes", macro_use)]
 extern crate serde;
 extern crate byteorder;
+extern crate rustc_serialize;
+#[cfg(feature = "bytes")]
+extern crate bytes;
 
 pub mod serializer;
 pub mod deserializer;
 pub mod error;
+pub mod lazy;
+pub mod opaque;
+pub mod array;
+pub mod digest;
+pub mod text;
+#[cfg(feature = "bytes")]
+pub mod shared;
 
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use serde::{Serialize, Deserialize};
+#[cfg(feature = "bytes")]
+use bytes::{Bytes, BytesMut};
 pub use self::error::{EncoderError, DecoderResult, EncoderResult};
 
 pub use self::serializer::Serializer;
 pub use self::deserializer::Deserializer;
+pub use self::lazy::{Lazy, RawXdr};
+pub use self::digest::{to_hasher, to_sink};
+pub use self::text::{to_base64, from_base64, to_hex, from_hex};
 
 pub fn to_bytes<T>(value: &T, buf: &mut Vec<u8>) -> EncoderResult<()>
     where T: Serialize
@@ -22,22 +38,84 @@ pub fn to_bytes<T>(value: &T, buf: &mut Vec<u8>) -> EncoderResult<()>
     Ok(())
 }
 
+/// Encode a value straight into any writer
+pub fn to_writer<T, W>(value: &T, writer: W) -> EncoderResult<()>
+    where T: Serialize, W: Write
+{
+    let mut ser = Serializer::new(writer);
+    try!(value.serialize(&mut ser));
+    Ok(())
+}
+
 pub fn from_reader<T: Deserialize, R: Read>(reader: R) -> DecoderResult<(T, usize)> {
     let mut de = Deserializer::new(reader);
     let value = try!(Deserialize::deserialize(&mut de));
-    Ok((value, de.get_bytes_consumed()))
+    Ok((value, de.position()))
 }
 
+/// Decode a value from the start of `v`, returning it with the number of
+/// bytes it took up. Anything after that is left for the caller.
 pub fn from_bytes<T: Deserialize>(v: &[u8]) -> DecoderResult<(T, usize)> {
     from_reader(v)
 }
 
+/// Encode a value onto the end of a `BytesMut`
+#[cfg(feature = "bytes")]
+pub fn to_bytes_mut<T>(value: &T, buf: &mut BytesMut) -> EncoderResult<()>
+    where T: Serialize
+{
+    let mut ser = Serializer::new(shared::BytesMutWriter(buf));
+    try!(value.serialize(&mut ser));
+    Ok(())
+}
+
+/// Encode a value as a chain of buffers. Opaque fields declared with
+/// `serde_xdr::shared` are chained in as they are rather than copied.
+#[cfg(feature = "bytes")]
+pub fn to_chain<T>(value: &T) -> EncoderResult<Vec<Bytes>>
+    where T: Serialize
+{
+    let mut ser = Serializer::new(Vec::new()).chained();
+    try!(value.serialize(&mut ser));
+    let (head, chunks) = ser.into_chunks();
+    let head = Bytes::from(head);
+
+    let mut chain = Vec::new();
+    let mut last = 0;
+    for (offset, bytes) in chunks {
+        chain.push(head.slice(last, offset));
+        chain.push(bytes);
+        last = offset;
+    }
+    chain.push(head.slice_from(last));
+    chain.retain(|b| !b.is_empty());
+    Ok(chain)
+}
+
+/// Decode a value from the start of a shared buffer, like `from_bytes`.
+/// Opaque fields declared with `serde_xdr::shared` are slices of `buf`.
+#[cfg(feature = "bytes")]
+pub fn from_shared<T: Deserialize>(buf: &Bytes) -> DecoderResult<(T, usize)> {
+    let mut de = Deserializer::from_shared(buf.clone());
+    let value = try!(Deserialize::deserialize(&mut de));
+    Ok((value, de.position()))
+}
+
+/// Decode a value that must take up all of `v`, trailing bytes are an error
+pub fn from_slice_exact<T: Deserialize>(v: &[u8]) -> DecoderResult<T> {
+    let mut de = Deserializer::from_slice(v);
+    let value = try!(Deserialize::deserialize(&mut de));
+    try!(de.end());
+    Ok(value)
+}
+
 #[macro_export]
 macro_rules! xdr_enum {
-    ($name:ident { $($variant:ident = $value:expr, )* }) => {
+    ($(#[$attr:meta])* $name:ident { $($(#[$variant_attr:meta])* $variant:ident = $value:expr, )* }) => {
+        $(#[$attr])*
         #[derive(Clone, Copy, Debug, Eq, PartialEq)]
         pub enum $name {
-            $($variant = $value,)*
+            $($(#[$variant_attr])* $variant = $value,)*
         }
 
         impl ::serde::Serialize for $name {
@@ -0,0 +1,61 @@
+//! Variable length opaque data, for use as `#[serde(with = "serde_xdr::opaque")]`
+//! on `Vec<u8>` fields.
+//!
+//! Without it a `Vec<u8>` goes through serde's sequence path, which writes the
+//! bytes without the trailing padding XDR requires.
+use serde::{Serializer, Deserialize, Deserializer};
+use serde::bytes::ByteBuf;
+
+pub fn serialize<S>(bytes: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    serializer.serialize_bytes(bytes)
+}
+
+pub fn deserialize<D>(deserializer: D) -> Result<Vec<u8>, D::Error> where D: Deserializer {
+    let buf: ByteBuf = try!(Deserialize::deserialize(deserializer));
+    Ok(buf.into())
+}
+
+// Magic name recognised by the XDR (de)serializer for fixed length opaque data
+pub const FIXED_SYMBOL: &'static str = "__FIXED_OPAQUE_SYMBOL__";
+
+/// Fixed length opaque data, for use as `#[serde(with = "serde_xdr::opaque::fixed")]`
+/// on `[u8; N]` fields. The bytes are written as they are, padded to a multiple
+/// of four with no length in front.
+pub mod fixed {
+    use std::fmt;
+    use serde::{Serializer, Deserializer};
+    use serde::de::{self, Visitor};
+    use serde::bytes::Bytes;
+    use super::FIXED_SYMBOL;
+
+    pub fn serialize<S, const N: usize>(bytes: &[u8; N], serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_newtype_struct(FIXED_SYMBOL, &Bytes::from(&bytes[..]))
+    }
+
+    pub fn deserialize<D, const N: usize>(deserializer: D) -> Result<[u8; N], D::Error> where D: Deserializer {
+        deserializer.deserialize_tuple_struct(FIXED_SYMBOL, N, FixedVisitor::<N>)
+    }
+
+    struct FixedVisitor<const N: usize>;
+
+    impl<const N: usize> Visitor for FixedVisitor<N> {
+        type Value = [u8; N];
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "{} bytes of fixed length opaque data", N)
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<[u8; N], E> where E: de::Error {
+            if v.len() != N {
+                return Err(E::invalid_length(v.len(), &self));
+            }
+            let mut bytes = [0u8; N];
+            bytes.copy_from_slice(v);
+            Ok(bytes)
+        }
+
+        fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<[u8; N], E> where E: de::Error {
+            self.visit_bytes(&v)
+        }
+    }
+}
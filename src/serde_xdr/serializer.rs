@@ -1,12 +1,18 @@
 use std::fmt;
 use std::error;
 use std::result;
-use std::io;
+use std::io::{self, Write};
 use serde::ser;
 use serde::ser::Serialize;
 use byteorder::{BigEndian, WriteBytesExt}; // This is unfair for the VAX
 
 use error::{EncoderResult, EncoderError};
+use lazy::RAW_SYMBOL;
+use opaque::FIXED_SYMBOL;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+#[cfg(feature = "bytes")]
+use shared::{self, SHARED_SYMBOL};
 use super::to_bytes;
 
 macro_rules! not_implemented {
@@ -19,19 +25,86 @@ macro_rules! not_implemented {
 
 pub struct Serializer<W> {
     writer: W,
+    // Counted as bytes go into the writer, see the Write impl below
+    bytes_written: usize,
+    // Set while serializing a RawXdr/Lazy payload, bytes are written verbatim
+    raw: bool,
+    // Shared opaque payloads left out of the writer, with the offset they go at
+    #[cfg(feature = "bytes")]
+    chunks: Option<Vec<(usize, Bytes)>>,
 }
 
 impl<W: io::Write> Serializer<W> {
     pub fn new(writer: W) -> Self {
         Serializer {
             writer: writer,
+            bytes_written: 0,
+            raw: false,
+            #[cfg(feature = "bytes")]
+            chunks: None,
         }
     }
 
+    /// Keep `serde_xdr::shared` payloads out of the writer so they can be
+    /// chained after it without copying, see `into_chunks`
+    #[cfg(feature = "bytes")]
+    pub fn chained(mut self) -> Self {
+        self.chunks = Some(Vec::new());
+        self
+    }
+
+    /// The writer along with the shared payloads it is missing, each paired
+    /// with the offset in the written output it belongs at
+    #[cfg(feature = "bytes")]
+    pub fn into_chunks(self) -> (W, Vec<(usize, Bytes)>) {
+        (self.writer, self.chunks.unwrap_or_default())
+    }
+
+    /// Number of bytes written so far
+    pub fn position(&self) -> usize {
+        self.bytes_written
+    }
+
     pub fn into_inner(self) -> W {
         self.writer
     }
 
+    // Zero padding to bring a `len` byte item up to a multiple of 4
+    fn write_padding(&mut self, len: usize) -> EncoderResult<()> {
+        let padding = [0u8; 3];
+        let extra_bytes = (4 - len % 4) % 4;
+        self.write_all(&padding[..extra_bytes]).map_err(From::from)
+    }
+
+    #[cfg(feature = "bytes")]
+    fn serialize_shared<T: ?Sized>(&mut self, value: &T) -> EncoderResult<()> where T: ser::Serialize {
+        match (shared::as_shared(value), self.chunks.is_some()) {
+            (Some(shared), true) => {
+                let bytes = shared.0.clone();
+                try!(self.write_u32::<BigEndian>(bytes.len() as u32));
+                let offset = self.bytes_written;
+                let len = bytes.len();
+                if let Some(ref mut chunks) = self.chunks {
+                    chunks.push((offset, bytes));
+                }
+                self.write_padding(len)
+            },
+            _ => value.serialize(self)
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for Serializer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.bytes_written += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
 }
 
 #[derive(Debug)]
@@ -53,46 +126,51 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     type SerializeStructVariant = Compound<'a, W>;
 
     fn serialize_i8(self, value: i8) -> EncoderResult<()> {
-        self.writer.write_i8(value).map_err(From::from)
+        self.write_i8(value).map_err(From::from)
 	}
 
     fn serialize_i16(self, value: i16) -> EncoderResult<()> {
-        self.writer.write_i16::<BigEndian>(value).map_err(From::from)
+        self.write_i16::<BigEndian>(value).map_err(From::from)
 	}
 
     fn serialize_i32(self, value: i32) -> EncoderResult<()> {
-        self.writer.write_i32::<BigEndian>(value).map_err(From::from)
+        self.write_i32::<BigEndian>(value).map_err(From::from)
 	}
 
     fn serialize_i64(self, value: i64) -> EncoderResult<()> {
-        self.writer.write_i64::<BigEndian>(value).map_err(From::from)
+        self.write_i64::<BigEndian>(value).map_err(From::from)
 	}
 
     fn serialize_u8(self, value: u8) -> EncoderResult<()> {
-        self.writer.write_u8(value).map_err(From::from)
+        self.write_u8(value).map_err(From::from)
 	}
 
     fn serialize_u16(self, value: u16) -> EncoderResult<()> {
-        self.writer.write_u16::<BigEndian>(value).map_err(From::from)
+        self.write_u16::<BigEndian>(value).map_err(From::from)
 	}
 
     fn serialize_u32(self, value: u32) -> EncoderResult<()> {
-        self.writer.write_u32::<BigEndian>(value).map_err(From::from)
+        self.write_u32::<BigEndian>(value).map_err(From::from)
 	}
 
     fn serialize_u64(self, value: u64) -> EncoderResult<()> {
-        self.writer.write_u64::<BigEndian>(value).map_err(From::from)
+        self.write_u64::<BigEndian>(value).map_err(From::from)
 	}
 
     not_implemented!(
         serialize_f32(val: f32,);
         serialize_f64(val: f64,);
-        serialize_none();
         serialize_unit_struct(_name: &'static str,);
     );
 
     fn serialize_bytes(self, val: &[u8]) -> EncoderResult<()> {
-        Err(EncoderError::Unknown(String::from("Not yet implemented")))
+        if self.raw {
+            return self.write_all(val).map_err(From::from);
+        }
+        // Variable length opaque data
+        try!(self.serialize_u32(val.len() as u32));
+        try!(self.write_all(val));
+        self.write_padding(val.len())
     }
 
     fn serialize_char(self, val: char) -> EncoderResult<()> {
@@ -101,31 +179,56 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
 
     fn serialize_str(self, val: &str) -> EncoderResult<()> {
         self.serialize_u32((val.len() as u32));
-        let extra_bytes = 4 - val.len() % 4;
         for c in val.chars() {
             self.serialize_char(c);
         }
         // Spec needs padding to multiple of 4
-        for _ in 0 .. extra_bytes {
-            self.serialize_u8(0 as u8);
-        }
-        Ok(())
+        self.write_padding(val.len())
     }
     fn serialize_bool(self, v: bool) -> EncoderResult<()> {
-        self.writer.write_u8(if v {1} else {0}).map_err(From::from)
+        self.write_u8(if v {1} else {0}).map_err(From::from)
     }
 
     fn serialize_unit(self) -> EncoderResult<()> {
         Ok(())
 	}
 
+    // Optional data (`T *x`) is a bool saying whether the value follows
+    fn serialize_none(self) -> EncoderResult<()> {
+        self.write_u32::<BigEndian>(0).map_err(From::from)
+    }
+
     fn serialize_some<T: ?Sized>(self, value: &T) -> EncoderResult<()> where T: ser::Serialize, {
-        Err(EncoderError::Unknown(String::from("Not yet implemented")))
+        try!(self.write_u32::<BigEndian>(1));
+        value.serialize(self)
     }
 
 
-    fn serialize_newtype_struct<T: ?Sized>( self, _name: &'static str, value: &T) -> EncoderResult<()> where T: ser::Serialize {
-        Err(EncoderError::Unknown(String::from("Not yet implemented")))
+    fn serialize_newtype_struct<T: ?Sized>( self, name: &'static str, value: &T) -> EncoderResult<()> where T: ser::Serialize {
+        #[cfg(feature = "bytes")]
+        {
+            if name == SHARED_SYMBOL {
+                return self.serialize_shared(value);
+            }
+        }
+        if name == RAW_SYMBOL {
+            self.raw = true;
+            let res = value.serialize(&mut *self);
+            self.raw = false;
+            return res;
+        }
+        if name == FIXED_SYMBOL {
+            // Fixed length opaque data is raw bytes plus padding
+            let start = self.bytes_written;
+            self.raw = true;
+            let res = value.serialize(&mut *self);
+            self.raw = false;
+            try!(res);
+            let len = self.bytes_written - start;
+            return self.write_padding(len);
+        }
+        // Other newtypes encode as whatever they wrap
+        value.serialize(self)
     }
 
 
@@ -167,7 +270,8 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_struct_variant(self, _name: &'static str, variant_idx: usize, variant: &'static str, len: usize) -> EncoderResult<Self::SerializeStructVariant> {
-        let descr_idx = variant.parse::<u32>();
+        // Arms are renamed to their case value, which may be negative
+        let descr_idx = variant.parse::<u32>().or_else(|_| variant.parse::<i32>().map(|idx| idx as u32));
         match descr_idx {
             Ok(idx) => {
                 self.serialize_u32(idx);
@@ -0,0 +1,137 @@
+//! Zero-copy opaque data backed by `bytes::Bytes`, enabled with the `bytes`
+//! feature. Use as `#[serde(with = "serde_xdr::shared")]` on `Bytes` fields.
+//!
+//! Decoding with `from_shared` gives each such field a slice of the input
+//! buffer, and encoding with `to_chain` leaves the payloads out of the
+//! encoded header so they can be chained after it without copying. With any
+//! other entry point they behave like `serde_xdr::opaque`.
+use std::io;
+use std::fmt;
+use std::mem;
+use std::ptr;
+use std::any::TypeId;
+use std::marker::PhantomData;
+use bytes::{Bytes, BytesMut};
+use serde::{Serialize, Serializer, Deserializer};
+use serde::de::{self, Visitor};
+
+use error::EncoderError;
+
+pub const SHARED_SYMBOL: &'static str = "__SHARED_SYMBOL__";
+
+// serde's data model has no way to pass a `Bytes` along, so the XDR
+// (de)serializer and the functions below pass this through the
+// SHARED_SYMBOL newtype instead. Everywhere else it is plain opaque data.
+#[doc(hidden)]
+pub struct SharedBytes(pub Bytes);
+
+impl Serialize for SharedBytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl Deserializer for SharedBytes {
+    type Error = EncoderError;
+
+    fn deserialize<V>(self, visitor: V) -> Result<V::Value, EncoderError> where V: Visitor {
+        visitor.visit_bytes(&self.0)
+    }
+
+    forward_to_deserialize! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit option
+        seq seq_fixed_size bytes byte_buf map unit_struct newtype_struct
+        tuple_struct struct struct_field tuple enum ignored_any
+    }
+}
+
+// TypeId::of only takes 'static types and serde's type parameters are not
+// declared 'static. Lifetimes have no part in a TypeId, so erasing them
+// behind a trait object to ask for it is sound.
+trait TypeIdOf {
+    fn type_id_of(&self) -> TypeId where Self: 'static;
+}
+
+impl<T: ?Sized> TypeIdOf for PhantomData<T> {
+    fn type_id_of(&self) -> TypeId where Self: 'static {
+        TypeId::of::<T>()
+    }
+}
+
+fn is_shared<T: ?Sized>() -> bool {
+    let marker = PhantomData::<T>;
+    let erased: &dyn TypeIdOf = &marker;
+    let erased: &(dyn TypeIdOf + 'static) = unsafe { mem::transmute(erased) };
+    erased.type_id_of() == TypeId::of::<SharedBytes>()
+}
+
+/// The payload behind a value handed to the SHARED_SYMBOL newtype, if it
+/// came from `serialize` below
+#[doc(hidden)]
+pub fn as_shared<T: ?Sized>(value: &T) -> Option<&SharedBytes> {
+    if is_shared::<T>() {
+        Some(unsafe { &*(value as *const T as *const SharedBytes) })
+    } else {
+        None
+    }
+}
+
+// The shared slice passed to SharedVisitor as its newtype deserializer
+fn into_shared<D>(deserializer: D) -> Result<Bytes, D> {
+    if is_shared::<D>() {
+        let deserializer = mem::ManuallyDrop::new(deserializer);
+        let shared = unsafe { ptr::read(&*deserializer as *const D as *const SharedBytes) };
+        Ok(shared.0)
+    } else {
+        Err(deserializer)
+    }
+}
+
+pub fn serialize<S>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    serializer.serialize_newtype_struct(SHARED_SYMBOL, &SharedBytes(bytes.clone()))
+}
+
+pub fn deserialize<D>(deserializer: D) -> Result<Bytes, D::Error> where D: Deserializer {
+    deserializer.deserialize_newtype_struct(SHARED_SYMBOL, SharedVisitor)
+}
+
+struct SharedVisitor;
+
+impl Visitor for SharedVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("opaque data")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Bytes, E> where E: de::Error {
+        Ok(Bytes::from(v))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Bytes, E> where E: de::Error {
+        Ok(Bytes::from(v))
+    }
+
+    // A slice of the shared buffer comes wrapped in SharedBytes
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Bytes, D::Error> where D: Deserializer {
+        match into_shared(deserializer) {
+            Ok(bytes) => Ok(bytes),
+            Err(deserializer) => deserializer.deserialize_byte_buf(self),
+        }
+    }
+}
+
+// Appends to a BytesMut, growing it as needed
+#[doc(hidden)]
+pub struct BytesMutWriter<'a>(pub &'a mut BytesMut);
+
+impl<'a> io::Write for BytesMutWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
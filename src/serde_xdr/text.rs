@@ -0,0 +1,36 @@
+//! Base64 and hex forms of XDR encoded values, for embedding them in JSON
+//! configs and logs.
+use serde::{Serialize, Deserialize};
+use rustc_serialize::base64::{ToBase64, FromBase64, STANDARD};
+use rustc_serialize::hex::{ToHex, FromHex};
+
+use error::{EncoderResult, DecoderResult, EncoderError};
+use super::{to_bytes, from_slice_exact};
+
+pub fn to_base64<T: Serialize>(value: &T) -> EncoderResult<String> {
+    let mut buf = Vec::new();
+    try!(to_bytes(value, &mut buf));
+    Ok(buf.to_base64(STANDARD))
+}
+
+/// Decode a value from base64, the decoded bytes must hold exactly one value
+pub fn from_base64<T: Deserialize>(s: &str) -> DecoderResult<T> {
+    let buf = try!(s.from_base64().map_err(|e| {
+        EncoderError::Unknown(format!("invalid base64 XDR: {}", e))
+    }));
+    from_slice_exact(&buf)
+}
+
+pub fn to_hex<T: Serialize>(value: &T) -> EncoderResult<String> {
+    let mut buf = Vec::new();
+    try!(to_bytes(value, &mut buf));
+    Ok(buf.to_hex())
+}
+
+/// Decode a value from hex, the decoded bytes must hold exactly one value
+pub fn from_hex<T: Deserialize>(s: &str) -> DecoderResult<T> {
+    let buf = try!(s.from_hex().map_err(|e| {
+        EncoderError::Unknown(format!("invalid hex XDR: {}", e))
+    }));
+    from_slice_exact(&buf)
+}
@@ -0,0 +1,315 @@
+// Typed syntax tree for the XDR language (RFC 4506) and the RPC program
+// definitions of RFC 5531, as produced by `parser::parse`.
+use std::fmt;
+
+/// A range of preprocessed source, as byte offsets. `Preprocessed::diagnostic`
+/// turns one back into a file, line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start: start, end: end }
+    }
+
+    /// The smallest span covering both
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    Bool,
+    Int,
+    Uint,
+    Hyper,
+    Uhyper,
+    Float,
+    Double,
+    Quadruple,
+}
+
+impl Type {
+    /// The type's name in XDR, `unsigned int` for `unsigned` too
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Type::Bool => "bool",
+            Type::Int => "int",
+            Type::Uint => "unsigned int",
+            Type::Hyper => "hyper",
+            Type::Uhyper => "unsigned hyper",
+            Type::Float => "float",
+            Type::Double => "double",
+            Type::Quadruple => "quadruple",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    And,
+    Or,
+    Xor,
+}
+
+/// A constant expression. Operators applied only to numbers are folded while
+/// parsing, what's left refers to named constants.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Value {
+    // Wide enough for both `hyper` and `unsigned hyper` values
+    Constant(i128, Span),
+    Named(Ident),
+    Unary{op: UnaryOp, operand: Box<Value>, span: Span},
+    Binary{op: BinaryOp, lhs: Box<Value>, rhs: Box<Value>, span: Span},
+}
+
+impl Value {
+    pub fn span(&self) -> Span {
+        match *self {
+            Value::Constant(_, span) => span,
+            Value::Named(ref id) => id.span,
+            Value::Unary{span, ..} | Value::Binary{span, ..} => span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeSpec {
+    Primitive(Type, Span),
+    /// A typedef, struct, enum or union defined elsewhere. `struct foo` and
+    /// friends end up here too.
+    Named(Ident),
+    Enum(EnumBody),
+    Struct(StructBody),
+    Union(Box<UnionBody>),
+}
+
+impl TypeSpec {
+    pub fn span(&self) -> Span {
+        match *self {
+            TypeSpec::Primitive(_, span) => span,
+            TypeSpec::Named(ref id) => id.span,
+            TypeSpec::Enum(ref body) => body.span,
+            TypeSpec::Struct(ref body) => body.span,
+            TypeSpec::Union(ref body) => body.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EnumVariant {
+    pub id: Ident,
+    /// Required by the RFC, but rpcgen lets it be left out
+    pub value: Option<Value>,
+    pub span: Span,
+    /// Leading and trailing comments, see `Definition::comments`
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EnumBody {
+    pub variants: Vec<EnumVariant>,
+    pub span: Span,
+    /// Comments after the last variant, before the `}`
+    pub end_comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StructBody {
+    pub fields: Vec<Declaration>,
+    pub span: Span,
+    /// Comments after the last field, before the `}`
+    pub end_comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnionCase {
+    /// Every `case` label sharing this arm
+    pub values: Vec<Value>,
+    pub decl: Declaration,
+    pub span: Span,
+    /// Leading and trailing comments, see `Definition::comments`
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnionBody {
+    pub discriminant: Declaration,
+    pub cases: Vec<UnionCase>,
+    pub default: Option<Declaration>,
+    pub span: Span,
+    /// Comments after the last arm, before the `}`
+    pub end_comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeclarationKind {
+    Void,
+    /// `T id`
+    Scalar{ty: TypeSpec, id: Ident},
+    /// `T id[size]`
+    Array{ty: TypeSpec, id: Ident, size: Value},
+    /// `T id<size>`
+    VarArray{ty: TypeSpec, id: Ident, size: Option<Value>},
+    /// `opaque id[size]`
+    Opaque{id: Ident, size: Value},
+    /// `opaque id<size>`
+    VarOpaque{id: Ident, size: Option<Value>},
+    /// `string id<size>`
+    String{id: Ident, size: Option<Value>},
+    /// `T *id`, optional data
+    Pointer{ty: TypeSpec, id: Ident},
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Declaration {
+    pub kind: DeclarationKind,
+    pub span: Span,
+    /// Leading and trailing comments, see `Definition::comments`
+    pub comments: Vec<String>,
+}
+
+impl Declaration {
+    /// The declared name, `void` has none
+    pub fn id(&self) -> Option<&Ident> {
+        match self.kind {
+            DeclarationKind::Void => None,
+            DeclarationKind::Scalar{ref id, ..} |
+            DeclarationKind::Array{ref id, ..} |
+            DeclarationKind::VarArray{ref id, ..} |
+            DeclarationKind::Opaque{ref id, ..} |
+            DeclarationKind::VarOpaque{ref id, ..} |
+            DeclarationKind::String{ref id, ..} |
+            DeclarationKind::Pointer{ref id, ..} => Some(id),
+        }
+    }
+
+    /// The element type, for declarations that name one
+    pub fn type_spec(&self) -> Option<&TypeSpec> {
+        match self.kind {
+            DeclarationKind::Scalar{ref ty, ..} |
+            DeclarationKind::Array{ref ty, ..} |
+            DeclarationKind::VarArray{ref ty, ..} |
+            DeclarationKind::Pointer{ref ty, ..} => Some(ty),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Procedure {
+    pub id: Ident,
+    /// `None` for `void`
+    pub return_type: Option<TypeSpec>,
+    /// Empty for `void`, more than one with rpcgen's newstyle `-N`
+    pub arg_types: Vec<TypeSpec>,
+    pub number: i128,
+    pub span: Span,
+    /// Leading and trailing comments, see `Definition::comments`
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Version {
+    pub id: Ident,
+    pub procedures: Vec<Procedure>,
+    pub number: i128,
+    pub span: Span,
+    /// Leading and trailing comments, see `Definition::comments`
+    pub comments: Vec<String>,
+    /// Comments after the last procedure, before the `}`
+    pub end_comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Program {
+    pub id: Ident,
+    pub versions: Vec<Version>,
+    pub number: i128,
+    pub span: Span,
+    /// Leading and trailing comments, see `Definition::comments`
+    pub comments: Vec<String>,
+    /// Comments after the last version, before the `}`
+    pub end_comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DefinitionKind {
+    Const{id: Ident, value: Value},
+    Typedef(Declaration),
+    Enum{id: Ident, body: EnumBody},
+    Struct{id: Ident, body: StructBody},
+    Union{id: Ident, body: UnionBody},
+    Program(Program),
+    Namespace{id: Ident, programs: Vec<Program>},
+    /// A `//` or `/* */` comment between definitions, not attached to one
+    Comment(String),
+    /// A `%` line, without the `%`
+    Snippet(String),
+    /// A `#` preprocessor line, without the `#`. Only found by `parse`, which
+    /// reads a file as it is instead of preprocessing it first.
+    Directive(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Definition {
+    pub kind: DefinitionKind,
+    pub span: Span,
+    /// Comments on the lines just before it, then one after it on its last
+    /// line, as written between the comment markers. A program keeps its own
+    /// on the `Program`.
+    pub comments: Vec<String>,
+}
+
+impl Definition {
+    /// The name this definition introduces, if any
+    pub fn id(&self) -> Option<&Ident> {
+        match self.kind {
+            DefinitionKind::Const{ref id, ..} |
+            DefinitionKind::Enum{ref id, ..} |
+            DefinitionKind::Struct{ref id, ..} |
+            DefinitionKind::Union{ref id, ..} |
+            DefinitionKind::Namespace{ref id, ..} => Some(id),
+            DefinitionKind::Typedef(ref decl) => decl.id(),
+            DefinitionKind::Program(ref prog) => Some(&prog.id),
+            DefinitionKind::Comment(_) |
+            DefinitionKind::Snippet(_) |
+            DefinitionKind::Directive(_) => None,
+        }
+    }
+}
+
+/// Everything defined in one preprocessed file, in source order
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Specification {
+    pub definitions: Vec<Definition>,
+}
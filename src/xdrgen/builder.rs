@@ -0,0 +1,284 @@
+// Generate Rust from .x files in a build script, the way the `xdrgen`
+// binary does from the command line.
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use code_writer::CodeWriter;
+use codegen::CodeGen;
+use doc;
+use ir;
+use parser::ParseError;
+use preprocessor::{Preprocessed, Preprocessor};
+
+#[derive(Debug)]
+pub enum BuildError {
+    Io(io::Error),
+    /// Problems in the .x files, with where they are
+    Parse(ParseError),
+    /// Neither `out_dir` nor the `OUT_DIR` Cargo sets for build scripts
+    NoOutDir,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::Io(ref e) => write!(f, "{}", e),
+            BuildError::Parse(ref e) => write!(f, "{}", e),
+            BuildError::NoOutDir => f.write_str("no output directory, set one with `out_dir` \
+                                                 or run from a build script"),
+        }
+    }
+}
+
+impl error::Error for BuildError {
+    fn description(&self) -> &str {
+        match *self {
+            BuildError::Io(_) => "i/o error",
+            BuildError::Parse(_) => "invalid XDR",
+            BuildError::NoOutDir => "no output directory",
+        }
+    }
+}
+
+impl From<io::Error> for BuildError {
+    fn from(e: io::Error) -> BuildError {
+        BuildError::Io(e)
+    }
+}
+
+impl From<ParseError> for BuildError {
+    fn from(e: ParseError) -> BuildError {
+        BuildError::Parse(e)
+    }
+}
+
+/// The generated files, as source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generated {
+    pub prot: String,
+    pub codec: String,
+    pub service: String,
+    /// The inputs and every file they `#include`, the generated code
+    /// depends on all of them
+    pub sources: Vec<String>,
+}
+
+/// Generate prot.rs, codec.rs and service.rs from a build script:
+///
+/// ```no_run
+/// extern crate xdrgen;
+///
+/// fn main() {
+///     xdrgen::Builder::new()
+///         .input("proto/exampledbd.x")
+///         .include_path("proto/include")
+///         .generate()
+///         .unwrap();
+/// }
+/// ```
+///
+/// The files land in `OUT_DIR` unless `out_dir` says otherwise, ready for
+/// `include!(concat!(env!("OUT_DIR"), "/prot.rs"))` in a module named
+/// after `module_path`.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    inputs: Vec<PathBuf>,
+    preprocessor: Preprocessor,
+    out_dir: Option<PathBuf>,
+    module_path: Option<String>,
+    service_path: Option<String>,
+    opaque_bytes: bool,
+    snippet_prefix: Option<String>,
+    cargo_metadata: bool,
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder {
+            inputs: Vec::new(),
+            preprocessor: Preprocessor::new(),
+            out_dir: None,
+            module_path: None,
+            service_path: None,
+            opaque_bytes: false,
+            snippet_prefix: None,
+            cargo_metadata: true,
+        }
+    }
+
+    /// Add a .x file to generate code for
+    pub fn input<P: AsRef<Path>>(&mut self, path: P) -> &mut Builder {
+        self.inputs.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Search `path` for `#include`d files, as `-I` does
+    pub fn include_path<P: AsRef<Path>>(&mut self, path: P) -> &mut Builder {
+        self.preprocessor.include_path(path);
+        self
+    }
+
+    /// Predefine a preprocessor macro, as `-D NAME=VALUE` does
+    pub fn define(&mut self, name: &str, value: &str) -> &mut Builder {
+        self.preprocessor.define(name, value);
+        self
+    }
+
+    /// Preprocess the inputs with `preprocessor`, replacing the include
+    /// paths and macros given so far
+    pub fn preprocessor(&mut self, preprocessor: Preprocessor) -> &mut Builder {
+        self.preprocessor = preprocessor;
+        self
+    }
+
+    /// Where to write the generated files, `OUT_DIR` by default
+    pub fn out_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Builder {
+        self.out_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// The module holding `prot`, `codec` and `service`, which the
+    /// generated files refer to each other through. `xdrgen` by default.
+    pub fn module_path(&mut self, path: &str) -> &mut Builder {
+        self.module_path = Some(path.to_string());
+        self
+    }
+
+    /// The module with the hand written `<Program>Service` structs that
+    /// service.rs dispatches to. `service` by default.
+    pub fn service_path(&mut self, path: &str) -> &mut Builder {
+        self.service_path = Some(path.to_string());
+        self
+    }
+
+    /// Generate `bytes::Bytes` for `opaque<>`, as `--opaque-bytes` does
+    pub fn opaque_bytes(&mut self, enabled: bool) -> &mut Builder {
+        self.opaque_bytes = enabled;
+        self
+    }
+
+    /// Copy `%` lines starting with `prefix` into prot.rs, `#[rust]` by default
+    pub fn snippet_prefix(&mut self, prefix: &str) -> &mut Builder {
+        self.snippet_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Print `cargo:rerun-if-changed` for every input and included file
+    /// when generating, on by default
+    pub fn cargo_metadata(&mut self, enabled: bool) -> &mut Builder {
+        self.cargo_metadata = enabled;
+        self
+    }
+
+    /// Preprocess, parse and generate code for the inputs without writing
+    /// anything. Nothing is generated unless every input is valid.
+    pub fn compile(&self) -> Result<Generated, BuildError> {
+        let (units, mut sources) = try!(self.preprocess());
+
+        let mut prot = Vec::new();
+        let mut codec = Vec::new();
+        let mut service = Vec::new();
+        {
+            let mut types_wr = CodeWriter::new(&mut prot);
+            let mut codec_wr = CodeWriter::new(&mut codec);
+            let mut service_wr = CodeWriter::new(&mut service);
+
+            let mut cg = CodeGen::new(&mut types_wr, &mut codec_wr, &mut service_wr);
+            cg.opaque_as_bytes(self.opaque_bytes);
+            if let Some(ref prefix) = self.snippet_prefix {
+                cg.snippet_prefix(prefix);
+            }
+            if let Some(ref path) = self.module_path {
+                cg.module_path(path);
+            }
+            if let Some(ref path) = self.service_path {
+                cg.service_path(path);
+            }
+            try!(cg.compile(&units, false));
+        }
+
+        let mut seen = Vec::new();
+        sources.retain(|file| if seen.contains(file) {
+            false
+        } else {
+            seen.push(file.clone());
+            true
+        });
+        Ok(Generated {
+            prot: String::from_utf8_lossy(&prot).into_owned(),
+            codec: String::from_utf8_lossy(&codec).into_owned(),
+            service: String::from_utf8_lossy(&service).into_owned(),
+            sources: sources,
+        })
+    }
+
+    /// The resolved schema of the inputs as JSON, in the format the `ir`
+    /// module documents
+    pub fn ir(&self) -> Result<String, BuildError> {
+        let (units, _) = try!(self.preprocess());
+        Ok(try!(ir::emit(&units)))
+    }
+
+    /// Reference documentation for the inputs, as Markdown or HTML
+    pub fn doc(&self, format: doc::Format) -> Result<String, BuildError> {
+        let (units, _) = try!(self.preprocess());
+        Ok(try!(doc::generate(&units, format)))
+    }
+
+    // Preprocess every input, returning them with the files they read
+    fn preprocess(&self) -> Result<(Vec<Preprocessed>, Vec<String>), BuildError> {
+        let mut units = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut sources = Vec::new();
+        for input in self.inputs.iter() {
+            let file = input.to_string_lossy().into_owned();
+            let mut source = String::new();
+            let read = File::open(input).and_then(|mut fin| fin.read_to_string(&mut source));
+            if let Err(e) = read {
+                return Err(BuildError::Io(io::Error::new(e.kind(), format!("{}: {}", file, e))));
+            }
+            sources.push(file.clone());
+            match self.preprocessor.preprocess(&file, &source) {
+                Ok(unit) => {
+                    sources.extend(unit.includes.iter().cloned());
+                    units.push(unit);
+                },
+                Err(e) => diagnostics.extend(e.diagnostics),
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(BuildError::Parse(ParseError { diagnostics: diagnostics }));
+        }
+        Ok((units, sources))
+    }
+
+    /// Compile the inputs and write prot.rs, codec.rs and service.rs
+    pub fn generate(&self) -> Result<Generated, BuildError> {
+        let out_dir = match self.out_dir {
+            Some(ref dir) => dir.clone(),
+            None => match env::var_os("OUT_DIR") {
+                Some(dir) => PathBuf::from(dir),
+                None => return Err(BuildError::NoOutDir),
+            },
+        };
+        // Cargo reruns a build script that failed anyway
+        let generated = try!(self.compile());
+        if self.cargo_metadata {
+            for file in generated.sources.iter() {
+                println!("cargo:rerun-if-changed={}", file);
+            }
+        }
+
+        for &(name, source) in [("prot.rs", &generated.prot),
+                                ("codec.rs", &generated.codec),
+                                ("service.rs", &generated.service)].iter() {
+            let mut fout = try!(File::create(out_dir.join(name)));
+            try!(fout.write_all(source.as_bytes()));
+        }
+        Ok(generated)
+    }
+}
@@ -36,6 +36,16 @@ impl<'a> CodeWriter<'a> {
         }
     }
 
+    pub fn doc_comment(&mut self, lines: &[String]) {
+        for line in lines {
+            if line.is_empty() {
+                self.write_line("///");
+            } else {
+                self.write_line(&format!("/// {}", line));
+            }
+        }
+    }
+
     pub fn write<S : AsRef<str>>(&mut self, line: S) {
         let s: String = [self.indent.as_ref(), line.as_ref()].concat();
         let _ = self.writer.write_all(s.as_bytes());
@@ -54,7 +64,7 @@ impl<'a> CodeWriter<'a> {
         }).unwrap();
     }
 
-    pub fn write_codec_header(&mut self) {
+    pub fn write_codec_header(&mut self, module_path: &str) {
         self.comment("autogenerated by xdrust");
         self.comment("boilerplate for tokio services");
         self.write_line("use std::{io, result};");
@@ -63,8 +73,8 @@ impl<'a> CodeWriter<'a> {
         self.write_line("use xdr_rpc::xdr_codec::{AppCodec, XdrCodec};");
         self.write_line("use tokio_proto::pipeline::ServerProto;");
         self.write_line("use serde_xdr;");
-        self.write_line("use xdrgen::prot::*;");
-        self.write_line("use xdrgen::service::*;");
+        self.write_line(&format!("use {}::prot::*;", module_path));
+        self.write_line(&format!("use {}::service::*;", module_path));
         self.write_line("");
     }
 
@@ -75,7 +85,7 @@ impl<'a> CodeWriter<'a> {
         self.write_line("");
     }
 
-    pub fn write_service_header(&mut self) {
+    pub fn write_service_header(&mut self, module_path: &str, service_path: &str) {
         self.comment("Autogenerated by xdrust");
         self.write_line("#[allow(dead_code)]");
         self.write_line("use std::io;");
@@ -85,22 +95,27 @@ impl<'a> CodeWriter<'a> {
 		self.write_line("use tokio_core::io::EasyBuf;");
 		self.write_line("use futures_cpupool::CpuPool;");
 		self.write_line("use xdr_rpc::xdr_rpc;");
-		self.write_line("use xdrgen::prot::*;");
+		self.write_line(&format!("use {}::prot::*;", module_path));
 		self.write_line("use std::default::Default;");
         self.write_line("use serde_xdr;");
         self.write_line("");
-        self.write_line("use service::*;");
+        self.write_line(&format!("use {}::*;", service_path));
     }
 
     pub fn write_proto_header(&mut self) {
         self.comment("autogenerated by xdrust");
         self.comment("translated XDR->Rust types and functions");
+    }
+
+    pub fn write_proto_imports(&mut self, opaque_bytes: bool) {
         self.write_line("#[allow(dead_code)]");
         self.write_line("use std::{io, fmt};");
         self.write_line("use serde_xdr;");
         self.write_line("use xdr_rpc::xdr_rpc;");
         self.write_line("use tokio_core::io::EasyBuf;");
-        self.write_line("");
+        if opaque_bytes {
+            self.write_line("use bytes::Bytes;");
+        }
     }
 
     pub fn alias_impl<S : AsRef<str>, F>(&mut self, prefix: &str,  name: S, mut cb: F)
@@ -128,37 +143,55 @@ impl<'a> CodeWriter<'a> {
 								 struct_name.as_ref()), "", cb);
 	}
 
-    pub fn pub_union_enum<S : AsRef<str>, F>(&mut self, name: S, mut cb: F) where F : FnMut(&mut CodeWriter) {
+    pub fn pub_union_enum<S : AsRef<str>, F>(&mut self, name: S, docs: &[String], mut cb: F)
+            where F : FnMut(&mut CodeWriter) {
         self.write_line("");
+        self.doc_comment(docs);
         self.write_line("#[derive(Serialize, Deserialize, PartialEq, Debug)]");
         self.write_line("#[serde(rename(deserialize = \"__UNION_SYMBOL__\"))]");
         self.expr_block(&format!("pub enum {}", name.as_ref()), "", cb);
     }
 
-    pub fn pub_enum<S : AsRef<str>, F>(&mut self, name: S, mut cb: F) where F : FnMut(&mut CodeWriter) {
+    pub fn pub_enum<S : AsRef<str>, F>(&mut self, name: S, docs: &[String], mut cb: F)
+            where F : FnMut(&mut CodeWriter) {
         self.write_line("");
+        self.doc_comment(docs);
         self.write_line("#[derive(Serialize, Deserialize, PartialEq, Debug)]");
         self.expr_block(&format!("pub enum {}", name.as_ref()), "", cb);
     }
 
+    pub fn pub_newtype<S : AsRef<str>>(&mut self, name: S, field_type: &str, module: &str,
+                                       docs: &[String]) {
+        self.write_line("");
+        self.doc_comment(docs);
+        self.write_line("#[derive(Serialize, Deserialize, PartialEq, Debug)]");
+        self.write_line(&format!("pub struct {}(#[serde(with = \"{}\")] pub {});",
+                                 name.as_ref(), module, field_type));
+    }
+
+    pub fn pub_const(&mut self, name: &str, const_type: &str, val: &str) {
+        self.write_line(&format!("pub const {}: {} = {};", name, const_type, val));
+    }
+
     pub fn pub_struct_no_body<S : AsRef<str>>(&mut self, name: S) {
         self.write_line("");
         self.write_line(&format!("pub struct {};", name.as_ref()));
     }
 
-    pub fn pub_struct<S : AsRef<str>, F>(&mut self, name: S, mut cb: F)
+    pub fn pub_struct<S : AsRef<str>, F>(&mut self, name: S, docs: &[String], mut cb: F)
         where F : FnMut(&mut CodeWriter) {
             self.write_line("");
+            self.doc_comment(docs);
             self.write_line("#[derive(Serialize, Deserialize, PartialEq, Debug)]");
             self.expr_block(&format!("pub struct {}", name.as_ref()), "", cb);
     }
 
 
     pub fn program_version_request<S: AsRef<str>, F>(&mut self, prog_name: S,
-                                                     ver_num: i64, mut cb: F)
+                                                     ver_num: i128, mut cb: F)
             where F: FnMut(&mut CodeWriter) {
         self.pub_enum(&format!("{}RequestV{}", prog_name.as_ref(), ver_num),
-            cb);
+            &[], cb);
 
     }
 
@@ -190,10 +223,10 @@ impl<'a> CodeWriter<'a> {
     }
 
     pub fn program_version_response<S: AsRef<str>, F>(&mut self, prog_name: S,
-                                                     ver_num: i64, mut cb: F)
+                                                     ver_num: i128, mut cb: F)
             where F: FnMut(&mut CodeWriter) {
         self.pub_enum(&format!("{}ResponseV{}", prog_name.as_ref(), ver_num),
-            cb);
+            &[], cb);
     }
 
     pub fn version_proc_response<S, Q>(&mut self, name: S, ret: Option<Q>)
@@ -244,8 +277,10 @@ impl<'a> CodeWriter<'a> {
         self.write_line("},");
     }
 
-    pub fn namespace<S: AsRef<str>, F>(&mut self, name: S, mut cb: F)
+    pub fn namespace<S: AsRef<str>, F>(&mut self, name: S, docs: &[String], mut cb: F)
             where F: FnMut(&mut CodeWriter) {
+        self.write_line("");
+        self.doc_comment(docs);
         self.expr_block(&format!("pub mod {}", name.as_ref()), "", cb)
     }
 
@@ -292,6 +327,10 @@ impl<'a> CodeWriter<'a> {
         self.write_line(&format!("#[serde(rename = \"{}\")]", name));
     }
 
+    pub fn with_annotation(&mut self, module: &str) {
+        self.write_line(&format!("#[serde(with = \"{}\")]", module));
+    }
+
     pub fn field_decl(&mut self, name: &str, field_type: &str) {
         self.write_line(&format!("{}: {},", name, field_type));
     }
@@ -302,8 +341,16 @@ impl<'a> CodeWriter<'a> {
                 &format!("}}{}", trailing_char), cb);
     }
 
-    pub fn xdr_enum<F>(&mut self, prefix: &str, mut cb: F) where F : FnMut(&mut CodeWriter) {
-            self.block(&format!("xdr_enum!({} {{", prefix), "});", cb);
+    pub fn xdr_enum<F>(&mut self, prefix: &str, docs: &[String], mut cb: F)
+        where F : FnMut(&mut CodeWriter) {
+            // Doc comments only reach the enum from inside the macro call
+            if docs.is_empty() {
+                self.block(&format!("xdr_enum!({} {{", prefix), "});", cb);
+            } else {
+                self.write_line("xdr_enum!(");
+                self.doc_comment(docs);
+                self.block(&format!("{} {{", prefix), "});", cb);
+            }
     }
 
     pub fn block<F>(&mut self, first_line: &str, last_line: &str, mut cb: F)
@@ -0,0 +1,1247 @@
+use std::str;
+use std::fs;
+use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use code_writer::CodeWriter;
+use function_writer::*;
+use ast::*;
+use parser::{self, ParseError};
+use resolve::{self, Schema};
+use preprocessor::Preprocessed;
+
+// convert from snake_case to CamelCase
+pub fn rustify(underscores: &String) -> String {
+    let mut collect = String::from("");
+    let chars: Vec<char> = underscores.chars().collect();
+    let mut under = false;
+    let mut first = true;
+    let mut i = 0; // there is a more rusty way to do this
+    let end_index = chars.len() - 1;
+    for c in chars {
+        if c == 't' && i == end_index && under {
+            // don't push this guy, it's an annoying _t
+        } else if c == '_' {
+            under = true;
+        } else if under || first {
+            collect.push_str(c.to_uppercase().collect::<String>().as_str());
+            first = false;
+            under = false;
+        } else {
+            collect.push_str(c.to_lowercase().collect::<String>().as_str());
+        }
+        i += 1;
+    }
+    collect
+}
+
+// The text of comments kept by the parser, as doc comment lines. Leading
+// `*`s of block comment lines and the extra `/` of `///` are dropped.
+pub fn doc_lines(comments: &[String]) -> Vec<String> {
+    let mut docs = Vec::new();
+    for comment in comments {
+        let lines: Vec<&str> = comment.lines()
+            .map(|line| line.trim().trim_start_matches(|c| c == '*' || c == '/').trim())
+            .collect();
+        let first = lines.iter().position(|line| !line.is_empty());
+        let last = lines.iter().rposition(|line| !line.is_empty());
+        if let (Some(first), Some(last)) = (first, last) {
+            docs.extend(lines[first..last + 1].iter().map(|line| line.to_string()));
+        }
+    }
+    docs
+}
+
+fn primitive_type(ty: &Type) -> String {
+    match *ty {
+        Type::Uint   => { String::from("u32") },
+        Type::Int    => { String::from("i32") },
+        Type::Uhyper => { String::from("u64") },
+        Type::Hyper  => { String::from("i64") },
+        Type::Float  => { String::from("f32") },
+        Type::Double => { String::from("f64") },
+        Type::Bool   => { String::from("bool") },
+        Type::Quadruple => { unreachable!("quadruple is rejected when resolving") }
+    }
+}
+
+// Inline types are hoisted out and named before their declarations get here,
+// and procedures can only name types
+fn type_name(ty: &TypeSpec) -> String {
+    match *ty {
+        TypeSpec::Primitive(ref ty, _) => primitive_type(ty),
+        TypeSpec::Named(ref id) => rustify(&id.name),
+        _ => { unreachable!("inline type definitions are hoisted before naming them") }
+    }
+}
+
+// Array lengths keep referring to a named constant where there is one
+fn array_size(size: &Value, tab: &CodegenState) -> String {
+    match *size {
+        Value::Named(ref id) if tab.schema.constants.contains_key(&id.name) => format!("{} as usize", id),
+        _ => match tab.constant_value(size) {
+            Some(val) => val.to_string(),
+            None => format!("UNSUPORTED SIZE: {:?}", size)
+        }
+    }
+}
+
+// The Rust type of a declaration that doesn't define a nested type, and the
+// serde_xdr module to encode it with when serde's own impls won't do
+fn declaration_type(decl: &Declaration, tab: &CodegenState) -> Option<(String, Option<&'static str>)> {
+    match decl.kind {
+        DeclarationKind::Scalar{ref ty, ..} => {
+            if tab.closes_cycle(ty) {
+                Some((format!("Box<{}>", type_name(ty)), None))
+            } else {
+                Some((type_name(ty), None))
+            }
+        },
+        DeclarationKind::Pointer{ref ty, ..} => {
+            if tab.needs_box(ty) {
+                Some((format!("Option<Box<{}>>", type_name(ty)), None))
+            } else {
+                Some((format!("Option<{}>", type_name(ty)), None))
+            }
+        },
+        DeclarationKind::String{..} => Some((String::from("String"), None)),
+        DeclarationKind::VarArray{ref ty, ..} => {
+            Some((format!("Vec<{}>", type_name(ty)), None))
+        },
+        DeclarationKind::Array{ref ty, ref size, ..} => {
+            let elem = if tab.closes_cycle(ty) {
+                format!("Box<{}>", type_name(ty))
+            } else {
+                type_name(ty)
+            };
+            Some((format!("[{}; {}]", elem, array_size(size, tab)), Some("serde_xdr::array")))
+        },
+        DeclarationKind::Opaque{ref size, ..} => {
+            Some((format!("[u8; {}]", array_size(size, tab)), Some("serde_xdr::opaque::fixed")))
+        },
+        DeclarationKind::VarOpaque{..} => {
+            if tab.opaque_bytes {
+                Some((String::from("Bytes"), Some("serde_xdr::shared")))
+            } else {
+                Some((String::from("Vec<u8>"), Some("serde_xdr::opaque")))
+            }
+        },
+        DeclarationKind::Void => None
+    }
+}
+
+// The types a declaration holds in place rather than behind a Vec or String,
+// a type holding itself this way needs a Box somewhere to have a size.
+// Optional data is left out unless `optional` is set.
+fn inline_types(decl: &Declaration, optional: bool, types: &mut HashSet<String>) {
+    match decl.kind {
+        DeclarationKind::Pointer{..} if !optional => {},
+        DeclarationKind::Scalar{ref ty, ..} |
+        DeclarationKind::Array{ref ty, ..} |
+        DeclarationKind::Pointer{ref ty, ..} => {
+            match *ty {
+                TypeSpec::Named(ref id) => { types.insert(id.name.clone()); },
+                TypeSpec::Struct(ref body) => {
+                    for field in body.fields.iter() {
+                        inline_types(field, optional, types);
+                    }
+                },
+                TypeSpec::Union(ref body) => union_inline_types(body, optional, types),
+                _ => {}
+            }
+        },
+        _ => {}
+    }
+}
+
+fn definition_types(def: &Definition, optional: bool) -> HashSet<String> {
+    let mut types = HashSet::new();
+    match def.kind {
+        DefinitionKind::Struct{ref body, ..} => {
+            for field in body.fields.iter() {
+                inline_types(field, optional, &mut types);
+            }
+        },
+        DefinitionKind::Union{ref body, ..} => union_inline_types(body, optional, &mut types),
+        DefinitionKind::Typedef(ref decl) => inline_types(decl, optional, &mut types),
+        _ => {}
+    }
+    types
+}
+
+fn union_inline_types(body: &UnionBody, optional: bool, types: &mut HashSet<String>) {
+    inline_types(&body.discriminant, optional, types);
+    for arm in body.cases.iter() {
+        inline_types(&arm.decl, optional, types);
+    }
+    if let Some(ref decl) = body.default {
+        inline_types(decl, optional, types);
+    }
+}
+
+fn write_struct(ident: &Ident,
+                body: &StructBody,
+                comments: &[String],
+                tab: &mut CodegenState,
+                wr: &mut CodeWriter) -> bool {
+    tab.owner = ident.name.clone();
+    wr.pub_struct(rustify(&ident.name), &doc_lines(comments), |wr| {
+        for field in body.fields.iter() {
+            wr.doc_comment(&doc_lines(&field.comments));
+            let hoisted = tab.hoist(field);
+            let field = hoisted.as_ref().unwrap_or(field);
+            // `void` fields were rejected when resolving
+            if let (Some(field_id), Some((field_type, with))) = (field.id(), declaration_type(field, tab)) {
+                if let Some(module) = with {
+                    wr.with_annotation(module);
+                }
+                wr.pub_field_decl(field_id.name.as_str(), field_type.as_str());
+            }
+        }
+    });
+    true
+}
+
+// Arms named by a plain number get a name from its value, `Case3` or `CaseNeg1`
+fn case_variant_name(case: &Value, tab: &CodegenState) -> String {
+    match *case {
+        Value::Named(ref id) => rustify(&id.name),
+        _ => match tab.constant_value(case) {
+            Some(val) if val < 0 => format!("CaseNeg{}", -val),
+            Some(val) => format!("Case{}", val),
+            None => format!("UNSUPORTED CASE: {:?}", case)
+        }
+    }
+}
+
+// The field of a union arm, whose inline type was already hoisted
+fn write_arm_field(decl: &Declaration, tab: &mut CodegenState, wr: &mut CodeWriter) {
+    // A void arm has no field
+    if let (Some(id), Some((ty, with))) = (decl.id(), declaration_type(decl, tab)) {
+        if let Some(module) = with {
+            wr.with_annotation(module);
+        }
+        wr.field_decl(id.name.as_str(), ty.as_str());
+    }
+}
+
+fn write_union(ident: &Ident,
+               body: &UnionBody,
+               comments: &[String],
+               tab: &mut CodegenState,
+               wr: &mut CodeWriter) -> bool {
+    tab.owner = ident.name.clone();
+    // Case labels are looked up among the discriminant's enum values
+    let ns = match body.discriminant.type_spec() {
+        Some(&TypeSpec::Named(ref ty)) => Some(ty.name.clone()),
+        _ => None
+    };
+
+    wr.pub_union_enum(rustify(&ident.name), &doc_lines(comments), |wr| {
+        for arm in body.cases.iter() {
+            // One type for all of the arm's labels, so values move between them
+            let hoisted = tab.hoist(&arm.decl);
+            let decl = hoisted.as_ref().unwrap_or(&arm.decl);
+            for case in arm.values.iter() {
+                wr.doc_comment(&doc_lines(&arm.comments));
+                let val = match tab.get_symbol(ns.as_ref().map(|ns| ns.as_str()), case) {
+                    Ok(val) => val,
+                    Err(message) => {
+                        tab.errors.push((case.span(), message));
+                        continue;
+                    },
+                };
+                wr.enc_annotation(val.to_string().as_str());
+                wr.enum_struct_decl(case_variant_name(case, tab).as_str(), |wr| {
+                    write_arm_field(decl, tab, wr);
+                });
+            }
+        }
+        if let Some(ref decl) = body.default {
+            wr.comment("Default case for the XDR Union");
+            wr.doc_comment(&doc_lines(&decl.comments));
+            let hoisted = tab.hoist(decl);
+            let decl = hoisted.as_ref().unwrap_or(decl);
+            wr.enum_struct_decl("UnionDefault_", |wr| {
+                write_arm_field(decl, tab, wr);
+            });
+        }
+    });
+    true
+}
+
+fn write_enum(ident: &Ident,
+              body: &EnumBody,
+              comments: &[String],
+              tab: &mut CodegenState,
+              wr: &mut CodeWriter) -> bool {
+    wr.xdr_enum(rustify(&ident.name).as_str(), &doc_lines(comments), |wr| {
+        for variant in body.variants.iter() {
+            // Values were resolved up front, including ones left out or naming a constant
+            if let Some(val) = tab.schema.value(&variant.id.name) {
+                wr.doc_comment(&doc_lines(&variant.comments));
+                wr.enum_decl(rustify(&variant.id.name).as_str(), val.to_string().as_str());
+            }
+        }
+    });
+    true
+}
+
+// Write the nested types found while writing the last definition, and any
+// found in those, right after it
+fn write_hoisted(tab: &mut CodegenState, wr: &mut CodeWriter) {
+    loop {
+        let hoisted = tab.take_hoisted();
+        if hoisted.is_empty() {
+            break;
+        }
+        for def in hoisted.iter() {
+            match def.kind {
+                DefinitionKind::Union{ref id, ref body} => {
+                    write_union(id, body, &def.comments, tab, wr);
+                },
+                DefinitionKind::Struct{ref id, ref body} => {
+                    write_struct(id, body, &def.comments, tab, wr);
+                },
+                DefinitionKind::Enum{ref id, ref body} => {
+                    write_enum(id, body, &def.comments, tab, wr);
+                },
+                _ => { unreachable!() }
+            }
+        }
+    }
+}
+
+fn write_typedef(def: &Declaration, comments: &[String], tab: &mut CodegenState,
+                 wr: &mut CodeWriter) -> bool {
+    match def.kind {
+        // `typedef struct {...} name;` and friends just name a new type
+        DeclarationKind::Scalar{ref ty, ref id} => {
+            match *ty {
+                TypeSpec::Struct(ref body) => {
+                    return write_struct(id, body, comments, tab, wr);
+                },
+                TypeSpec::Union(ref body) => {
+                    return write_union(id, body, comments, tab, wr);
+                },
+                TypeSpec::Enum(ref body) => {
+                    return write_enum(id, body, comments, tab, wr);
+                },
+                _ => {}
+            }
+        },
+        // `typedef void` was rejected when resolving
+        DeclarationKind::Void => return false,
+        _ => {}
+    };
+
+    if let Some(id) = def.id() {
+        tab.owner = id.name.clone();
+    }
+    let hoisted = tab.hoist(def);
+    let def = hoisted.as_ref().unwrap_or(def);
+    match (def.id(), declaration_type(def, tab)) {
+        // Types that need a serde_xdr module get a newtype to carry it
+        (Some(id), Some((ty, Some(module)))) => {
+            wr.pub_newtype(rustify(&id.name), ty.as_str(), module, &doc_lines(comments));
+        },
+        (Some(id), Some((ty, None))) => {
+            wr.doc_comment(&doc_lines(comments));
+            wr.pub_alias(rustify(&id.name), |wr| {
+                wr.raw_write(ty.as_str());
+            });
+        },
+        _ => return false
+    };
+    true
+}
+
+fn write_const(id: &Ident, value: &Value, comments: &[String], tab: &CodegenState,
+               wr: &mut CodeWriter) -> bool {
+    match tab.constant_value(value) {
+        Some(val) => {
+            // XDR constants are untyped, keep the common unsigned ones usable as u32
+            let ty = if val >= 0 && val <= u32::max_value() as i128 {
+                "u32"
+            } else if val <= i64::max_value() as i128 {
+                "i64"
+            } else {
+                "u64"
+            };
+            wr.doc_comment(&doc_lines(comments));
+            wr.pub_const(&id.name, ty, &val.to_string());
+            true
+        },
+        None => false
+    }
+}
+
+fn write_version(prog_name: &String, version: &Version, wr: &mut CodeWriter) -> bool {
+    let ver_num = version.number;
+    let procs = &version.procedures;
+    let docs = doc_lines(&version.comments);
+    wr.pub_enum(&format!("{}RequestV{}", prog_name, ver_num), &docs, |wr| {
+        for procedure in procs {
+            let arg_strings: Vec<String> = procedure.arg_types.iter()
+                .map(type_name)
+                .collect();
+
+            wr.doc_comment(&doc_lines(&procedure.comments));
+            wr.version_proc_request(rustify(&procedure.id.name).as_str(),
+                &arg_strings);
+        }
+    });
+
+    wr.pub_enum(&format!("{}ResponseV{}", prog_name, ver_num), &docs, |wr| {
+        for procedure in procs {
+            let ret_str: Option<String> = procedure.return_type.as_ref().map(type_name);
+
+            wr.doc_comment(&doc_lines(&procedure.comments));
+            wr.version_proc_response(
+                rustify(&procedure.id.name).as_str(), ret_str);
+        }
+    });
+
+    true
+}
+
+fn write_service_proc(prog_name: &String, ver_num: i128, procedure: &Procedure,
+                      wr: &mut CodeWriter) {
+    let arg_names = (0..procedure.arg_types.len()).map(|x| { format!("arg{}", x) }).collect();
+    let proc_name_str = rustify(&procedure.id.name);
+    for line in doc_lines(&procedure.comments) {
+        wr.comment(&line);
+    }
+    wr.match_option(&format!("{}RequestV{}::{}", prog_name, ver_num,
+        proc_name_str.as_str()), &arg_names, |wr| {
+        wr.write(&format!("self.{}_v{}(",
+            procedure.id.name.to_lowercase(),
+            ver_num));
+        wr.comma_fields(&arg_names);
+        wr.raw_write(")");
+        wrap_proc_result(prog_name, ver_num, proc_name_str.as_str(),
+            procedure.return_type.is_some(), wr);
+        wr.raw_write(".boxed()\n");
+    });
+}
+
+fn write_service_version(prog_name: &String, ver_num: i128, procs: &Vec<Procedure>,
+                         wr: &mut CodeWriter) {
+    let version_fields = vec!["data"];
+    wr.match_option(&format!("{}Request::V{}", prog_name, ver_num),
+            &version_fields, |wr| {
+        wr.let_match_block("res", "data", |wr| {
+            for procedure in procs {
+                write_service_proc(prog_name, ver_num, procedure, wr);
+            }
+            decoder_miss_future("procedure", wr);
+        });
+        wrap_version_result(prog_name, ver_num, wr);
+    });
+}
+
+fn write_service(prog_name: &String, versions: &Vec<Version>,
+                 wr: &mut CodeWriter) -> bool {
+    let rust_prog_name = rustify(prog_name);
+    wr.program_version_service(&format!("{}Service", rust_prog_name), |wr| {
+        wr.alias("Request", |wr| {
+            wr.raw_write(&format!("xdr_rpc::XdrRequest<{}Request>", rust_prog_name));
+        });
+        wr.alias("Response", |wr| {
+            wr.raw_write(&format!("xdr_rpc::XdrResponse<{}Response>", rust_prog_name));
+        });
+        wr.alias("Error", |wr| {
+            wr.raw_write("io::Error");
+        });
+        wr.alias("Future", |wr| {
+            wr.raw_write("BoxFuture<Self::Response, Self::Error>");
+        });
+        wr.dispatch_function(|wr| {
+            wr.write_line("let xid = req.xid;");
+            wr.match_block("req.val", |wr| {
+                for version in versions {
+                    write_service_version(&rust_prog_name, version.number,
+                                          &version.procedures, wr);
+                }
+                decoder_miss_future("version", wr);
+            });
+        });
+    });
+    true
+}
+
+fn write_version_set(prog_name: &String, versions: &Vec<Version>, docs: &[String],
+                     set_type: &str, wr: &mut CodeWriter) -> bool {
+    wr.pub_enum(&format!("{}{}", prog_name, set_type), docs, |wr| {
+        for version in versions {
+            wr.doc_comment(&doc_lines(&version.comments));
+            wr.enum_tuple_decl(&format!("V{}", version.number), |w2| {
+                w2.raw_write(&format!("{}{}V{}", prog_name, set_type,
+                                      version.number));
+            })
+        }
+    });
+
+    true
+}
+
+// The name of a function generated for a program in prot.rs, e.g.
+// `nfs_prog_decode`. codec.rs calls these, so both sides use this.
+fn program_fn(prog_name: &String, suffix: &str) -> String {
+    format!("{}_{}", prog_name.to_lowercase(), suffix)
+}
+
+fn write_codec(prog_name: &String, wr: &mut CodeWriter) -> bool {
+    let rust_prog_name = rustify(prog_name);
+
+    // `NFS_PROG` gets an `NfsCodec`, other names keep all their words
+    let base = if rust_prog_name.ends_with("Prog") {
+        &rust_prog_name[..rust_prog_name.len() - "Prog".len()]
+    } else {
+        &rust_prog_name[..]
+    };
+    let app_codec = format!("{}AppCodec", base);
+    let codec     = format!("{}Codec", base);
+    let protocol  = format!("{}Protocol", base);
+    let app_req   = format!("{}Request", rust_prog_name);
+    let app_res   = format!("{}Response", rust_prog_name);
+
+    wr.pub_struct_no_body(app_codec.clone());
+    wr.impl_("", "Codec", app_codec.as_str(), |wr| {
+        wr.alias_impl("", "In", |wr| {
+            wr.write(app_req.as_str());
+        });
+        wr.alias_impl("", "Out", |wr| {
+            wr.write(app_res.as_str());
+        });
+        codec_fns(program_fn(prog_name, "encode"), wr);
+    });
+
+    wr.impl_("", "AppCodec", app_codec.as_str(), |wr| {
+        app_codec_fn(program_fn(prog_name, "decode"), wr);
+    });
+
+    wr.pub_alias(codec.as_str(), |wr| {
+        wr.raw_write(format!("XdrCodec<{}>", app_codec.as_str()));
+    });
+
+    wr.pub_struct_no_body(protocol.clone());
+    wr.impl_("<T: Io + 'static>", "ServerProto<T>", protocol.as_str(), |wr| {
+        wr.alias_impl("", "Request", |wr| {
+            wr.write(format!("xdr_rpc::XdrRequest<{}>", app_req.as_str()));
+        });
+        wr.alias_impl("", "Response", |wr| {
+            wr.write(format!("xdr_rpc::XdrResponse<{}>", app_res.as_str()));
+        });
+        wr.alias_impl("", "Transport", |wr| {
+            wr.write(format!("Framed<T, {}>", codec.as_str()));
+        });
+        wr.alias_impl("", "BindTransport", |wr| {
+            wr.write("io::Result<Self::Transport>");
+        });
+        proto_fn(codec.as_str(), app_codec.as_str(), wr);
+    });
+    true
+}
+
+fn write_program(prog_name: &String, versions: &Vec<Version>, comments: &[String],
+                 wr: &mut CodeWriter) -> bool {
+    let rust_prog_name = rustify(prog_name);
+
+    let docs = doc_lines(comments);
+    write_version_set(&rust_prog_name, versions, &docs, "Request", wr);
+    write_version_set(&rust_prog_name, versions, &docs, "Response", wr);
+
+    for version in versions {
+        if !write_version(&rust_prog_name, version, wr) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn write_proc_decoder(prog_name: &String, ver_num: i128, procedure: &Procedure,
+                      wr: &mut CodeWriter) {
+    let proc_decoder_fn = format!("{}_decode_v{}_{}",
+                                  prog_name.to_lowercase(),
+                                  ver_num,
+                                  procedure.id.name.to_lowercase());
+
+    proc_decoder(rustify(prog_name).as_str(), &proc_decoder_fn, ver_num, wr, |wr| {
+        let mut i = 0u32;
+        for arg in procedure.arg_types.iter() {
+            proc_arg_decoder(i, type_name(arg).as_str(), wr);
+            i += 1;
+        }
+
+        let req_type = format!("{}RequestV{}", rustify(prog_name), ver_num);
+        let req_name = rustify(&procedure.id.name);
+
+        proc_decoder_finalize(&req_type, &req_name, i, wr);
+    });
+}
+
+fn write_version_decoder(prog_name: &String, ver_num: i128, procs: &Vec<Procedure>,
+                         wr: &mut CodeWriter) {
+    let version_decoder_fn = format!("{}_decode_v{}",
+        prog_name.to_lowercase(), ver_num);
+    version_decoder(rustify(prog_name).as_str(), &version_decoder_fn, wr, |wr| {
+        version_decoder_match(wr, |wr| {
+            for procedure in procs {
+                let proc_decoder_fn = &format!("{}_{}",
+                    &version_decoder_fn,
+                    procedure.id.name.to_lowercase());
+                wr.match_option(&format!("{}u32", procedure.number),
+                    &Vec::<String>::new(), |wr| {
+                    proc_decoder_call(&proc_decoder_fn, wr);
+                });
+            }
+            decoder_miss("procedure", wr);
+        });
+
+        version_decoder_finalize(rustify(prog_name), ver_num, wr);
+    });
+
+    for procedure in procs {
+        write_proc_decoder(prog_name, ver_num, procedure, wr);
+    }
+}
+
+fn write_decoder(prog_name: &String, versions: &Vec<Version>,
+                 wr: &mut CodeWriter) -> bool {
+    let prog_decoder_fn = program_fn(prog_name, "decode");
+    prog_decoder(rustify(prog_name).as_str(), &prog_decoder_fn, wr, |wr| {
+        wr.match_block("version", |wr| {
+            for version in versions {
+                wr.match_option(
+                    &format!("{}u32", version.number),
+                    &Vec::<String>::new(), |wr| {
+                    version_decoder_call(&format!("{}_v{}",
+                        &prog_decoder_fn, version.number), wr);
+                });
+            }
+            decoder_miss("version", wr);
+        });
+    });
+
+    for version in versions {
+        write_version_decoder(prog_name, version.number, &version.procedures, wr);
+    }
+
+    true
+}
+
+fn write_version_encoder(prog_name: &String, ver_num: i128, procs: &Vec<Procedure>,
+                         wr: &mut CodeWriter) {
+    encoder_version(prog_name, ver_num, wr, |wr| {
+        wr.match_block("rsp", |wr| {
+            for procedure in procs {
+                encoder_proc(prog_name,
+                             rustify(&procedure.id.name).as_str(),
+                             ver_num, procedure.return_type.is_some(), wr);
+            }
+            decoder_miss("procedure", wr);
+        });
+        wr.write_line("Ok(())");
+    });
+}
+
+fn write_encoder(prog_name: &String, versions: &Vec<Version>,
+                 wr: &mut CodeWriter) -> bool {
+    let rust_prog_name = rustify(prog_name);
+
+    let encoder_fn = program_fn(prog_name, "encode");
+    encoder(&rust_prog_name, &encoder_fn, wr, |wr| {
+        wr.match_block("msg", |wr| {
+            for version in versions {
+                write_version_encoder(&rust_prog_name, version.number,
+                                      &version.procedures, wr);
+            }
+            decoder_miss("version", wr);
+        });
+    });
+    true
+}
+
+// `%` lines starting with the snippet prefix are copied into prot.rs where
+// they appear, minus the prefix. The others are meant for rpcgen's C output.
+fn write_snippet(line: &str, tab: &CodegenState, wr: &mut CodeWriter) {
+    if !line.starts_with(tab.snippet_prefix.as_str()) {
+        return;
+    }
+    let code = &line[tab.snippet_prefix.len()..];
+    let code = if code.starts_with(' ') { &code[1..] } else { code };
+    wr.write_line(code.trim_end());
+}
+
+// The request/response types, decoders and encoder of a program, in prot.rs
+fn write_program_types(prog: &Program, wr: &mut CodeWriter) {
+    let name_str = &prog.id.name;
+    write_program(name_str, &prog.versions, &prog.comments, wr);
+    write_decoder(name_str, &prog.versions, wr);
+    write_encoder(name_str, &prog.versions, wr);
+}
+
+// A namespace becomes a module of the same name in each generated file. The
+// programs' types live in `prot::<namespace>`, codec.rs and service.rs import
+// them from there, and everything in the enclosing file stays in scope.
+fn write_namespace(id: &Ident, programs: &Vec<Program>, comments: &[String], module_path: &str,
+                   types_wr: &mut CodeWriter, codec_wr: &mut CodeWriter,
+                   service_wr: &mut CodeWriter) -> bool {
+    let module = id.name.to_lowercase();
+    let docs = doc_lines(comments);
+    // A path relative to the enclosing file is one more level up from here
+    let module_path = if module_path.starts_with("super") {
+        format!("super::{}", module_path)
+    } else {
+        module_path.to_string()
+    };
+    types_wr.namespace(&module, &docs, |wr| {
+        wr.write_line("use super::*;");
+        for prog in programs {
+            write_program_types(prog, wr);
+        }
+    });
+    codec_wr.namespace(&module, &docs, |wr| {
+        wr.write_line("use super::*;");
+        wr.write_line(&format!("use {}::prot::{}::*;", module_path, module));
+        for prog in programs {
+            write_codec(&prog.id.name, wr);
+        }
+    });
+    service_wr.namespace(&module, &docs, |wr| {
+        wr.write_line("use super::*;");
+        wr.write_line(&format!("use {}::prot::{}::*;", module_path, module));
+        for prog in programs {
+            write_service(&prog.id.name, &prog.versions, wr);
+        }
+    });
+    true
+}
+
+#[derive(Debug)]
+struct CodegenState<'a> {
+    name: &'a str,
+    hoister: Vec<Definition>,
+    // Constants, enum values and type names of the whole input
+    schema: Schema,
+    // The types each type holds in place, by name
+    contains: HashMap<String, HashSet<String>>,
+    // The same leaving out optional data, which is boxed on any cycle anyway
+    embeds: HashMap<String, HashSet<String>>,
+    // The order types were defined or hoisted in, which picks the edge of a
+    // cycle that gets boxed
+    order: HashMap<String, usize>,
+    // The type being written
+    owner: String,
+    // Every type name generated so far, nested types must not reuse them
+    type_names: HashSet<String>,
+    // Map opaque<> to bytes::Bytes rather than Vec<u8>
+    opaque_bytes: bool,
+    // Marks the `%` lines that are Rust
+    snippet_prefix: String,
+    // Where prot.rs, codec.rs and service.rs are in the crate using them
+    module_path: String,
+    // Where the hand written service structs are
+    service_path: String,
+    // Problems found while writing the current module, with where they are
+    errors: Vec<(Span, String)>,
+}
+
+impl<'a> CodegenState<'a> {
+    fn new(name: &str) -> CodegenState {
+        let hoister = Vec::new();
+        CodegenState {
+            name: name,
+            hoister: hoister,
+            schema: Schema::default(),
+            contains: HashMap::new(),
+            embeds: HashMap::new(),
+            order: HashMap::new(),
+            owner: String::new(),
+            type_names: HashSet::new(),
+            opaque_bytes: false,
+            snippet_prefix: String::from("#[rust]"),
+            module_path: String::from("xdrgen"),
+            service_path: String::from("service"),
+            errors: Vec::new(),
+        }
+    }
+
+    // Inline `struct {...}`, `union switch` and `enum {...}` types become
+    // types of their own, named after where they are declared as in
+    // `ParentField`. Gives the declaration naming the new type instead.
+    fn hoist(&mut self, decl: &Declaration) -> Option<Declaration> {
+        let (ty, id) = match (decl.type_spec(), decl.id()) {
+            (Some(ty), Some(id)) => (ty, id),
+            _ => { return None }
+        };
+        match *ty {
+            TypeSpec::Union(_) | TypeSpec::Struct(_) | TypeSpec::Enum(_) => {},
+            _ => { return None }
+        }
+        let parent = self.owner.clone();
+        let mut scoped = format!("{}_{}", parent, id.name);
+        let mut n = 1;
+        while self.type_names.contains(&rustify(&scoped)) {
+            n += 1;
+            scoped = format!("{}_{}{}", parent, id.name, n);
+        }
+        let name = Ident{name: scoped.clone(), span: id.span};
+        let kind = match *ty {
+            TypeSpec::Union(ref body) => DefinitionKind::Union{id: name, body: (**body).clone()},
+            TypeSpec::Struct(ref body) => DefinitionKind::Struct{id: name, body: body.clone()},
+            TypeSpec::Enum(ref body) => DefinitionKind::Enum{id: name, body: body.clone()},
+            _ => { unreachable!() }
+        };
+        // The field's comments are all there is to say about its type
+        let def = Definition{kind: kind, span: decl.span, comments: decl.comments.clone()};
+
+        self.type_names.insert(rustify(&scoped));
+        self.add_contained(&def);
+        self.contains.entry(parent.clone()).or_insert_with(HashSet::new).insert(scoped.clone());
+        match decl.kind {
+            DeclarationKind::Pointer{..} => {},
+            _ => { self.embeds.entry(parent).or_insert_with(HashSet::new).insert(scoped.clone()); }
+        }
+        self.hoister.push(def);
+
+        let named = TypeSpec::Named(Ident{name: scoped, span: ty.span()});
+        let kind = match decl.kind {
+            DeclarationKind::Scalar{ref id, ..} => DeclarationKind::Scalar{ty: named, id: id.clone()},
+            DeclarationKind::Array{ref id, ref size, ..} => {
+                DeclarationKind::Array{ty: named, id: id.clone(), size: size.clone()}
+            },
+            DeclarationKind::VarArray{ref id, ref size, ..} => {
+                DeclarationKind::VarArray{ty: named, id: id.clone(), size: size.clone()}
+            },
+            DeclarationKind::Pointer{ref id, ..} => DeclarationKind::Pointer{ty: named, id: id.clone()},
+            _ => { unreachable!() }
+        };
+        Some(Declaration{kind: kind, span: decl.span, comments: decl.comments.clone()})
+    }
+
+    fn take_hoisted(&mut self) -> Vec<Definition> {
+        self.hoister.drain(..).collect()
+    }
+
+    fn get_symbol(&mut self, ns: Option<&str>, case: &Value) -> Result<i128, String> {
+        if let (Some(ns), &Value::Named(ref id)) = (ns, case) {
+            let val = self.schema.enums.get(ns)
+                .and_then(|values| values.iter().find(|v| v.0 == id.name));
+            if let Some(&(_, val)) = val {
+                return Ok(val);
+            }
+        }
+        // Case labels may also be plain numbers or named constants
+        self.schema.evaluate(case)
+    }
+
+    fn add_contained(&mut self, def: &Definition) {
+        let id = match def.kind {
+            DefinitionKind::Struct{ref id, ..} | DefinitionKind::Union{ref id, ..} => id,
+            DefinitionKind::Typedef(ref decl) => match decl.id() {
+                Some(id) => id,
+                None => { return }
+            },
+            _ => { return }
+        };
+        let order = self.order.len();
+        self.order.entry(id.name.clone()).or_insert(order);
+        self.contains.insert(id.name.clone(), definition_types(def, true));
+        self.embeds.insert(id.name.clone(), definition_types(def, false));
+    }
+
+    // Whether `from` holds `to` in place, directly or through other types
+    fn reaches(graph: &HashMap<String, HashSet<String>>, from: &str, to: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(name) = stack.pop() {
+            if name == to {
+                return true;
+            }
+            if !seen.insert(name) {
+                continue;
+            }
+            if let Some(types) = graph.get(name) {
+                stack.extend(types.iter().map(|ty| ty.as_str()));
+            }
+        }
+        false
+    }
+
+    // Optional data only needs a Box when it leads back to the type holding it
+    fn needs_box(&self, ty: &TypeSpec) -> bool {
+        match *ty {
+            TypeSpec::Named(ref id) => CodegenState::reaches(&self.contains, &id.name, &self.owner),
+            _ => false
+        }
+    }
+
+    // A cycle of types holding each other in place, through struct fields,
+    // union arms, typedefs and fixed arrays, has no size. Each such cycle goes
+    // back to an earlier type somewhere, and that is where the Box goes.
+    // Cycles through optional data are already broken by its Box.
+    fn closes_cycle(&self, ty: &TypeSpec) -> bool {
+        let target = match *ty {
+            TypeSpec::Named(ref id) => &id.name,
+            _ => return false
+        };
+        let earlier = match (self.order.get(target), self.order.get(&self.owner)) {
+            (Some(target), Some(owner)) => target <= owner,
+            _ => false
+        };
+        earlier && CodegenState::reaches(&self.embeds, target, &self.owner)
+    }
+
+    // The number behind a literal, named constant or constant expression
+    fn constant_value(&self, val: &Value) -> Option<i128> {
+        self.schema.evaluate(val).ok()
+    }
+}
+
+// The generated module for one source file, `#include`d ones included
+#[derive(Debug)]
+struct FileModule {
+    // Canonical path, a file included by several inputs is compiled once
+    file: String,
+    name: String,
+    // The input the definitions were parsed from, their spans point into it
+    unit: usize,
+    definitions: Vec<Definition>,
+}
+
+const RUST_KEYWORDS: &'static [&'static str] = &[
+    "as", "box", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn",
+    "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while",
+];
+
+/// Parse and resolve preprocessed inputs the way code is generated from
+/// them. Definitions from a file an earlier input already brought in are
+/// left out of the returned specifications, so shared types are resolved
+/// and generated only once.
+pub fn load(units: &[Preprocessed]) -> Result<(Vec<Specification>, Schema), ParseError> {
+    let mut specs = Vec::new();
+    let mut diagnostics = Vec::new();
+    for unit in units {
+        match parser::parse_preprocessed(unit, false) {
+            Ok(spec) => specs.push(spec),
+            Err(e) => diagnostics.extend(e.diagnostics),
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(ParseError { diagnostics: diagnostics });
+    }
+
+    let mut owners: HashMap<String, usize> = HashMap::new();
+    let mut kept = Vec::new();
+    for (index, (unit, spec)) in units.iter().zip(specs.into_iter()).enumerate() {
+        let definitions = spec.definitions.into_iter()
+            .filter(|def| {
+                let file = canonical_file(&unit.origin(def.span.start).file);
+                *owners.entry(file).or_insert(index) == index
+            })
+            .collect();
+        kept.push(Specification { definitions: definitions });
+    }
+    let schema = {
+        let files: Vec<(&Preprocessed, &Specification)> = units.iter().zip(kept.iter()).collect();
+        try!(resolve::resolve(&files))
+    };
+    Ok((kept, schema))
+}
+
+fn canonical_file(file: &str) -> String {
+    fs::canonicalize(file)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or(file.to_string())
+}
+
+// A module name for `file` from its stem, `rpc.x` is `rpc`
+fn module_name(file: &str, taken: &HashSet<String>) -> String {
+    let stem = Path::new(file).file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let mut name: String = stem.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || RUST_KEYWORDS.contains(&&name[..]) {
+        name = format!("xdr_{}", name);
+    }
+    let mut unique = name.clone();
+    let mut n = 1;
+    while taken.contains(&unique) {
+        n += 1;
+        unique = format!("{}{}", name, n);
+    }
+    unique
+}
+
+// The types and constants a declaration's generated code names
+fn declaration_references(decl: &Declaration, names: &mut HashSet<String>) {
+    if let Some(ty) = decl.type_spec() {
+        type_references(ty, names);
+    }
+    match decl.kind {
+        DeclarationKind::Array{size: Value::Named(ref id), ..} |
+        DeclarationKind::Opaque{size: Value::Named(ref id), ..} => { names.insert(id.name.clone()); },
+        _ => {}
+    }
+}
+
+fn type_references(ty: &TypeSpec, names: &mut HashSet<String>) {
+    match *ty {
+        TypeSpec::Named(ref id) => { names.insert(id.name.clone()); },
+        TypeSpec::Struct(ref body) => {
+            for field in body.fields.iter() {
+                declaration_references(field, names);
+            }
+        },
+        TypeSpec::Union(ref body) => union_references(body, names),
+        _ => {}
+    }
+}
+
+// Only the arms, case labels and the discriminant end up as numbers
+fn union_references(body: &UnionBody, names: &mut HashSet<String>) {
+    for arm in body.cases.iter() {
+        declaration_references(&arm.decl, names);
+    }
+    if let Some(ref decl) = body.default {
+        declaration_references(decl, names);
+    }
+}
+
+fn program_references(prog: &Program, names: &mut HashSet<String>) {
+    for version in prog.versions.iter() {
+        for procedure in version.procedures.iter() {
+            for ty in procedure.arg_types.iter().chain(procedure.return_type.iter()) {
+                type_references(ty, names);
+            }
+        }
+    }
+}
+
+fn definition_references(def: &Definition, names: &mut HashSet<String>) {
+    match def.kind {
+        DefinitionKind::Typedef(ref decl) => declaration_references(decl, names),
+        DefinitionKind::Struct{ref body, ..} => {
+            for field in body.fields.iter() {
+                declaration_references(field, names);
+            }
+        },
+        DefinitionKind::Union{ref body, ..} => union_references(body, names),
+        DefinitionKind::Program(ref prog) => program_references(prog, names),
+        DefinitionKind::Namespace{ref programs, ..} => {
+            for prog in programs.iter() {
+                program_references(prog, names);
+            }
+        },
+        _ => {}
+    }
+}
+
+fn write_definition(def: &Definition,
+                    tab: &mut CodegenState,
+                    types_wr: &mut CodeWriter,
+                    codec_wr: &mut CodeWriter,
+                    service_wr: &mut CodeWriter) {
+    match def.kind {
+        // Comments worth keeping were attached to what they describe, and
+        // preprocessed input has no directives left
+        DefinitionKind::Comment(_) | DefinitionKind::Directive(_) => {},
+        DefinitionKind::Snippet(ref line) => {
+            write_snippet(line, tab, types_wr);
+        },
+
+        // These definitions are incredibly useful
+        DefinitionKind::Union{ref id, ref body} => {
+            write_union(id, body, &def.comments, tab, types_wr);
+        },
+        DefinitionKind::Struct{ref id, ref body} => {
+            write_struct(id, body, &def.comments, tab, types_wr);
+        },
+        DefinitionKind::Enum{ref id, ref body} => {
+            write_enum(id, body, &def.comments, tab, types_wr);
+        },
+        DefinitionKind::Typedef(ref decl) => {
+            write_typedef(decl, &def.comments, tab, types_wr);
+        },
+        DefinitionKind::Const{ref id, ref value} => {
+            write_const(id, value, &def.comments, tab, types_wr);
+        },
+        DefinitionKind::Program(ref prog) => {
+            let name_str = &prog.id.name;
+            write_codec(name_str, codec_wr);
+            write_service(name_str, &prog.versions, service_wr);
+            write_program_types(prog, types_wr);
+        },
+        DefinitionKind::Namespace{ref id, ref programs} => {
+            write_namespace(id, programs, &def.comments, &tab.module_path,
+                            types_wr, codec_wr, service_wr);
+        },
+    }
+    write_hoisted(tab, types_wr);
+}
+
+pub struct CodeGen<'a: 'b, 'b: 'c, 'c> {
+	types_wr: &'c mut CodeWriter<'a>,
+	codec_wr: &'c mut CodeWriter<'b>,
+    service_wr: &'c mut CodeWriter<'c>,
+    modules: Vec<FileModule>,
+    state: CodegenState<'c>,
+}
+
+impl<'a, 'b, 'c> CodeGen<'a, 'b, 'c> {
+    pub fn new(tw: &'c mut CodeWriter<'a>,
+			   cw: &'c mut CodeWriter<'b>,
+			   sw: &'c mut CodeWriter<'c>) -> CodeGen<'a, 'b, 'c> {
+        CodeGen {
+			types_wr: tw,
+			codec_wr: cw,
+			service_wr: sw,
+            modules: Vec::new(),
+            state: CodegenState::new("CodegenTable"),
+		}
+    }
+
+    /// Generate `bytes::Bytes` for variable length opaque data, decoded
+    /// without copying when the input is a shared buffer. Needs serde_xdr's
+    /// `bytes` feature.
+    pub fn opaque_as_bytes(&mut self, enabled: bool) {
+        self.state.opaque_bytes = enabled;
+    }
+
+    /// Copy `%` lines starting with `prefix` into prot.rs, `#[rust]` unless
+    /// set. An empty prefix copies every `%` line.
+    pub fn snippet_prefix(&mut self, prefix: &str) {
+        self.state.snippet_prefix = prefix.to_string();
+    }
+
+    /// The module prot.rs, codec.rs and service.rs are included as, in the
+    /// crate using them. They `use` each other through it. `xdrgen` unless set.
+    pub fn module_path(&mut self, path: &str) {
+        self.state.module_path = path.to_string();
+    }
+
+    /// The module defining the `<Program>Service` structs whose methods
+    /// service.rs calls, `service` unless set
+    pub fn service_path(&mut self, path: &str) {
+        self.state.service_path = path.to_string();
+    }
+
+    /// Compile preprocessed files, nothing is generated unless every one of
+    /// them parses cleanly. Each source file, `#include`d ones too, becomes a
+    /// module of prot.rs.
+    pub fn compile(&mut self, units: &[Preprocessed], dump_parse: bool) -> Result<&'static str, ParseError> {
+        if dump_parse {
+            let mut diagnostics = Vec::new();
+            for unit in units {
+                if let Err(e) = parser::parse_preprocessed(unit, true) {
+                    diagnostics.extend(e.diagnostics);
+                }
+            }
+            if !diagnostics.is_empty() {
+                return Err(ParseError { diagnostics: diagnostics });
+            }
+            return Ok("Dumped parse tree");
+        }
+
+        let (specs, schema) = try!(load(units));
+        self.add_modules(units, &specs);
+        self.state.schema = schema;
+        self.state.type_names = self.state.schema.types.keys().map(rustify).collect();
+        for module in self.modules.iter() {
+            for def in module.definitions.iter() {
+                self.state.add_contained(def);
+            }
+        }
+        self.codec_wr.write_codec_header(&self.state.module_path);
+        self.types_wr.write_proto_header();
+        self.service_wr.write_service_header(&self.state.module_path, &self.state.service_path);
+
+        try!(self.codegen_all(units));
+        Ok("Complete codegen")
+    }
+
+    // Sort definitions into modules named after the files they were
+    // written in
+    fn add_modules(&mut self, units: &[Preprocessed], specs: &[Specification]) {
+        let mut taken = HashSet::new();
+        for (index, (unit, spec)) in units.iter().zip(specs.iter()).enumerate() {
+            for def in spec.definitions.iter() {
+                let origin = unit.origin(def.span.start).file;
+                let file = canonical_file(&origin);
+                let position = self.modules.iter().position(|module| module.file == file);
+                let module = match position {
+                    Some(module) => module,
+                    None => {
+                        let name = module_name(&origin, &taken);
+                        taken.insert(name.clone());
+                        self.modules.push(FileModule {
+                            file: file,
+                            name: name,
+                            unit: index,
+                            definitions: Vec::new(),
+                        });
+                        self.modules.len() - 1
+                    },
+                };
+                self.modules[module].definitions.push(def.clone());
+            }
+        }
+    }
+
+    fn codegen_all(&mut self, units: &[Preprocessed]) -> Result<&'static str, ParseError> {
+        let mut defined_in = HashMap::new();
+        for (index, module) in self.modules.iter().enumerate() {
+            for def in module.definitions.iter() {
+                if let Some(id) = def.id() {
+                    defined_in.insert(id.name.clone(), index);
+                }
+            }
+        }
+
+        let mut diagnostics = Vec::new();
+        let state = &mut self.state;
+        let codec_wr = &mut *self.codec_wr;
+        let service_wr = &mut *self.service_wr;
+        // The modules keep the glob imports prot.rs always had working
+        for module in self.modules.iter() {
+            self.types_wr.write_line(&format!("pub use self::{}::*;", module.name));
+        }
+        for (index, module) in self.modules.iter().enumerate() {
+            let mut names = HashSet::new();
+            for def in module.definitions.iter() {
+                definition_references(def, &mut names);
+            }
+            let mut uses: Vec<usize> = names.iter()
+                .filter_map(|name| defined_in.get(name).cloned())
+                .filter(|&other| other != index)
+                .collect();
+            uses.sort();
+            uses.dedup();
+
+            let file = Path::new(&module.file).file_name()
+                .map_or(module.file.clone(), |name| name.to_string_lossy().into_owned());
+            let docs = vec![format!("Generated from `{}`", file)];
+            let modules = &self.modules;
+            self.types_wr.namespace(&module.name, &docs, |wr| {
+                wr.write_proto_imports(state.opaque_bytes);
+                for &other in uses.iter() {
+                    wr.write_line(&format!("use super::{}::*;", modules[other].name));
+                }
+                wr.write_line("");
+                for def in module.definitions.iter() {
+                    write_definition(def, state, wr, codec_wr, service_wr);
+                }
+            });
+            let unit = &units[module.unit];
+            diagnostics.extend(state.errors.drain(..).map(|(span, message)| unit.diagnostic(span, message)));
+        }
+        if !diagnostics.is_empty() {
+            return Err(ParseError { diagnostics: diagnostics });
+        }
+        Ok("Complete codegen")
+    }
+}
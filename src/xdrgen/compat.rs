@@ -0,0 +1,728 @@
+// Compare two versions of a schema for changes that break data or calls
+// encoded with one and decoded with the other.
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+
+use ast::*;
+use builder::BuildError;
+use parser;
+use preprocessor::Preprocessor;
+use resolve::{self, Schema};
+
+/// A difference between two versions of a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// Whether what one side encodes may not decode on the other
+    pub breaking: bool,
+    pub message: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", if self.breaking { "breaking" } else { "safe" }, self.message)
+    }
+}
+
+/// Preprocess, parse and resolve the old and new versions of a .x file and
+/// compare them
+pub fn check(preprocessor: &Preprocessor, old: &str, new: &str) -> Result<Vec<Change>, BuildError> {
+    let (old_spec, old_schema) = try!(load(preprocessor, old));
+    let (new_spec, new_schema) = try!(load(preprocessor, new));
+    Ok(compare(&old_spec, &old_schema, &new_spec, &new_schema))
+}
+
+fn load(preprocessor: &Preprocessor, file: &str) -> Result<(Specification, Schema), BuildError> {
+    let mut source = String::new();
+    let read = File::open(file).and_then(|mut fin| fin.read_to_string(&mut source));
+    if let Err(e) = read {
+        return Err(BuildError::Io(io::Error::new(e.kind(), format!("{}: {}", file, e))));
+    }
+    let unit = try!(preprocessor.preprocess(file, &source));
+    let spec = try!(parser::parse_preprocessed(&unit, false));
+    let schema = try!(resolve::resolve(&[(&unit, &spec)]));
+    Ok((spec, schema))
+}
+
+/// The changes from `old` to `new`, in the order of the old definitions.
+/// Types are matched by name, or by encoding when renamed. Programs, versions
+/// and procedures are matched by name, or by number when renamed.
+pub fn compare(old: &Specification, old_schema: &Schema,
+               new: &Specification, new_schema: &Schema) -> Vec<Change> {
+    let mut comparison = Comparison {
+        old: Side::new(old, old_schema),
+        new: Side::new(new, new_schema),
+        changes: Vec::new(),
+        visiting: RefCell::new(HashSet::new()),
+    };
+    comparison.types();
+    comparison.programs();
+    comparison.changes
+}
+
+// One version of the schema
+struct Side<'a> {
+    schema: &'a Schema,
+    // Named types in source order, and by name
+    types: Vec<(&'a str, &'a DefinitionKind)>,
+    by_name: HashMap<&'a str, &'a DefinitionKind>,
+    programs: Vec<&'a Program>,
+}
+
+impl<'a> Side<'a> {
+    fn new(spec: &'a Specification, schema: &'a Schema) -> Side<'a> {
+        let mut side = Side {
+            schema: schema,
+            types: Vec::new(),
+            by_name: HashMap::new(),
+            programs: Vec::new(),
+        };
+        for def in spec.definitions.iter() {
+            match def.kind {
+                DefinitionKind::Typedef(_) |
+                DefinitionKind::Enum{..} |
+                DefinitionKind::Struct{..} |
+                DefinitionKind::Union{..} => {
+                    if let Some(id) = def.id() {
+                        side.types.push((&id.name, &def.kind));
+                        side.by_name.insert(&id.name, &def.kind);
+                    }
+                },
+                DefinitionKind::Program(ref prog) => side.programs.push(prog),
+                DefinitionKind::Namespace{ref programs, ..} => side.programs.extend(programs.iter()),
+                DefinitionKind::Const{..} |
+                DefinitionKind::Comment(_) |
+                DefinitionKind::Snippet(_) |
+                DefinitionKind::Directive(_) => {},
+            }
+        }
+        side
+    }
+
+    fn evaluate(&self, value: &Value) -> Option<i128> {
+        self.schema.evaluate(value).ok()
+    }
+
+    fn enum_values(&self, body: &'a EnumBody) -> Vec<(&'a str, i128)> {
+        body.variants.iter()
+            .map(|variant| (&variant.id.name[..], self.schema.value(&variant.id.name).unwrap_or(0)))
+            .collect()
+    }
+
+    /// An element type, with typedefs of plain types looked through
+    fn ty(&self, ty: &'a TypeSpec) -> Ty<'a> {
+        self.ty_depth(ty, 0)
+    }
+
+    fn ty_depth(&self, ty: &'a TypeSpec, depth: usize) -> Ty<'a> {
+        let name = match *ty {
+            TypeSpec::Primitive(ty, _) => return Ty::Primitive(ty),
+            TypeSpec::Enum(ref body) => return Ty::Enum(body, None),
+            TypeSpec::Struct(ref body) => return Ty::Struct(body, None),
+            TypeSpec::Union(ref body) => return Ty::Union(body, None),
+            TypeSpec::Named(ref id) => &id.name[..],
+        };
+        match self.by_name.get(name) {
+            Some(&&DefinitionKind::Enum{ref body, ..}) => Ty::Enum(body, Some(name)),
+            Some(&&DefinitionKind::Struct{ref body, ..}) => Ty::Struct(body, Some(name)),
+            Some(&&DefinitionKind::Union{ref body, ..}) => Ty::Union(body, Some(name)),
+            Some(&&DefinitionKind::Typedef(ref decl)) => match decl.kind {
+                // Resolution already rejected typedefs of themselves, but
+                // don't hang on one anyway
+                DeclarationKind::Scalar{ref ty, ..} if depth < 64 => self.ty_depth(ty, depth + 1),
+                _ => Ty::Alias(decl, name),
+            },
+            _ => Ty::Unknown(name),
+        }
+    }
+
+    /// How a declaration is encoded
+    fn shape(&self, decl: &'a Declaration) -> Shape<'a> {
+        match decl.kind {
+            DeclarationKind::Void => Shape::Void,
+            DeclarationKind::Scalar{ref ty, ..} => self.type_shape(ty),
+            DeclarationKind::Array{ref ty, ref size, ..} => {
+                Shape::Array(self.ty(ty), self.evaluate(size).unwrap_or(-1))
+            },
+            DeclarationKind::VarArray{ref ty, ref size, ..} => {
+                Shape::VarArray(self.ty(ty), size.as_ref().and_then(|size| self.evaluate(size)))
+            },
+            DeclarationKind::Opaque{ref size, ..} => Shape::Opaque(self.evaluate(size).unwrap_or(-1)),
+            DeclarationKind::VarOpaque{ref size, ..} => {
+                Shape::VarOpaque(size.as_ref().and_then(|size| self.evaluate(size)))
+            },
+            DeclarationKind::String{ref size, ..} => {
+                Shape::String(size.as_ref().and_then(|size| self.evaluate(size)))
+            },
+            DeclarationKind::Pointer{ref ty, ..} => Shape::Optional(self.ty(ty)),
+        }
+    }
+
+    /// A lone type, such as a field's or a procedure argument, which may be a
+    /// typedef of an array, string or opaque
+    fn type_shape(&self, ty: &'a TypeSpec) -> Shape<'a> {
+        match self.ty(ty) {
+            Ty::Alias(decl, _) => self.shape(decl),
+            ty => Shape::Scalar(ty),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Ty<'a> {
+    Primitive(Type),
+    // Inline bodies have no name
+    Enum(&'a EnumBody, Option<&'a str>),
+    Struct(&'a StructBody, Option<&'a str>),
+    Union(&'a UnionBody, Option<&'a str>),
+    /// A typedef of something other than a plain type
+    Alias(&'a Declaration, &'a str),
+    Unknown(&'a str),
+}
+
+impl<'a> Ty<'a> {
+    fn name(&self) -> Option<&'a str> {
+        match *self {
+            Ty::Enum(_, name) | Ty::Struct(_, name) | Ty::Union(_, name) => name,
+            Ty::Alias(_, name) | Ty::Unknown(name) => Some(name),
+            Ty::Primitive(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Shape<'a> {
+    Void,
+    Scalar(Ty<'a>),
+    Array(Ty<'a>, i128),
+    /// `None` without a maximum size
+    VarArray(Ty<'a>, Option<i128>),
+    Opaque(i128),
+    VarOpaque(Option<i128>),
+    String(Option<i128>),
+    Optional(Ty<'a>),
+}
+
+struct Comparison<'a> {
+    old: Side<'a>,
+    new: Side<'a>,
+    changes: Vec<Change>,
+    // Pairs of bodies being compared, for recursive types
+    visiting: RefCell<HashSet<(usize, usize)>>,
+}
+
+impl<'a> Comparison<'a> {
+    fn breaking(&mut self, message: String) {
+        self.changes.push(Change { breaking: true, message: message });
+    }
+
+    fn safe(&mut self, message: String) {
+        self.changes.push(Change { breaking: false, message: message });
+    }
+
+    fn types(&mut self) {
+        let old_types = self.old.types.clone();
+        let new_types = self.new.types.clone();
+        // Names aren't encoded, a type that's gone and a new one encoded
+        // the same way are a rename
+        let mut renamed = HashSet::new();
+        for &(name, old) in old_types.iter() {
+            if let Some(new) = self.new.by_name.get(name).cloned() {
+                self.definition(name, old, new);
+                continue;
+            }
+            let old_shape = self.old.named(name);
+            let new_name = new_types.iter()
+                .map(|&(new_name, _)| new_name)
+                .filter(|new_name| !self.old.by_name.contains_key(new_name) && !renamed.contains(new_name))
+                .find(|new_name| self.same_shape(old_shape, self.new.named(new_name)));
+            match new_name {
+                Some(new_name) => {
+                    renamed.insert(new_name);
+                    self.safe(format!("type `{}` was renamed to `{}`", name, new_name));
+                },
+                None => self.breaking(format!("type `{}` was removed", name)),
+            }
+        }
+        for &(name, _) in new_types.iter() {
+            if !self.old.by_name.contains_key(name) && !renamed.contains(name) {
+                self.safe(format!("type `{}` was added", name));
+            }
+        }
+    }
+
+    fn definition(&mut self, name: &str, old: &'a DefinitionKind, new: &'a DefinitionKind) {
+        match (old, new) {
+            (&DefinitionKind::Struct{body: ref old, ..}, &DefinitionKind::Struct{body: ref new, ..}) => {
+                self.fields(&format!("struct `{}`", name), old, new);
+            },
+            (&DefinitionKind::Enum{body: ref old, ..}, &DefinitionKind::Enum{body: ref new, ..}) => {
+                self.enum_values(&format!("enum `{}`", name), old, new);
+            },
+            (&DefinitionKind::Union{body: ref old, ..}, &DefinitionKind::Union{body: ref new, ..}) => {
+                self.union(&format!("union `{}`", name), old, new);
+            },
+            (&DefinitionKind::Typedef(ref old), &DefinitionKind::Typedef(ref new)) => {
+                self.declaration(&format!("typedef `{}`", name), old, new);
+            },
+            // `typedef struct {...} foo` and `struct foo {...}` are the same
+            _ => {
+                let (old_ty, new_ty) = (self.old.named(name), self.new.named(name));
+                if !self.same_shape(old_ty, new_ty) {
+                    self.breaking(format!("`{}` changed from {} to {}", name, kind_name(old), kind_name(new)));
+                }
+            },
+        }
+    }
+
+    fn fields(&mut self, what: &str, old: &'a StructBody, new: &'a StructBody) {
+        let old_names: Vec<&str> = old.fields.iter().filter_map(|f| f.id()).map(|id| &id.name[..]).collect();
+        let new_names: Vec<&str> = new.fields.iter().filter_map(|f| f.id()).map(|id| &id.name[..]).collect();
+        let mut sorted_old = old_names.clone();
+        let mut sorted_new = new_names.clone();
+        sorted_old.sort();
+        sorted_new.sort();
+        if old_names != new_names && sorted_old == sorted_new {
+            self.breaking(format!("fields of {} were reordered", what));
+            for field in old.fields.iter() {
+                let id = field.id().unwrap();
+                let other = new.fields.iter().find(|f| f.id().map(|i| &i.name) == Some(&id.name));
+                if let Some(other) = other {
+                    self.declaration(&format!("field `{}` of {}", id, what), field, other);
+                }
+            }
+            return;
+        }
+
+        for (old_field, new_field) in old.fields.iter().zip(new.fields.iter()) {
+            let old_name = field_name(old_field);
+            let new_name = field_name(new_field);
+            if old_name == new_name {
+                self.declaration(&format!("field `{}` of {}", old_name, what), old_field, new_field);
+            } else if self.same_declaration(old_field, new_field) {
+                self.safe(format!("field `{}` of {} was renamed to `{}`", old_name, what, new_name));
+            } else {
+                self.breaking(format!("field `{}` of {} was replaced by `{}` with a different encoding",
+                                      old_name, what, new_name));
+            }
+        }
+        for field in old.fields.iter().skip(new.fields.len()) {
+            self.breaking(format!("field `{}` was removed from {}", field_name(field), what));
+        }
+        for field in new.fields.iter().skip(old.fields.len()) {
+            self.breaking(format!("field `{}` was added to {}", field_name(field), what));
+        }
+    }
+
+    fn enum_values(&mut self, what: &str, old: &'a EnumBody, new: &'a EnumBody) {
+        let old_values = self.old.enum_values(old);
+        let new_values = self.new.enum_values(new);
+        for &(name, value) in old_values.iter() {
+            match new_values.iter().find(|v| v.0 == name) {
+                Some(&(_, new_value)) if new_value != value => {
+                    self.breaking(format!("value `{}` of {} changed from {} to {}", name, what, value, new_value));
+                },
+                Some(_) => {},
+                None => match new_values.iter().find(|v| v.1 == value) {
+                    Some(&(renamed, _)) if !old_values.iter().any(|v| v.0 == renamed) => {
+                        self.safe(format!("value `{}` of {} was renamed to `{}`", name, what, renamed));
+                    },
+                    _ => self.breaking(format!("value `{}` = {} was removed from {}", name, value, what)),
+                },
+            }
+        }
+        for &(name, value) in new_values.iter() {
+            if !old_values.iter().any(|v| v.0 == name || v.1 == value) {
+                self.safe(format!("value `{}` = {} was added to {}", name, value, what));
+            }
+        }
+    }
+
+    fn union(&mut self, what: &str, old: &'a UnionBody, new: &'a UnionBody) {
+        let change = format!("discriminant of {} changed from {} to {}", what,
+                             describe(&self.old, &old.discriminant), describe(&self.new, &new.discriminant));
+        if !self.same_discriminant(&old.discriminant, &new.discriminant) {
+            self.breaking(change);
+            return;
+        }
+        if !self.same_declaration(&old.discriminant, &new.discriminant) {
+            self.safe(change);
+        }
+        let old_arms = arms(&self.old, old);
+        let new_arms = arms(&self.new, new);
+        for &(value, ref label, decl) in old_arms.iter() {
+            match new_arms.iter().find(|arm| arm.0 == value) {
+                Some(&(_, _, new_decl)) => self.declaration(&format!("arm `{}` of {}", label, what), decl, new_decl),
+                None => self.breaking(format!("arm `{}` was removed from {}", label, what)),
+            }
+        }
+        for &(value, ref label, decl) in new_arms.iter() {
+            if old_arms.iter().any(|arm| arm.0 == value) {
+                continue;
+            }
+            // It used to take the default arm
+            match old.default {
+                Some(ref default) if !self.same_declaration(default, decl) => {
+                    self.breaking(format!("arm `{}` of {} no longer uses the default arm's encoding",
+                                          label, what));
+                },
+                _ => self.safe(format!("arm `{}` was added to {}", label, what)),
+            }
+        }
+        match (&old.default, &new.default) {
+            (&Some(ref old), &Some(ref new)) => self.declaration(&format!("default arm of {}", what), old, new),
+            (&Some(_), &None) => self.breaking(format!("default arm was removed from {}", what)),
+            (&None, &Some(_)) => self.safe(format!("default arm was added to {}", what)),
+            (&None, &None) => {},
+        }
+    }
+
+    fn declaration(&mut self, what: &str, old: &'a Declaration, new: &'a Declaration) {
+        if let (Some(old_ty), Some(new_ty)) = (old.type_spec(), new.type_spec()) {
+            if let (&DeclarationKind::Scalar{..}, &DeclarationKind::Scalar{..}) = (&old.kind, &new.kind) {
+                if self.same_name(old_ty, new_ty) {
+                    return;
+                }
+            }
+        }
+        let (old_shape, new_shape) = (self.old.shape(old), self.new.shape(new));
+        let (old_desc, new_desc) = (describe(&self.old, old), describe(&self.new, new));
+        self.shape_changes(what, old_shape, new_shape, &old_desc, &new_desc);
+    }
+
+    fn shape_changes(&mut self, what: &str, old: Shape<'a>, new: Shape<'a>, old_desc: &str, new_desc: &str) {
+        let changed = match (old, new) {
+            (Shape::Void, Shape::Void) => false,
+            (Shape::Scalar(old), Shape::Scalar(new)) |
+            (Shape::Optional(old), Shape::Optional(new)) => !self.same_ty(old, new),
+            (Shape::Array(old, old_size), Shape::Array(new, new_size)) => {
+                old_size != new_size || !self.same_ty(old, new)
+            },
+            (Shape::Opaque(old_size), Shape::Opaque(new_size)) => old_size != new_size,
+            (Shape::VarArray(old, old_max), Shape::VarArray(new, new_max)) => {
+                if !self.same_ty(old, new) {
+                    true
+                } else {
+                    self.bound(what, old_max, new_max);
+                    false
+                }
+            },
+            (Shape::VarOpaque(old_max), Shape::VarOpaque(new_max)) |
+            (Shape::String(old_max), Shape::String(new_max)) => {
+                self.bound(what, old_max, new_max);
+                false
+            },
+            _ => true,
+        };
+        if changed {
+            self.breaking(format!("{} changed from `{}` to `{}`", what, old_desc, new_desc));
+        }
+    }
+
+    fn bound(&mut self, what: &str, old: Option<i128>, new: Option<i128>) {
+        let limit = |max: Option<i128>| max.map_or("unlimited".to_string(), |max| max.to_string());
+        let tightened = match (old, new) {
+            (None, Some(_)) => true,
+            (Some(old), Some(new)) if new < old => true,
+            (Some(old), Some(new)) if new == old => return,
+            (None, None) => return,
+            _ => false,
+        };
+        let message = format!("maximum size of {} {} from {} to {}",
+                              what, if tightened { "tightened" } else { "loosened" }, limit(old), limit(new));
+        if tightened {
+            self.breaking(message);
+        } else {
+            self.safe(message);
+        }
+    }
+
+    fn programs(&mut self) {
+        let (old, new) = (self.old.programs.clone(), self.new.programs.clone());
+        let pairs = pair(&old, &new, |prog| (&prog.id.name[..], prog.number));
+        for &(old, new) in pairs.iter() {
+            match new {
+                Some(new) => {
+                    self.numbered(&format!("program `{}`", old.id), &old.id, old.number, &new.id.name, new.number);
+                    self.versions(old, new);
+                },
+                None => self.breaking(format!("program `{}` ({}) was removed", old.id, old.number)),
+            }
+        }
+        for prog in new.iter() {
+            if !pairs.iter().any(|&(_, new)| new.map_or(false, |new| new.id == prog.id)) {
+                self.safe(format!("program `{}` ({}) was added", prog.id, prog.number));
+            }
+        }
+    }
+
+    fn versions(&mut self, old: &'a Program, new: &'a Program) {
+        let pairs = pair(&old.versions, &new.versions, |v| (&v.id.name[..], v.number));
+        for &(old_version, new_version) in pairs.iter() {
+            match new_version {
+                Some(new_version) => {
+                    self.numbered(&format!("version `{}` of program `{}`", old_version.id, old.id),
+                                  &old_version.id, old_version.number, &new_version.id.name, new_version.number);
+                    self.procedures(old_version, new_version);
+                },
+                None => self.breaking(format!("version `{}` ({}) was removed from program `{}`",
+                                              old_version.id, old_version.number, old.id)),
+            }
+        }
+        for version in new.versions.iter() {
+            if !pairs.iter().any(|&(_, new)| new.map_or(false, |new| new.id == version.id)) {
+                self.safe(format!("version `{}` ({}) was added to program `{}`",
+                                  version.id, version.number, new.id));
+            }
+        }
+    }
+
+    fn procedures(&mut self, old: &'a Version, new: &'a Version) {
+        let pairs = pair(&old.procedures, &new.procedures, |p| (&p.id.name[..], p.number));
+        for &(old_proc, new_proc) in pairs.iter() {
+            let new_proc = match new_proc {
+                Some(new_proc) => new_proc,
+                None => {
+                    self.breaking(format!("procedure `{}` ({}) was removed from version `{}`",
+                                          old_proc.id, old_proc.number, old.id));
+                    continue;
+                },
+            };
+            let what = format!("procedure `{}` of version `{}`", old_proc.id, old.id);
+            self.numbered(&what, &old_proc.id, old_proc.number, &new_proc.id.name, new_proc.number);
+            if old_proc.arg_types.len() != new_proc.arg_types.len() {
+                let count = new_proc.arg_types.len();
+                self.breaking(format!("{} takes {} argument{} instead of {}", what, count,
+                                      if count == 1 { "" } else { "s" }, old_proc.arg_types.len()));
+            } else {
+                for (i, (old_arg, new_arg)) in old_proc.arg_types.iter().zip(new_proc.arg_types.iter()).enumerate() {
+                    self.procedure_type(&format!("argument {} of {}", i + 1, what), Some(old_arg), Some(new_arg));
+                }
+            }
+            self.procedure_type(&format!("result of {}", what),
+                                old_proc.return_type.as_ref(), new_proc.return_type.as_ref());
+        }
+        for procedure in new.procedures.iter() {
+            if !pairs.iter().any(|&(_, new)| new.map_or(false, |new| new.id == procedure.id)) {
+                self.safe(format!("procedure `{}` ({}) was added to version `{}`",
+                                  procedure.id, procedure.number, new.id));
+            }
+        }
+    }
+
+    fn procedure_type(&mut self, what: &str, old: Option<&'a TypeSpec>, new: Option<&'a TypeSpec>) {
+        if let (Some(old_ty), Some(new_ty)) = (old, new) {
+            if self.same_name(old_ty, new_ty) {
+                return;
+            }
+        }
+        let old_shape = old.map_or(Shape::Void, |ty| self.old.type_shape(ty));
+        let new_shape = new.map_or(Shape::Void, |ty| self.new.type_shape(ty));
+        let old_desc = old.map_or("void".to_string(), type_name);
+        let new_desc = new.map_or("void".to_string(), type_name);
+        self.shape_changes(what, old_shape, new_shape, &old_desc, &new_desc);
+    }
+
+    // Uses of a type defined on both sides, whose changes are reported
+    // where it's defined
+    fn same_name(&self, old: &TypeSpec, new: &TypeSpec) -> bool {
+        match (old, new) {
+            (&TypeSpec::Named(ref old), &TypeSpec::Named(ref new)) => {
+                old.name == new.name && self.new.by_name.contains_key(&old.name[..])
+            },
+            _ => false,
+        }
+    }
+
+    /// Report a renamed or renumbered program, version or procedure
+    fn numbered(&mut self, what: &str, old_id: &Ident, old_number: i128, new_name: &str, new_number: i128) {
+        if old_number != new_number {
+            self.breaking(format!("number of {} changed from {} to {}", what, old_number, new_number));
+        } else if old_id.name != new_name {
+            self.safe(format!("{} was renamed to `{}`", what, new_name));
+        }
+    }
+
+    fn same_declaration(&self, old: &'a Declaration, new: &'a Declaration) -> bool {
+        self.same_shape(self.old.shape(old), self.new.shape(new))
+    }
+
+    /// An enum is encoded as an `int`, the arms' values say which are valid
+    fn same_discriminant(&self, old: &'a Declaration, new: &'a Declaration) -> bool {
+        match (self.old.shape(old), self.new.shape(new)) {
+            (Shape::Scalar(Ty::Enum(..)), Shape::Scalar(Ty::Primitive(Type::Int))) |
+            (Shape::Scalar(Ty::Primitive(Type::Int)), Shape::Scalar(Ty::Enum(..))) => true,
+            _ => self.same_declaration(old, new),
+        }
+    }
+
+    fn same_shape(&self, old: Shape<'a>, new: Shape<'a>) -> bool {
+        match (old, new) {
+            (Shape::Void, Shape::Void) => true,
+            (Shape::Scalar(old), Shape::Scalar(new)) |
+            (Shape::Optional(old), Shape::Optional(new)) => self.same_ty(old, new),
+            (Shape::Array(old, old_size), Shape::Array(new, new_size)) => {
+                old_size == new_size && self.same_ty(old, new)
+            },
+            (Shape::VarArray(old, old_max), Shape::VarArray(new, new_max)) => {
+                old_max == new_max && self.same_ty(old, new)
+            },
+            (Shape::Opaque(old), Shape::Opaque(new)) => old == new,
+            (Shape::VarOpaque(old), Shape::VarOpaque(new)) |
+            (Shape::String(old), Shape::String(new)) => old == new,
+            _ => false,
+        }
+    }
+
+    /// Whether two types encode the same way. Types of the same name are,
+    /// whatever changed in them is reported where they're defined.
+    fn same_ty(&self, old: Ty<'a>, new: Ty<'a>) -> bool {
+        if let (Some(old_name), Some(new_name)) = (old.name(), new.name()) {
+            if old_name == new_name && self.new.by_name.contains_key(old_name) {
+                return true;
+            }
+        }
+        match (old, new) {
+            (Ty::Primitive(old), Ty::Primitive(new)) => old == new,
+            (Ty::Enum(old, _), Ty::Enum(new, _)) => {
+                let mut old_values: Vec<i128> = self.old.enum_values(old).into_iter().map(|v| v.1).collect();
+                let mut new_values: Vec<i128> = self.new.enum_values(new).into_iter().map(|v| v.1).collect();
+                old_values.sort();
+                new_values.sort();
+                old_values == new_values
+            },
+            (Ty::Struct(old, _), Ty::Struct(new, _)) => self.visit(old, new, |this| {
+                old.fields.len() == new.fields.len() &&
+                    old.fields.iter().zip(new.fields.iter()).all(|(o, n)| this.same_declaration(o, n))
+            }),
+            (Ty::Union(old, _), Ty::Union(new, _)) => self.visit(old, new, |this| {
+                let old_arms = arms(&this.old, old);
+                let new_arms = arms(&this.new, new);
+                this.same_discriminant(&old.discriminant, &new.discriminant) &&
+                    old_arms.len() == new_arms.len() &&
+                    old_arms.iter().all(|arm| new_arms.iter().any(|other| {
+                        other.0 == arm.0 && this.same_declaration(arm.2, other.2)
+                    })) &&
+                    match (&old.default, &new.default) {
+                        (&Some(ref old), &Some(ref new)) => this.same_declaration(old, new),
+                        (&None, &None) => true,
+                        _ => false,
+                    }
+            }),
+            (Ty::Alias(old, _), Ty::Alias(new, _)) => self.same_declaration(old, new),
+            _ => false,
+        }
+    }
+
+    // Compare two bodies, taking them as the same if they're already being
+    // compared further up, as recursive types are
+    fn visit<T, F>(&self, old: &T, new: &T, compare: F) -> bool where F: Fn(&Self) -> bool {
+        let key = (old as *const T as usize, new as *const T as usize);
+        if !self.visiting.borrow_mut().insert(key) {
+            return true;
+        }
+        let same = compare(self);
+        self.visiting.borrow_mut().remove(&key);
+        same
+    }
+}
+
+impl<'a> Side<'a> {
+    /// A named type as a type, for comparing definitions of different kinds
+    fn named(&self, name: &'a str) -> Shape<'a> {
+        match self.by_name.get(name) {
+            Some(&&DefinitionKind::Typedef(ref decl)) => self.shape(decl),
+            Some(&&DefinitionKind::Enum{ref body, ..}) => Shape::Scalar(Ty::Enum(body, None)),
+            Some(&&DefinitionKind::Struct{ref body, ..}) => Shape::Scalar(Ty::Struct(body, None)),
+            Some(&&DefinitionKind::Union{ref body, ..}) => Shape::Scalar(Ty::Union(body, None)),
+            _ => Shape::Scalar(Ty::Unknown(name)),
+        }
+    }
+}
+
+/// Match up programs, versions or procedures by name, then those left over
+/// by number
+fn pair<'a, T, F>(old: &'a [T], new: &'a [T], key: F) -> Vec<(&'a T, Option<&'a T>)>
+        where F: Fn(&'a T) -> (&'a str, i128) {
+    let mut taken = vec![false; new.len()];
+    let mut pairs: Vec<(&'a T, Option<&'a T>)> = old.iter().map(|item| {
+        let found = new.iter().position(|other| key(other).0 == key(item).0);
+        if let Some(i) = found {
+            taken[i] = true;
+        }
+        (item, found.map(|i| &new[i]))
+    }).collect();
+    for pair in pairs.iter_mut() {
+        if pair.1.is_some() {
+            continue;
+        }
+        let found = new.iter().enumerate()
+            .position(|(i, other)| !taken[i] && key(other).1 == key(pair.0).1 &&
+                      !old.iter().any(|item| key(item).0 == key(other).0));
+        if let Some(i) = found {
+            taken[i] = true;
+            pair.1 = Some(&new[i]);
+        }
+    }
+    pairs
+}
+
+// The arms of a union by the value of their case labels, with the label as
+// written
+fn arms<'a>(side: &Side<'a>, body: &'a UnionBody) -> Vec<(Option<i128>, String, &'a Declaration)> {
+    let mut arms = Vec::new();
+    for case in body.cases.iter() {
+        for value in case.values.iter() {
+            arms.push((side.evaluate(value), size_of(side, value), &case.decl));
+        }
+    }
+    arms
+}
+
+fn field_name(decl: &Declaration) -> &str {
+    decl.id().map_or("void", |id| &id.name[..])
+}
+
+fn kind_name(kind: &DefinitionKind) -> &'static str {
+    match *kind {
+        DefinitionKind::Struct{..} => "a struct",
+        DefinitionKind::Enum{..} => "an enum",
+        DefinitionKind::Union{..} => "a union",
+        _ => "a typedef",
+    }
+}
+
+fn type_name(ty: &TypeSpec) -> String {
+    match *ty {
+        TypeSpec::Primitive(ty, _) => ty.name().to_string(),
+        TypeSpec::Named(ref id) => id.name.clone(),
+        TypeSpec::Enum(_) => "enum {...}".to_string(),
+        TypeSpec::Struct(_) => "struct {...}".to_string(),
+        TypeSpec::Union(_) => "union {...}".to_string(),
+    }
+}
+
+// A declaration without its name, as in messages
+fn describe(side: &Side, decl: &Declaration) -> String {
+    let bound = |max: &Option<Value>| max.as_ref().map_or(String::new(), |max| size_of(side, max));
+    match decl.kind {
+        DeclarationKind::Void => "void".to_string(),
+        DeclarationKind::Scalar{ref ty, ..} => type_name(ty),
+        DeclarationKind::Array{ref ty, ref size, ..} => format!("{}[{}]", type_name(ty), size_of(side, size)),
+        DeclarationKind::VarArray{ref ty, ref size, ..} => format!("{}<{}>", type_name(ty), bound(size)),
+        DeclarationKind::Opaque{ref size, ..} => format!("opaque[{}]", size_of(side, size)),
+        DeclarationKind::VarOpaque{ref size, ..} => format!("opaque<{}>", bound(size)),
+        DeclarationKind::String{ref size, ..} => format!("string<{}>", bound(size)),
+        DeclarationKind::Pointer{ref ty, ..} => format!("{} *", type_name(ty)),
+    }
+}
+
+// A size or case label as written, or its value if it's an expression
+fn size_of(side: &Side, value: &Value) -> String {
+    match *value {
+        Value::Named(ref id) => id.name.clone(),
+        _ => side.evaluate(value).map_or("?".to_string(), |v| v.to_string()),
+    }
+}
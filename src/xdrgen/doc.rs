@@ -0,0 +1,475 @@
+// Reference documentation for .x files, as Markdown or HTML. Programs come
+// first, then types, then constants. Every type named links to where it is
+// defined, and comments on definitions become their descriptions.
+use std::path::Path;
+
+use ast::*;
+use codegen::{self, doc_lines};
+use parser::ParseError;
+use preprocessor::Preprocessed;
+use resolve::Schema;
+
+/// What `generate` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    /// A standalone page
+    Html,
+}
+
+/// Document preprocessed inputs, resolved the way code is generated from
+/// them
+pub fn generate(units: &[Preprocessed], format: Format) -> Result<String, ParseError> {
+    let (specs, schema) = try!(codegen::load(units));
+    let mut doc = Doc { format: format, schema: &schema, out: String::new() };
+
+    let mut programs = Vec::new();
+    let mut types = Vec::new();
+    let mut constants = Vec::new();
+    for (unit, spec) in units.iter().zip(specs.iter()) {
+        for def in spec.definitions.iter() {
+            let file = unit.origin(def.span.start).file;
+            match def.kind {
+                DefinitionKind::Program(ref prog) => programs.push((prog, None, file)),
+                DefinitionKind::Namespace{ref id, programs: ref progs} => {
+                    programs.extend(progs.iter().map(|prog| (prog, Some(id), file.clone())));
+                },
+                DefinitionKind::Const{ref id, ref value} => constants.push((id, value, def)),
+                DefinitionKind::Typedef(_) |
+                DefinitionKind::Enum{..} |
+                DefinitionKind::Struct{..} |
+                DefinitionKind::Union{..} => types.push((def, file)),
+                DefinitionKind::Comment(_) | DefinitionKind::Snippet(_) | DefinitionKind::Directive(_) => {},
+            }
+        }
+    }
+
+    let inputs: Vec<String> = units.iter()
+        .filter_map(|unit| unit.lines.first())
+        .map(|line| Path::new(&line.file).file_name().map_or(line.file.clone(), |name| {
+            name.to_string_lossy().into_owned()
+        }))
+        .collect();
+    let title = format!("{} reference", inputs.join(", "));
+    doc.begin(&title);
+
+    if !programs.is_empty() {
+        doc.heading(2, None, "Programs");
+        for &(prog, namespace, ref file) in programs.iter() {
+            doc.program(prog, namespace, file);
+        }
+    }
+    if !types.is_empty() {
+        doc.heading(2, None, "Types");
+        for &(def, ref file) in types.iter() {
+            doc.type_definition(def, file);
+        }
+    }
+    if !constants.is_empty() {
+        doc.heading(2, None, "Constants");
+        let rows = constants.iter().map(|&(id, value, def)| vec![
+            format!("{}{}", anchor(&format!("const-{}", id.name)), doc.code(&id.name)),
+            schema.evaluate(value).map(|v| v.to_string()).unwrap_or_default(),
+            doc.description(&def.comments),
+        ]).collect();
+        doc.table(&["Name", "Value", "Description"], rows);
+    }
+
+    doc.end();
+    Ok(doc.out)
+}
+
+struct Doc<'a> {
+    format: Format,
+    schema: &'a Schema,
+    out: String,
+}
+
+impl<'a> Doc<'a> {
+    fn begin(&mut self, title: &str) {
+        if self.format == Format::Html {
+            self.out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+            self.out.push_str(&format!("<title>{}</title>\n", self.text(title)));
+            self.out.push_str("<style>\n\
+                               body { font-family: sans-serif; max-width: 60em; margin: auto; }\n\
+                               table { border-collapse: collapse; }\n\
+                               th, td { border: 1px solid #ccc; padding: 0.2em 0.5em; \
+                               text-align: left; vertical-align: top; }\n\
+                               </style>\n</head>\n<body>\n");
+        }
+        let title = self.text(title);
+        self.heading(1, None, &title);
+    }
+
+    fn end(&mut self) {
+        if self.format == Format::Html {
+            self.out.push_str("</body>\n</html>\n");
+        }
+    }
+
+    /// `text` is markup already
+    fn heading(&mut self, level: usize, id: Option<&str>, text: &str) {
+        match self.format {
+            Format::Markdown => {
+                if let Some(id) = id {
+                    self.out.push_str(&anchor(id));
+                    self.out.push('\n');
+                }
+                self.out.push_str(&format!("{} {}\n\n", "#".repeat(level), text));
+            },
+            Format::Html => {
+                let id = id.map_or(String::new(), |id| format!(" id=\"{}\"", id));
+                self.out.push_str(&format!("<h{}{}>{}</h{}>\n", level, id, text, level));
+            },
+        }
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.format {
+            Format::Markdown => self.out.push_str(&format!("{}\n\n", text)),
+            Format::Html => self.out.push_str(&format!("<p>{}</p>\n", text)),
+        }
+    }
+
+    fn table(&mut self, headers: &[&str], rows: Vec<Vec<String>>) {
+        match self.format {
+            Format::Markdown => {
+                self.out.push_str(&format!("| {} |\n", headers.join(" | ")));
+                self.out.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+                for row in rows {
+                    self.out.push_str(&format!("| {} |\n", row.join(" | ")));
+                }
+                self.out.push('\n');
+            },
+            Format::Html => {
+                self.out.push_str("<table>\n<tr>");
+                for header in headers {
+                    self.out.push_str(&format!("<th>{}</th>", header));
+                }
+                self.out.push_str("</tr>\n");
+                for row in rows {
+                    self.out.push_str("<tr>");
+                    for cell in row {
+                        self.out.push_str(&format!("<td>{}</td>", cell));
+                    }
+                    self.out.push_str("</tr>\n");
+                }
+                self.out.push_str("</table>\n");
+            },
+        }
+    }
+
+    /// Plain text as markup
+    fn text(&self, text: &str) -> String {
+        let mut escaped = String::new();
+        for c in text.chars() {
+            match (self.format, c) {
+                (Format::Html, '&') => escaped.push_str("&amp;"),
+                (Format::Html, '<') => escaped.push_str("&lt;"),
+                (Format::Html, '>') => escaped.push_str("&gt;"),
+                (Format::Markdown, '\\') | (Format::Markdown, '`') | (Format::Markdown, '*') |
+                (Format::Markdown, '_') | (Format::Markdown, '[') | (Format::Markdown, ']') |
+                (Format::Markdown, '<') | (Format::Markdown, '>') | (Format::Markdown, '|') => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                },
+                (_, c) => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    fn code(&self, text: &str) -> String {
+        match self.format {
+            // Table cells end at `|` even in code spans
+            Format::Markdown => format!("`{}`", text.replace('|', "\\|")),
+            Format::Html => format!("<code>{}</code>", self.text(text)),
+        }
+    }
+
+    /// `text` is markup already
+    fn link(&self, text: &str, id: &str) -> String {
+        match self.format {
+            Format::Markdown => format!("[{}](#{})", text, id),
+            Format::Html => format!("<a href=\"#{}\">{}</a>", id, text),
+        }
+    }
+
+    // Comments as one line of text, for table cells
+    fn description(&self, comments: &[String]) -> String {
+        let lines: Vec<String> = comments.iter()
+            .map(|comment| doc_lines(&[comment.clone()]).join(" "))
+            .filter(|text| !text.is_empty())
+            .map(|text| self.text(&text))
+            .collect();
+        // Separate comments stay on separate lines
+        lines.join("<br>")
+    }
+
+    // Comments as paragraphs, split at blank lines
+    fn description_paragraphs(&mut self, comments: &[String]) {
+        let lines = doc_lines(comments);
+        for paragraph in lines.split(|line| line.is_empty()) {
+            let text = self.text(&paragraph.join(" "));
+            self.paragraph(&text);
+        }
+    }
+
+    fn program(&mut self, prog: &Program, namespace: Option<&Ident>, file: &str) {
+        let title = format!("Program {} = {} ({:#x})", self.code(&prog.id.name), prog.number, prog.number);
+        self.heading(3, Some(&format!("program-{}", prog.id.name)), &title);
+        let mut defined = format!("Defined in {}", self.code(file));
+        if let Some(namespace) = namespace {
+            defined.push_str(&format!(", in namespace {}", self.code(&namespace.name)));
+        }
+        defined.push('.');
+        self.paragraph(&defined);
+        self.description_paragraphs(&prog.comments);
+
+        for version in prog.versions.iter() {
+            let title = format!("Version {} = {}", self.code(&version.id.name), version.number);
+            self.heading(4, Some(&format!("program-{}-{}", prog.id.name, version.id.name)), &title);
+            self.description_paragraphs(&version.comments);
+            let rows = version.procedures.iter().map(|procedure| {
+                let arguments = if procedure.arg_types.is_empty() {
+                    self.code("void")
+                } else {
+                    procedure.arg_types.iter().map(|ty| self.type_spec(ty, "")).collect::<Vec<_>>().join(", ")
+                };
+                vec![
+                    self.code(&procedure.id.name),
+                    procedure.number.to_string(),
+                    arguments,
+                    procedure.return_type.as_ref().map_or_else(|| self.code("void"), |ty| self.type_spec(ty, "")),
+                    self.description(&procedure.comments),
+                ]
+            }).collect();
+            self.table(&["Procedure", "Number", "Arguments", "Result", "Description"], rows);
+        }
+    }
+
+    fn type_definition(&mut self, def: &Definition, file: &str) {
+        let defined = format!("Defined in {}.", self.code(file));
+        match def.kind {
+            DefinitionKind::Enum{ref id, ref body} => self.enum_body("enum", &id.name, &def.comments, &defined, body),
+            DefinitionKind::Struct{ref id, ref body} => {
+                self.struct_body("struct", &id.name, &def.comments, &defined, body);
+            },
+            DefinitionKind::Union{ref id, ref body} => {
+                self.union_body("union", &id.name, &def.comments, &defined, body);
+            },
+            DefinitionKind::Typedef(ref decl) => {
+                let name = decl.id().map_or("", |id| &id.name[..]);
+                // `typedef struct {...} name` reads like `struct name {...}`
+                match decl.kind {
+                    DeclarationKind::Scalar{ty: TypeSpec::Enum(ref body), ..} => {
+                        return self.enum_body("typedef enum", name, &def.comments, &defined, body);
+                    },
+                    DeclarationKind::Scalar{ty: TypeSpec::Struct(ref body), ..} => {
+                        return self.struct_body("typedef struct", name, &def.comments, &defined, body);
+                    },
+                    DeclarationKind::Scalar{ty: TypeSpec::Union(ref body), ..} => {
+                        return self.union_body("typedef union", name, &def.comments, &defined, body);
+                    },
+                    _ => {},
+                }
+                self.type_heading("typedef", name);
+                self.paragraph(&defined);
+                self.description_paragraphs(&def.comments);
+                let ty = format!("Type: {}.", self.declaration_type(decl, name));
+                self.paragraph(&ty);
+            },
+            _ => {},
+        }
+    }
+
+    // Named types get level 3 headings, inline ones (`outer.field`) are
+    // nested under them
+    fn type_heading(&mut self, kind: &str, name: &str) {
+        let level = (3 + name.matches('.').count()).min(6);
+        let title = format!("{} {}", kind, self.code(name));
+        self.heading(level, Some(&format!("type-{}", name)), &title);
+    }
+
+    fn enum_body(&mut self, kind: &str, name: &str, comments: &[String], defined: &str, body: &EnumBody) {
+        self.type_heading(kind, name);
+        self.paragraph(defined);
+        self.description_paragraphs(comments);
+        let rows = body.variants.iter().map(|variant| vec![
+            self.code(&variant.id.name),
+            self.schema.value(&variant.id.name).map_or(String::new(), |value| value.to_string()),
+            self.description(&variant.comments),
+        ]).collect();
+        self.table(&["Name", "Value", "Description"], rows);
+    }
+
+    fn struct_body(&mut self, kind: &str, name: &str, comments: &[String], defined: &str, body: &StructBody) {
+        self.type_heading(kind, name);
+        self.paragraph(defined);
+        self.description_paragraphs(comments);
+        let rows = body.fields.iter().map(|field| {
+            let field_name = field.id().map_or("void", |id| &id.name[..]);
+            vec![
+                self.code(field_name),
+                self.declaration_type(field, &format!("{}.{}", name, field_name)),
+                self.description(&field.comments),
+            ]
+        }).collect();
+        self.table(&["Field", "Type", "Description"], rows);
+        for field in body.fields.iter() {
+            self.inline_types(field, name);
+        }
+    }
+
+    fn union_body(&mut self, kind: &str, name: &str, comments: &[String], defined: &str, body: &UnionBody) {
+        self.type_heading(kind, name);
+        self.paragraph(defined);
+        self.description_paragraphs(comments);
+        let discriminant = body.discriminant.id().map_or("", |id| &id.name[..]);
+        let switch = format!("Switches on {} of type {}.", self.code(discriminant),
+                             self.declaration_type(&body.discriminant, &format!("{}.{}", name, discriminant)));
+        self.paragraph(&switch);
+
+        let mut rows = Vec::new();
+        for case in body.cases.iter() {
+            let labels: Vec<String> = case.values.iter().map(|value| self.value(value)).collect();
+            rows.push(self.arm_row(labels.join(", "), &case.decl, name, &case.comments));
+        }
+        if let Some(ref default) = body.default {
+            let label = self.text("default");
+            rows.push(self.arm_row(label, default, name, &[]));
+        }
+        self.table(&["Case", "Arm", "Type", "Description"], rows);
+        self.inline_types(&body.discriminant, name);
+        for case in body.cases.iter() {
+            self.inline_types(&case.decl, name);
+        }
+        if let Some(ref default) = body.default {
+            self.inline_types(default, name);
+        }
+    }
+
+    fn arm_row(&self, labels: String, decl: &Declaration, name: &str, comments: &[String]) -> Vec<String> {
+        let arm = decl.id().map_or("void", |id| &id.name[..]);
+        let comments: Vec<String> = comments.iter().chain(decl.comments.iter()).cloned().collect();
+        vec![
+            labels,
+            self.code(arm),
+            self.declaration_type(decl, &format!("{}.{}", name, arm)),
+            self.description(&comments),
+        ]
+    }
+
+    // Sections for enums, structs and unions declared inside a type, which
+    // its table links to
+    fn inline_types(&mut self, decl: &Declaration, outer: &str) {
+        let name = format!("{}.{}", outer, decl.id().map_or("void", |id| &id.name[..]));
+        let defined = format!("Declared in {}.", self.link(&self.code(outer), &format!("type-{}", outer)));
+        match decl.type_spec() {
+            Some(&TypeSpec::Enum(ref body)) => self.enum_body("enum", &name, &decl.comments, &defined, body),
+            Some(&TypeSpec::Struct(ref body)) => self.struct_body("struct", &name, &decl.comments, &defined, body),
+            Some(&TypeSpec::Union(ref body)) => self.union_body("union", &name, &decl.comments, &defined, body),
+            _ => {},
+        }
+    }
+
+    // A type as markup, linking to where it is defined. `inline` names the
+    // section for an enum, struct or union declared in place.
+    fn type_spec(&self, ty: &TypeSpec, inline: &str) -> String {
+        self.markup(&self.type_parts(ty, inline))
+    }
+
+    fn declaration_type(&self, decl: &Declaration, inline: &str) -> String {
+        self.markup(&self.declaration_parts(decl, inline))
+    }
+
+    // A size or case label, linking to the constant it was written as
+    fn value(&self, value: &Value) -> String {
+        self.markup(&self.value_parts(value))
+    }
+
+    fn type_parts(&self, ty: &TypeSpec, inline: &str) -> Vec<Part> {
+        let (text, id) = match *ty {
+            TypeSpec::Primitive(ty, _) => return vec![(ty.name().to_string(), None)],
+            TypeSpec::Named(ref id) => (id.name.clone(), id.name.clone()),
+            TypeSpec::Enum(_) => ("enum {...}".to_string(), inline.to_string()),
+            TypeSpec::Struct(_) => ("struct {...}".to_string(), inline.to_string()),
+            TypeSpec::Union(_) => ("union {...}".to_string(), inline.to_string()),
+        };
+        vec![(text, Some(format!("type-{}", id)))]
+    }
+
+    fn declaration_parts(&self, decl: &Declaration, inline: &str) -> Vec<Part> {
+        let code = |text: &str| vec![(text.to_string(), None)];
+        let bound = |size: &Option<Value>| size.as_ref().map_or(vec![], |size| self.value_parts(size));
+        let (mut parts, size, close) = match decl.kind {
+            DeclarationKind::Void => return code("void"),
+            DeclarationKind::Scalar{ref ty, ..} => return self.type_parts(ty, inline),
+            DeclarationKind::Pointer{ref ty, ..} => {
+                let mut parts = self.type_parts(ty, inline);
+                parts.push(("*".to_string(), None));
+                return parts;
+            },
+            DeclarationKind::Array{ref ty, ref size, ..} => {
+                let mut parts = self.type_parts(ty, inline);
+                parts.push(("[".to_string(), None));
+                (parts, self.value_parts(size), "]")
+            },
+            DeclarationKind::VarArray{ref ty, ref size, ..} => {
+                let mut parts = self.type_parts(ty, inline);
+                parts.push(("<".to_string(), None));
+                (parts, bound(size), ">")
+            },
+            DeclarationKind::Opaque{ref size, ..} => (code("opaque["), self.value_parts(size), "]"),
+            DeclarationKind::VarOpaque{ref size, ..} => (code("opaque<"), bound(size), ">"),
+            DeclarationKind::String{ref size, ..} => (code("string<"), bound(size), ">"),
+        };
+        parts.extend(size);
+        parts.push((close.to_string(), None));
+        parts
+    }
+
+    fn value_parts(&self, value: &Value) -> Vec<Part> {
+        match *value {
+            Value::Named(ref id) if self.schema.constants.contains_key(&id.name) => {
+                vec![(id.name.clone(), Some(format!("const-{}", id.name)))]
+            },
+            Value::Named(ref id) => vec![(id.name.clone(), None)],
+            _ => vec![(self.schema.evaluate(value).map(|v| v.to_string()).unwrap_or_default(), None)],
+        }
+    }
+
+    // Code, with the parts that link somewhere as links. Neighbouring parts
+    // that don't are joined, as Markdown can't have one code span right
+    // after another.
+    fn markup(&self, parts: &[Part]) -> String {
+        let mut out = String::new();
+        let mut code = String::new();
+        for &(ref text, ref id) in parts {
+            match *id {
+                Some(ref id) => {
+                    if !code.is_empty() {
+                        out.push_str(&self.code(&code));
+                        code.clear();
+                    }
+                    out.push_str(&self.link(&self.code(text), id));
+                },
+                None => code.push_str(text),
+            }
+        }
+        if !code.is_empty() {
+            out.push_str(&self.code(&code));
+        }
+        out
+    }
+}
+
+// Code text, and the anchor it links to
+type Part = (String, Option<String>);
+
+fn anchor(id: &str) -> String {
+    format!("<a id=\"{}\"></a>", id)
+}
@@ -6,18 +6,18 @@ pub fn proto_fn<S: AsRef<str>>(codec: S, app_codec: S, wr: &mut CodeWriter) {
     });
 }
 
-pub fn codec_fns<S: AsRef<str>>(prog_name: S, wr: &mut CodeWriter) {
+pub fn codec_fns<S: AsRef<str>>(encoder_fn: S, wr: &mut CodeWriter) {
     wr.expr_block("fn decode(&mut self, buf: &mut EasyBuf) -> io::Result<Option<Self::In>>", "", |wr| {
         wr.write_line("unreachable!()");
     });
     wr.expr_block("fn encode(&mut self, msg: Self::Out, buf: &mut Vec<u8>) -> io::Result<()>", "", |wr| {
-        wr.write_line("encode(msg, buf)");
+        wr.write_line(format!("{}(msg, buf)", encoder_fn.as_ref()));
     });
 }
 
-pub fn app_codec_fn<S: AsRef<str>>(prog_name: S, wr: &mut CodeWriter) {
+pub fn app_codec_fn<S: AsRef<str>>(decoder_fn: S, wr: &mut CodeWriter) {
     wr.expr_block("fn app_decode(&mut self, prog: u32, version: u32, procedure: u32, buf: &mut EasyBuf) -> io::Result<Option<Self::In>>", "", |wr| {
-        wr.write_line(&format!("{}_prog_decode(prog, version, procedure, buf)", prog_name.as_ref()));
+        wr.write_line(&format!("{}(prog, version, procedure, buf)", decoder_fn.as_ref()));
     });
 }
 
@@ -111,15 +111,15 @@ pub fn version_decoder_call<S: AsRef<str>>(fn_name: S, wr: &mut CodeWriter) {
     wr.write_line(&format!("{}(procedure, buf)", fn_name.as_ref()));
 }
 
-pub fn version_decoder_finalize<S: AsRef<str>>(prog_name: S, ver_num: i64,
+pub fn version_decoder_finalize<S: AsRef<str>>(prog_name: S, ver_num: i128,
                                                wr: &mut CodeWriter) {
-    wr.write_line(&format!("Ok(Some({}Request::V{}(request.unwrap().unwrap())))",
+    wr.write_line(&format!("Ok(request?.map({}Request::V{}))",
         prog_name.as_ref(), ver_num));
 }
 
 pub fn proc_decoder<S1: AsRef<str>, S2: AsRef<str>, F>(prog_name: S1,
                                                        fn_name: S2,
-                                                       ver_num: i64,
+                                                       ver_num: i128,
                                                        wr: &mut CodeWriter,
                                                        cb: F)
         where F : Fn(&mut CodeWriter) {
@@ -147,8 +147,8 @@ r###"let res{0} = serde_xdr::from_bytes::<{1}>(buf.as_slice());
                     return Err(i);
                 }},
                 serde_xdr::EncoderError::Unknown(s) => {{
-                    return Err(io::Error::new(io::ErrorKind::Other,
-                        format!("argument {0} parse failure: {{}}", s)));
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("GARBAGE_ARGS: argument {0} parse failure: {{}}", s)));
                 }}
             }}
         }}
@@ -165,14 +165,15 @@ pub fn proc_decoder_finalize<S1: AsRef<str>, S2: AsRef<str>>(
     wr.raw_write("))\n");
 }
 
-pub fn encoder<S: AsRef<str>, F>(prog_name: S, wr: &mut CodeWriter, cb: F)
+pub fn encoder<S1: AsRef<str>, S2: AsRef<str>, F>(prog_name: S1, fn_name: S2,
+                                                  wr: &mut CodeWriter, cb: F)
         where F : Fn(&mut CodeWriter) {
     wr.expr_block(&format!(
-        "pub fn encode(msg: {}Response, buf: &mut Vec<u8>) -> io::Result<()>",
-        prog_name.as_ref()), "", cb);
+        "pub fn {}(msg: {}Response, buf: &mut Vec<u8>) -> io::Result<()>",
+        fn_name.as_ref(), prog_name.as_ref()), "", cb);
 }
 
-pub fn encoder_version<S: AsRef<str>, F>(prog_name: S, ver_num: i64,
+pub fn encoder_version<S: AsRef<str>, F>(prog_name: S, ver_num: i128,
                                          wr: &mut CodeWriter, cb:F)
         where F : Fn(&mut CodeWriter) {
     wr.match_option(&format!("{}Response::V{}", prog_name.as_ref(), ver_num),
@@ -181,7 +182,7 @@ pub fn encoder_version<S: AsRef<str>, F>(prog_name: S, ver_num: i64,
 
 pub fn encoder_proc<S1: AsRef<str>, S2: AsRef<str>>(prog_name: S1,
                                                     proc_name: S2,
-                                                    ver_num: i64,
+                                                    ver_num: i128,
                                                     has_return: bool,
                                                     wr: &mut CodeWriter) {
     let mut arg_list = if has_return {
@@ -193,13 +194,13 @@ pub fn encoder_proc<S1: AsRef<str>, S2: AsRef<str>>(prog_name: S1,
     wr.match_option(&format!("{}ResponseV{}::{}", prog_name.as_ref(), ver_num,
             proc_name.as_ref()), &arg_list, |wr| {
         if has_return {
-            wr.write_line("try!(serde_xdr::to_bytes(&r, buf));");
+            wr.write_line("serde_xdr::to_bytes(&r, buf)?;");
         }
     });
 }
 
 pub fn wrap_proc_result<S1: AsRef<str>, S2: AsRef<str>>(prog_name: S1,
-                                                        ver_num: i64,
+                                                        ver_num: i128,
                                                         proc_name: S2,
                                                         has_return: bool,
                                                         wr: &mut CodeWriter) {
@@ -215,7 +216,7 @@ pub fn wrap_proc_result<S1: AsRef<str>, S2: AsRef<str>>(prog_name: S1,
     prog_name.as_ref(), ver_num, proc_name.as_ref(), wrapper_arg));
 }
 
-pub fn wrap_version_result<S: AsRef<str>>(prog_name: S, ver_num: i64,
+pub fn wrap_version_result<S: AsRef<str>>(prog_name: S, ver_num: i128,
                                           wr: &mut CodeWriter) {
     wr.write_line(
 &format!(r###"res.map(move |r| {{
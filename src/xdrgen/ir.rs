@@ -0,0 +1,392 @@
+//! The resolved schema as JSON, for tools that want the definitions without
+//! parsing XDR. Written by `xdrgen --emit-ir <file>` and `Builder::ir`.
+//!
+//! The format is versioned by the top level `version`. Within a version,
+//! fields are only ever added, never renamed, removed or given another
+//! meaning. Numbers are JSON integers and may be outside what a double holds
+//! exactly, for `hyper` constants. Objects keep the key order shown here, and
+//! definitions are listed in the order of the input files.
+//!
+//! ```text
+//! {
+//!   "format": "xdrgen-ir",
+//!   "version": 1,
+//!   "files": [<file>...],         every file read, inputs and #includes
+//!   "constants": [<constant>...],
+//!   "types": [<type definition>...],
+//!   "programs": [<program>...]
+//! }
+//! ```
+//!
+//! Every definition has a `file` it was written in and a `doc`, the text of
+//! the comments on it with the comment markers taken off, or `null`.
+//!
+//! - constant: `{"name", "value", "file", "doc"}`
+//! - type definition: `{"name", "file", "doc"}` and the fields of the type
+//!   it defines. `typedef` definitions are `{"name", "file", "doc", "kind":
+//!   "typedef", "type": <type>}`.
+//! - program: `{"name", "number", "namespace", "file", "doc", "versions":
+//!   [<version>...]}`, where `namespace` is the name of the `namespace`
+//!   block around it or `null`
+//! - version: `{"name", "number", "doc", "procedures": [<procedure>...]}`
+//! - procedure: `{"name", "number", "doc", "arguments": [<type>...],
+//!   "result": <type>}`
+//! - declaration, a struct field, union arm or discriminant: `{"name",
+//!   "type", "doc"}`. The name of `void` is `null`.
+//!
+//! A type is an object with a `kind`:
+//!
+//! - `{"kind": "primitive", "name"}`, where `name` is one of `bool`, `int`,
+//!   `unsigned int`, `hyper`, `unsigned hyper`, `float`, `double` and
+//!   `quadruple`
+//! - `{"kind": "ref", "name", "definition"}`, a type defined by name, where
+//!   `definition` is `enum`, `struct`, `union` or `typedef`
+//! - `{"kind": "enum", "values": [{"name", "value", "doc"}...]}`
+//! - `{"kind": "struct", "fields": [<declaration>...]}`
+//! - `{"kind": "union", "discriminant": <declaration>, "arms": [{"cases":
+//!   [{"name", "value"}...], "doc", "declaration"}...], "default":
+//!   <declaration> or null}`. Case `name`s are the enum values or constants
+//!   the case was written with, or `null` for numbers.
+//! - `{"kind": "array", "element": <type>, "length", "constant"}` (`T x[N]`)
+//! - `{"kind": "vararray", "element": <type>, "max", "constant"}` (`T x<N>`)
+//! - `{"kind": "opaque", "length", "constant"}` (`opaque x[N]`)
+//! - `{"kind": "varopaque", "max", "constant"}` (`opaque x<N>`)
+//! - `{"kind": "string", "max", "constant"}` (`string x<N>`)
+//! - `{"kind": "optional", "element": <type>}` (`T *x`)
+//! - `{"kind": "void"}`
+//!
+//! Sizes are evaluated. `constant` is the name of the constant a size was
+//! written as, or `null`. `max` is `null` when there is no maximum.
+use std::fmt::Write;
+
+use ast::*;
+use codegen::{self, doc_lines};
+use parser::ParseError;
+use preprocessor::Preprocessed;
+use resolve::{Schema, TypeKind};
+
+/// The version of the format `emit` writes
+pub const VERSION: i128 = 1;
+
+/// The resolved schema of preprocessed inputs, as JSON in the format above
+pub fn emit(units: &[Preprocessed]) -> Result<String, ParseError> {
+    let (specs, schema) = try!(codegen::load(units));
+    let mut files = Vec::new();
+    let mut constants = Vec::new();
+    let mut types = Vec::new();
+    let mut programs = Vec::new();
+    for (unit, spec) in units.iter().zip(specs.iter()) {
+        for file in unit.lines.iter().map(|line| &line.file).chain(unit.includes.iter()) {
+            if !files.contains(file) {
+                files.push(file.clone());
+            }
+        }
+        let ir = Ir { schema: &schema };
+        for def in spec.definitions.iter() {
+            let file = Json::String(unit.origin(def.span.start).file);
+            match def.kind {
+                DefinitionKind::Const{ref id, ref value} => constants.push(Json::Object(vec![
+                    ("name", Json::string(&id.name)),
+                    ("value", ir.value(value)),
+                    ("file", file),
+                    ("doc", doc(&def.comments)),
+                ])),
+                DefinitionKind::Typedef(ref decl) => types.push(Json::Object(vec![
+                    ("name", decl.id().map_or(Json::Null, |id| Json::string(&id.name))),
+                    ("file", file),
+                    ("doc", doc(&def.comments)),
+                    ("kind", Json::string("typedef")),
+                    ("type", ir.declaration_type(decl)),
+                ])),
+                DefinitionKind::Enum{ref id, ref body} => {
+                    types.push(ir.named(id, file, &def.comments, ir.enum_body(body)));
+                },
+                DefinitionKind::Struct{ref id, ref body} => {
+                    types.push(ir.named(id, file, &def.comments, ir.struct_body(body)));
+                },
+                DefinitionKind::Union{ref id, ref body} => {
+                    types.push(ir.named(id, file, &def.comments, ir.union_body(body)));
+                },
+                DefinitionKind::Program(ref prog) => programs.push(ir.program(prog, None, file)),
+                DefinitionKind::Namespace{ref id, programs: ref progs} => {
+                    for prog in progs.iter() {
+                        programs.push(ir.program(prog, Some(id), file.clone()));
+                    }
+                },
+                DefinitionKind::Comment(_) | DefinitionKind::Snippet(_) | DefinitionKind::Directive(_) => {},
+            }
+        }
+    }
+
+    let json = Json::Object(vec![
+        ("format", Json::string("xdrgen-ir")),
+        ("version", Json::Number(VERSION)),
+        ("files", Json::Array(files.into_iter().map(Json::String).collect())),
+        ("constants", Json::Array(constants)),
+        ("types", Json::Array(types)),
+        ("programs", Json::Array(programs)),
+    ]);
+    let mut out = String::new();
+    json.write(&mut out, 0);
+    out.push('\n');
+    Ok(out)
+}
+
+struct Ir<'a> {
+    schema: &'a Schema,
+}
+
+impl<'a> Ir<'a> {
+    fn value(&self, value: &Value) -> Json {
+        self.schema.evaluate(value).map(Json::Number).unwrap_or(Json::Null)
+    }
+
+    // A size, and the constant it was written as
+    fn size(&self, key: &'static str, size: Option<&Value>) -> Vec<(&'static str, Json)> {
+        let constant = match size {
+            Some(&Value::Named(ref id)) => Json::string(&id.name),
+            _ => Json::Null,
+        };
+        vec![(key, size.map_or(Json::Null, |size| self.value(size))), ("constant", constant)]
+    }
+
+    fn named(&self, id: &Ident, file: Json, comments: &[String], body: Json) -> Json {
+        let mut fields = vec![
+            ("name", Json::string(&id.name)),
+            ("file", file),
+            ("doc", doc(comments)),
+        ];
+        if let Json::Object(body) = body {
+            fields.extend(body);
+        }
+        Json::Object(fields)
+    }
+
+    fn type_spec(&self, ty: &TypeSpec) -> Json {
+        match *ty {
+            TypeSpec::Primitive(ty, _) => Json::Object(vec![
+                ("kind", Json::string("primitive")),
+                ("name", Json::string(ty.name())),
+            ]),
+            TypeSpec::Named(ref id) => {
+                let definition = match self.schema.types.get(&id.name) {
+                    Some(&TypeKind::Enum) => "enum",
+                    Some(&TypeKind::Struct) => "struct",
+                    Some(&TypeKind::Union) => "union",
+                    Some(&TypeKind::Typedef) | None => "typedef",
+                };
+                Json::Object(vec![
+                    ("kind", Json::string("ref")),
+                    ("name", Json::string(&id.name)),
+                    ("definition", Json::string(definition)),
+                ])
+            },
+            TypeSpec::Enum(ref body) => self.enum_body(body),
+            TypeSpec::Struct(ref body) => self.struct_body(body),
+            TypeSpec::Union(ref body) => self.union_body(body),
+        }
+    }
+
+    fn declaration_type(&self, decl: &Declaration) -> Json {
+        let mut fields = vec![];
+        match decl.kind {
+            DeclarationKind::Void => fields.push(("kind", Json::string("void"))),
+            DeclarationKind::Scalar{ref ty, ..} => return self.type_spec(ty),
+            DeclarationKind::Array{ref ty, ref size, ..} => {
+                fields.push(("kind", Json::string("array")));
+                fields.push(("element", self.type_spec(ty)));
+                fields.extend(self.size("length", Some(size)));
+            },
+            DeclarationKind::VarArray{ref ty, ref size, ..} => {
+                fields.push(("kind", Json::string("vararray")));
+                fields.push(("element", self.type_spec(ty)));
+                fields.extend(self.size("max", size.as_ref()));
+            },
+            DeclarationKind::Opaque{ref size, ..} => {
+                fields.push(("kind", Json::string("opaque")));
+                fields.extend(self.size("length", Some(size)));
+            },
+            DeclarationKind::VarOpaque{ref size, ..} => {
+                fields.push(("kind", Json::string("varopaque")));
+                fields.extend(self.size("max", size.as_ref()));
+            },
+            DeclarationKind::String{ref size, ..} => {
+                fields.push(("kind", Json::string("string")));
+                fields.extend(self.size("max", size.as_ref()));
+            },
+            DeclarationKind::Pointer{ref ty, ..} => {
+                fields.push(("kind", Json::string("optional")));
+                fields.push(("element", self.type_spec(ty)));
+            },
+        }
+        Json::Object(fields)
+    }
+
+    fn declaration(&self, decl: &Declaration) -> Json {
+        Json::Object(vec![
+            ("name", decl.id().map_or(Json::Null, |id| Json::string(&id.name))),
+            ("type", self.declaration_type(decl)),
+            ("doc", doc(&decl.comments)),
+        ])
+    }
+
+    fn enum_body(&self, body: &EnumBody) -> Json {
+        let values = body.variants.iter().map(|variant| Json::Object(vec![
+            ("name", Json::string(&variant.id.name)),
+            ("value", self.schema.value(&variant.id.name).map_or(Json::Null, Json::Number)),
+            ("doc", doc(&variant.comments)),
+        ])).collect();
+        Json::Object(vec![("kind", Json::string("enum")), ("values", Json::Array(values))])
+    }
+
+    fn struct_body(&self, body: &StructBody) -> Json {
+        Json::Object(vec![
+            ("kind", Json::string("struct")),
+            ("fields", Json::Array(body.fields.iter().map(|field| self.declaration(field)).collect())),
+        ])
+    }
+
+    fn union_body(&self, body: &UnionBody) -> Json {
+        let arms = body.cases.iter().map(|case| {
+            let cases = case.values.iter().map(|value| Json::Object(vec![
+                ("name", match *value {
+                    Value::Named(ref id) => Json::string(&id.name),
+                    _ => Json::Null,
+                }),
+                ("value", self.value(value)),
+            ])).collect();
+            Json::Object(vec![
+                ("cases", Json::Array(cases)),
+                ("doc", doc(&case.comments)),
+                ("declaration", self.declaration(&case.decl)),
+            ])
+        }).collect();
+        Json::Object(vec![
+            ("kind", Json::string("union")),
+            ("discriminant", self.declaration(&body.discriminant)),
+            ("arms", Json::Array(arms)),
+            ("default", body.default.as_ref().map_or(Json::Null, |decl| self.declaration(decl))),
+        ])
+    }
+
+    fn program(&self, prog: &Program, namespace: Option<&Ident>, file: Json) -> Json {
+        let versions = prog.versions.iter().map(|version| {
+            let procedures = version.procedures.iter().map(|procedure| Json::Object(vec![
+                ("name", Json::string(&procedure.id.name)),
+                ("number", Json::Number(procedure.number)),
+                ("doc", doc(&procedure.comments)),
+                ("arguments", Json::Array(procedure.arg_types.iter().map(|ty| self.type_spec(ty)).collect())),
+                ("result", procedure.return_type.as_ref().map_or_else(
+                    || Json::Object(vec![("kind", Json::string("void"))]),
+                    |ty| self.type_spec(ty))),
+            ])).collect();
+            Json::Object(vec![
+                ("name", Json::string(&version.id.name)),
+                ("number", Json::Number(version.number)),
+                ("doc", doc(&version.comments)),
+                ("procedures", Json::Array(procedures)),
+            ])
+        }).collect();
+        Json::Object(vec![
+            ("name", Json::string(&prog.id.name)),
+            ("number", Json::Number(prog.number)),
+            ("namespace", namespace.map_or(Json::Null, |id| Json::string(&id.name))),
+            ("file", file),
+            ("doc", doc(&prog.comments)),
+            ("versions", Json::Array(versions)),
+        ])
+    }
+}
+
+fn doc(comments: &[String]) -> Json {
+    let lines = doc_lines(comments);
+    if lines.is_empty() {
+        Json::Null
+    } else {
+        Json::String(lines.join("\n"))
+    }
+}
+
+#[derive(Clone)]
+enum Json {
+    Null,
+    Number(i128),
+    String(String),
+    Array(Vec<Json>),
+    // Keys in the order they're written
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn string(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+
+    // Two spaces per level. Arrays of scalars and empty containers stay on
+    // one line.
+    fn write(&self, out: &mut String, level: usize) {
+        let indent = "  ".repeat(level + 1);
+        match *self {
+            Json::Null => out.push_str("null"),
+            Json::Number(n) => { let _ = write!(out, "{}", n); },
+            Json::String(ref s) => write_string(out, s),
+            Json::Array(ref items) if items.is_empty() => out.push_str("[]"),
+            Json::Array(ref items) if items.iter().all(Json::is_scalar) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    item.write(out, level);
+                }
+                out.push(']');
+            },
+            Json::Array(ref items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(&indent);
+                    item.write(out, level + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(level));
+                out.push(']');
+            },
+            Json::Object(ref fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Object(ref fields) => {
+                out.push_str("{\n");
+                for (i, &(key, ref value)) in fields.iter().enumerate() {
+                    out.push_str(&indent);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, level + 1);
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(level));
+                out.push('}');
+            },
+        }
+    }
+
+    fn is_scalar(&self) -> bool {
+        match *self {
+            Json::Array(_) | Json::Object(_) => false,
+            _ => true,
+        }
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
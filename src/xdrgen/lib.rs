@@ -0,0 +1,26 @@
+//! The XDR/RPC code generator behind `xdrgen`. `Builder` generates Rust
+//! from .x files in a build script. The front end is public too: a
+//! preprocessor, a parser, the typed syntax tree it produces and name
+//! resolution over it, for tools that want to read .x files themselves.
+//! `printer` writes the tree back out as .x source, and `compat` compares
+//! two versions of a schema for changes that break the wire format. `ir`
+//! exports the resolved schema as JSON and `doc` writes reference docs.
+pub mod ast;
+pub mod builder;
+pub mod code_writer;
+pub mod codegen;
+pub mod compat;
+pub mod doc;
+mod function_writer;
+pub mod ir;
+pub mod parser;
+pub mod preprocessor;
+pub mod printer;
+pub mod resolve;
+
+pub use ast::Specification;
+pub use builder::{BuildError, Builder, Generated};
+pub use parser::{Diagnostic, ParseError, parse, parse_preprocessed};
+pub use preprocessor::{Preprocessed, Preprocessor};
+pub use printer::format;
+pub use resolve::{Schema, resolve};
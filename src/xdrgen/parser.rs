@@ -0,0 +1,1090 @@
+use std::error;
+use std::fmt;
+
+use ast::*;
+use preprocessor::Preprocessed;
+
+/// A single problem found while parsing an XDR file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// The source line the problem was found on
+    pub snippet: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keep any tabs so the caret lines up with the snippet
+        let marker: String = self.snippet.chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(self.line.to_string().len());
+        try!(writeln!(f, "error: {}", self.message));
+        try!(writeln!(f, "{}--> {}:{}:{}", gutter, self.file, self.line, self.column));
+        try!(writeln!(f, "{} |", gutter));
+        try!(writeln!(f, "{} | {}", self.line, self.snippet));
+        write!(f, "{} | {}^", gutter, marker)
+    }
+}
+
+/// Every problem found while parsing, in source order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f, "\n\n"));
+            }
+            try!(write!(f, "{}", diagnostic));
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    Ident(String),
+    Number(String),
+    Punct(char),
+    Comment(String),
+    Snippet(String),
+    Directive(String),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+struct Lexed {
+    lexeme: Lexeme,
+    offset: usize,
+    end: usize,
+}
+
+// Based on XDR spec RFC4506
+
+const KEYWORDS: &'static [&'static str] = &[
+    "bool", "case", "const", "default", "double", "quadruple", "enum", "float",
+    "hyper", "int", "opaque", "string", "struct", "switch", "typedef", "union",
+    "unsigned", "void", "program", "version",
+];
+
+// Definitions start with one of these, they are where parsing resumes after an error
+const DEFINITION_KEYWORDS: &'static [&'static str] = &[
+    "typedef", "enum", "union", "struct", "const", "namespace", "program",
+];
+
+#[inline]
+pub fn is_ident(chr:u8) -> bool {
+    (chr >= 0x41 && chr <= 0x5A) ||
+    (chr >= 0x61 && chr <= 0x7A) ||
+    (chr >= 0x30 && chr <= 0x39) ||
+    chr == 0x5f
+}
+
+fn is_keyword(word: &str) -> bool {
+    KEYWORDS.contains(&word)
+}
+
+/// Split the source into tokens, returning the offsets and messages of
+/// anything that couldn't be tokenized alongside them
+fn lex(source: &str) -> (Vec<Lexed>, Vec<(usize, String)>) {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    let mut line_start = true;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'\n' => {
+                line_start = true;
+                i += 1;
+                continue;
+            },
+            b' ' | b'\t' | b'\r' | 0x0c => {
+                i += 1;
+                continue;
+            },
+            _ => {},
+        }
+
+        let start = i;
+        let next = bytes.get(i + 1).cloned();
+        let lexeme = if c == b'/' && next == Some(b'/') {
+            i = source[start..].find('\n').map_or(bytes.len(), |end| start + end);
+            Some(Lexeme::Comment(source[start + 2..i].to_string()))
+        } else if c == b'/' && next == Some(b'*') {
+            match source[start + 2..].find("*/") {
+                Some(end) => {
+                    i = start + 2 + end + 2;
+                    Some(Lexeme::Comment(source[start + 2..i - 2].to_string()))
+                },
+                None => {
+                    errors.push((start, "unterminated block comment".to_string()));
+                    i = bytes.len();
+                    None
+                },
+            }
+        } else if c == b'%' && line_start {
+            i = source[start..].find('\n').map_or(bytes.len(), |end| start + end);
+            Some(Lexeme::Snippet(source[start + 1..i].to_string()))
+        } else if c == b'#' && line_start {
+            // Only left in source that wasn't preprocessed, backslashes
+            // continue them onto the next line
+            i = start;
+            loop {
+                i = source[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                if i == bytes.len() || !source[start..i].trim_end().ends_with('\\') {
+                    break;
+                }
+                i += 1;
+            }
+            Some(Lexeme::Directive(source[start + 1..i].trim_end().to_string()))
+        } else if is_ident(c) {
+            while i < bytes.len() && is_ident(bytes[i]) {
+                i += 1;
+            }
+            let word = source[start..i].to_string();
+            if (c as char).is_digit(10) {
+                Some(Lexeme::Number(word))
+            } else {
+                Some(Lexeme::Ident(word))
+            }
+        } else if b"{}()[]<>;,:=*+-/%&|^~".contains(&c) {
+            i += 1;
+            Some(Lexeme::Punct(c as char))
+        } else {
+            let chr = source[start..].chars().next().unwrap();
+            errors.push((start, format!("unexpected character `{}`", chr)));
+            i += chr.len_utf8();
+            None
+        };
+
+        if let Some(lexeme) = lexeme {
+            tokens.push(Lexed { lexeme: lexeme, offset: start, end: i });
+        }
+        line_start = false;
+    }
+
+    tokens.push(Lexed { lexeme: Lexeme::Eof, offset: bytes.len(), end: bytes.len() });
+    (tokens, errors)
+}
+
+type Parsed<T> = Result<T, Diagnostic>;
+
+struct Parser<'a> {
+    unit: &'a Preprocessed,
+    tokens: Vec<Lexed>,
+    pos: usize,
+    // Where the last token consumed ends, closes the span of each node
+    last_end: usize,
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> Parser<'a> {
+    fn diagnostic(&self, offset: usize, message: String) -> Diagnostic {
+        self.unit.diagnostic(Span::new(offset, offset), message)
+    }
+
+    fn skip_comments(&mut self) {
+        while let Lexeme::Comment(_) = self.tokens[self.pos].lexeme {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Lexeme {
+        self.skip_comments();
+        self.tokens[self.pos].lexeme.clone()
+    }
+
+    /// Where the next token starts, the beginning of a node's span
+    fn start(&mut self) -> usize {
+        self.skip_comments();
+        self.tokens[self.pos].offset
+    }
+
+    /// Whether nothing but whitespace comes before the token on its line
+    fn starts_line(&self, index: usize) -> bool {
+        let source = &self.unit.source;
+        let offset = self.tokens[index].offset;
+        let line_start = source[..offset].rfind('\n').map_or(0, |n| n + 1);
+        source[line_start..offset].trim().is_empty()
+    }
+
+    /// The comments documenting the node starting at token `index`: the run
+    /// of whole-line comments right above it, with no blank line in between
+    fn leading_comments(&self, index: usize) -> Vec<String> {
+        let mut comments = Vec::new();
+        let mut next = index;
+        while next > 0 {
+            let token = &self.tokens[next - 1];
+            let text = match token.lexeme {
+                Lexeme::Comment(ref text) => text,
+                _ => break,
+            };
+            let between = &self.unit.source[token.end..self.tokens[next].offset];
+            if between.matches('\n').count() > 1 || !self.starts_line(next - 1) {
+                break;
+            }
+            comments.push(text.clone());
+            next -= 1;
+        }
+        comments.reverse();
+        comments
+    }
+
+    /// Leading comments of the node about to be parsed
+    fn comments_before(&mut self) -> Vec<String> {
+        self.skip_comments();
+        self.leading_comments(self.pos)
+    }
+
+    /// Consume a comment following the node just parsed on the same line
+    fn trailing_comment(&mut self, comments: &mut Vec<String>) {
+        // Looking for an operator after a value may have skipped it already
+        let mut index = self.pos;
+        while index > 0 && self.tokens[index - 1].offset >= self.last_end {
+            index -= 1;
+        }
+        let comment = match self.tokens[index].lexeme {
+            Lexeme::Comment(ref text) => {
+                let between = &self.unit.source[self.last_end..self.tokens[index].offset];
+                if between.contains('\n') {
+                    return;
+                }
+                text.clone()
+            },
+            _ => return,
+        };
+        comments.push(comment);
+        self.last_end = self.tokens[index].end;
+        if index == self.pos {
+            self.pos += 1;
+        }
+    }
+
+    /// The comments between the node parsed last and the `}` closing the
+    /// body it's in
+    fn end_comments(&mut self) -> Vec<String> {
+        self.skip_comments();
+        let mut index = self.pos;
+        while index > 0 && self.tokens[index - 1].offset >= self.last_end {
+            index -= 1;
+        }
+        self.tokens[index..self.pos].iter()
+            .filter_map(|token| match token.lexeme {
+                Lexeme::Comment(ref text) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.last_end.max(start))
+    }
+
+    fn bump(&mut self) {
+        self.skip_comments();
+        if self.tokens[self.pos].lexeme != Lexeme::Eof {
+            self.last_end = self.tokens[self.pos].end;
+            self.pos += 1;
+        }
+    }
+
+    fn is_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Lexeme::Ident(ref word) => word == keyword,
+            _ => false,
+        }
+    }
+
+    fn is_punct(&mut self, punct: char) -> bool {
+        self.peek() == Lexeme::Punct(punct)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn eat_punct(&mut self, punct: char) -> bool {
+        let found = self.is_punct(punct);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    /// An error at the current token saying what should have been there
+    fn expected(&mut self, what: &str) -> Diagnostic {
+        let offset = self.tokens[self.pos].offset;
+        // Top-level ones are definitions of their own, `fmt` could not keep
+        // these in place
+        if let Lexeme::Directive(_) = self.peek() {
+            return self.diagnostic(offset, "directives inside definitions are not supported".to_string());
+        }
+        let found = match self.peek() {
+            Lexeme::Ident(ref word) if is_keyword(word) => format!("keyword `{}`", word),
+            Lexeme::Ident(word) | Lexeme::Number(word) => format!("`{}`", word),
+            Lexeme::Punct(punct) => format!("`{}`", punct),
+            Lexeme::Snippet(_) => "`%` line".to_string(),
+            Lexeme::Comment(_) | Lexeme::Directive(_) => unreachable!(),
+            Lexeme::Eof => "end of file".to_string(),
+        };
+        self.diagnostic(offset, format!("expected {}, found {}", what, found))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Parsed<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.expected(&format!("`{}`", keyword)))
+        }
+    }
+
+    fn expect_punct(&mut self, punct: char) -> Parsed<()> {
+        if self.eat_punct(punct) {
+            Ok(())
+        } else {
+            Err(self.expected(&format!("`{}`", punct)))
+        }
+    }
+
+    fn specification(&mut self) -> Specification {
+        let mut definitions = Vec::new();
+        loop {
+            let lexed = self.tokens[self.pos].clone();
+            let span = Span::new(lexed.offset, lexed.end);
+            match lexed.lexeme {
+                Lexeme::Eof => break,
+                Lexeme::Comment(comment) => {
+                    definitions.push(Definition {
+                        kind: DefinitionKind::Comment(comment),
+                        span: span,
+                        comments: Vec::new(),
+                    });
+                    self.pos += 1;
+                },
+                Lexeme::Snippet(snippet) => {
+                    definitions.push(Definition {
+                        kind: DefinitionKind::Snippet(snippet),
+                        span: span,
+                        comments: Vec::new(),
+                    });
+                    self.pos += 1;
+                },
+                Lexeme::Directive(directive) => {
+                    definitions.push(Definition {
+                        kind: DefinitionKind::Directive(directive),
+                        span: span,
+                        comments: Vec::new(),
+                    });
+                    self.pos += 1;
+                },
+                _ => {
+                    let start = self.pos;
+                    // The comments above it were taken as free-standing ones
+                    // on the way here, they belong to the definition instead.
+                    // Recovering from an error may have skipped some of them.
+                    let attached = self.leading_comments(start).len();
+                    let first = self.tokens[start - attached].offset;
+                    while let Some(&Definition { kind: DefinitionKind::Comment(_), span, .. }) = definitions.last() {
+                        if span.start < first {
+                            break;
+                        }
+                        definitions.pop();
+                    }
+                    match self.definition() {
+                        Ok(def) => definitions.push(def),
+                        Err(diagnostic) => {
+                            self.errors.push(diagnostic);
+                            self.recover(start);
+                        },
+                    }
+                },
+            }
+        }
+        Specification { definitions: definitions }
+    }
+
+    /// Skip the rest of a definition that failed to parse, up to the `;` that
+    /// closes it or the next line that starts a new definition
+    fn recover(&mut self, start: usize) {
+        let mut depth = 0;
+        let mut i = start;
+        loop {
+            let token = &self.tokens[i];
+            match token.lexeme {
+                Lexeme::Eof => break,
+                Lexeme::Punct('{') => depth += 1,
+                Lexeme::Punct('}') => depth -= 1,
+                Lexeme::Punct(';') if depth <= 0 => {
+                    i += 1;
+                    break;
+                },
+                Lexeme::Ident(ref word) if i > start && DEFINITION_KEYWORDS.contains(&&word[..]) => {
+                    let source = &self.unit.source;
+                    let line_start = source[..token.offset].rfind('\n').map_or(0, |n| n + 1);
+                    if source[line_start..token.offset].trim().is_empty() {
+                        break;
+                    }
+                },
+                _ => {},
+            }
+            i += 1;
+        }
+        self.pos = i;
+    }
+
+    fn definition(&mut self) -> Parsed<Definition> {
+        let keyword = match self.peek() {
+            Lexeme::Ident(word) => word,
+            _ => String::new(),
+        };
+        let start = self.start();
+        if keyword == "program" {
+            // Its comments are kept on the `Program`
+            let program = try!(self.program());
+            let span = self.span_from(start);
+            return Ok(Definition {
+                kind: DefinitionKind::Program(program),
+                span: span,
+                comments: Vec::new(),
+            });
+        }
+        let mut comments = self.comments_before();
+        let kind = match &keyword[..] {
+            "typedef" | "enum" | "union" | "struct" => try!(self.type_def()),
+            "const" => try!(self.constant_def()),
+            "namespace" => try!(self.namespace()),
+            _ => return Err(self.expected("a definition")),
+        };
+        let span = self.span_from(start);
+        self.trailing_comment(&mut comments);
+        Ok(Definition { kind: kind, span: span, comments: comments })
+    }
+
+    fn type_def(&mut self) -> Parsed<DefinitionKind> {
+        let def = if self.eat_keyword("typedef") {
+            DefinitionKind::Typedef(try!(self.declaration()))
+        } else if self.eat_keyword("enum") {
+            let id = try!(self.identifier());
+            let body = try!(self.enum_body());
+            DefinitionKind::Enum { id: id, body: body }
+        } else if self.eat_keyword("union") {
+            let id = try!(self.identifier());
+            let body = try!(self.union_body());
+            DefinitionKind::Union { id: id, body: body }
+        } else {
+            try!(self.expect_keyword("struct"));
+            let id = try!(self.identifier());
+            let body = try!(self.struct_body());
+            DefinitionKind::Struct { id: id, body: body }
+        };
+        try!(self.expect_punct(';'));
+        Ok(def)
+    }
+
+    fn constant_def(&mut self) -> Parsed<DefinitionKind> {
+        try!(self.expect_keyword("const"));
+        let id = try!(self.identifier());
+        try!(self.expect_punct('='));
+        let value = try!(self.value());
+        try!(self.expect_punct(';'));
+        Ok(DefinitionKind::Const { id: id, value: value })
+    }
+
+    /// A type, or `None` for `void` where procedures allow it
+    fn type_or_void(&mut self) -> Parsed<Option<TypeSpec>> {
+        if self.eat_keyword("void") {
+            Ok(None)
+        } else {
+            self.type_specifier().map(Some)
+        }
+    }
+
+    fn type_specifier(&mut self) -> Parsed<TypeSpec> {
+        let word = match self.peek() {
+            Lexeme::Ident(word) => word,
+            _ => return Err(self.expected("a type")),
+        };
+        let start = self.start();
+        let ty = match &word[..] {
+            "unsigned" => {
+                self.bump();
+                let ty = if self.eat_keyword("hyper") {
+                    Type::Uhyper
+                } else {
+                    self.eat_keyword("int");
+                    Type::Uint
+                };
+                return Ok(TypeSpec::Primitive(ty, self.span_from(start)));
+            },
+            "int" => Type::Int,
+            "hyper" => Type::Hyper,
+            "float" => Type::Float,
+            "double" => Type::Double,
+            "quadruple" => Type::Quadruple,
+            "bool" => Type::Bool,
+            // These aren't standard XDR, but what I'm attempting to interop
+            // with uses them like they are... so oh well
+            "uint32_t" | "u_int32_t" => Type::Uint,
+            "int32_t" => Type::Int,
+            "u_int64_t" => Type::Uhyper,
+            // There are standard XDR again! `struct foo` and friends may also
+            // just name a type, as rpcgen allows
+            "enum" => {
+                self.bump();
+                if self.is_punct('{') {
+                    return Ok(TypeSpec::Enum(try!(self.enum_body())));
+                }
+                return self.identifier().map(TypeSpec::Named);
+            },
+            "struct" => {
+                self.bump();
+                if self.is_punct('{') {
+                    return Ok(TypeSpec::Struct(try!(self.struct_body())));
+                }
+                return self.identifier().map(TypeSpec::Named);
+            },
+            "union" => {
+                self.bump();
+                if self.is_keyword("switch") {
+                    return Ok(TypeSpec::Union(Box::new(try!(self.union_body()))));
+                }
+                return self.identifier().map(TypeSpec::Named);
+            },
+            _ if is_keyword(&word) => return Err(self.expected("a type")),
+            _ => return self.identifier().map(TypeSpec::Named),
+        };
+        self.bump();
+        Ok(TypeSpec::Primitive(ty, self.span_from(start)))
+    }
+
+    fn union_body(&mut self) -> Parsed<UnionBody> {
+        let start = self.start();
+        try!(self.expect_keyword("switch"));
+        try!(self.expect_punct('('));
+        let discriminant = try!(self.declaration());
+        try!(self.expect_punct(')'));
+        try!(self.expect_punct('{'));
+        let mut cases = Vec::new();
+        while self.is_keyword("case") {
+            cases.push(try!(self.union_case()));
+        }
+        if cases.is_empty() {
+            return Err(self.expected("`case`"));
+        }
+        let mut comments = self.comments_before();
+        let default = if self.eat_keyword("default") {
+            try!(self.expect_punct(':'));
+            let mut decl = try!(self.declaration());
+            try!(self.expect_punct(';'));
+            self.trailing_comment(&mut comments);
+            decl.comments = comments;
+            Some(decl)
+        } else {
+            None
+        };
+        let end_comments = self.end_comments();
+        if !self.eat_punct('}') {
+            return Err(self.expected("`case`, `default` or `}`"));
+        }
+        Ok(UnionBody {
+            discriminant: discriminant,
+            cases: cases,
+            default: default,
+            span: self.span_from(start),
+            end_comments: end_comments,
+        })
+    }
+
+    fn union_case(&mut self) -> Parsed<UnionCase> {
+        let mut comments = self.comments_before();
+        let start = self.start();
+        let mut values = Vec::new();
+        while self.eat_keyword("case") {
+            values.push(try!(self.value()));
+            try!(self.expect_punct(':'));
+        }
+        let decl = try!(self.declaration());
+        try!(self.expect_punct(';'));
+        let span = self.span_from(start);
+        self.trailing_comment(&mut comments);
+        Ok(UnionCase {
+            values: values,
+            decl: decl,
+            span: span,
+            comments: comments,
+        })
+    }
+
+    fn enum_body(&mut self) -> Parsed<EnumBody> {
+        let start = self.start();
+        try!(self.expect_punct('{'));
+        let mut variants = Vec::new();
+        while !self.is_punct('}') {
+            let mut comments = self.comments_before();
+            let variant_start = self.start();
+            let id = try!(self.identifier());
+            let value = if self.eat_punct('=') {
+                Some(try!(self.value()))
+            } else {
+                None
+            };
+            let span = self.span_from(variant_start);
+            // The last variant's comment comes before the `}`, not after a `,`
+            self.trailing_comment(&mut comments);
+            if !self.eat_punct(',') && !self.is_punct('}') {
+                return Err(self.expected("`,` or `}`"));
+            }
+            self.trailing_comment(&mut comments);
+            variants.push(EnumVariant {
+                id: id,
+                value: value,
+                span: span,
+                comments: comments,
+            });
+        }
+        let end_comments = self.end_comments();
+        self.bump();
+        Ok(EnumBody { variants: variants, span: self.span_from(start), end_comments: end_comments })
+    }
+
+    fn struct_body(&mut self) -> Parsed<StructBody> {
+        let start = self.start();
+        try!(self.expect_punct('{'));
+        let mut fields = Vec::new();
+        while !self.is_punct('}') {
+            let mut comments = self.comments_before();
+            let mut field = try!(self.declaration());
+            try!(self.expect_punct(';'));
+            self.trailing_comment(&mut comments);
+            field.comments = comments;
+            fields.push(field);
+        }
+        let end_comments = self.end_comments();
+        self.bump();
+        Ok(StructBody { fields: fields, span: self.span_from(start), end_comments: end_comments })
+    }
+
+    fn namespace(&mut self) -> Parsed<DefinitionKind> {
+        try!(self.expect_keyword("namespace"));
+        let id = try!(self.identifier());
+        try!(self.expect_punct('{'));
+        let mut programs = Vec::new();
+        while self.is_keyword("program") {
+            programs.push(try!(self.program()));
+        }
+        if !self.eat_punct('}') {
+            return Err(self.expected("`program` or `}`"));
+        }
+        try!(self.expect_punct(';'));
+        Ok(DefinitionKind::Namespace { id: id, programs: programs })
+    }
+
+    fn program(&mut self) -> Parsed<Program> {
+        let mut comments = self.comments_before();
+        let start = self.start();
+        try!(self.expect_keyword("program"));
+        let id = try!(self.identifier());
+        try!(self.expect_punct('{'));
+        let mut versions = Vec::new();
+        while self.is_keyword("version") {
+            versions.push(try!(self.version()));
+        }
+        let end_comments = self.end_comments();
+        if !self.eat_punct('}') {
+            return Err(self.expected("`version` or `}`"));
+        }
+        let number = try!(self.numeric_id());
+        let span = self.span_from(start);
+        self.trailing_comment(&mut comments);
+        Ok(Program {
+            id: id,
+            versions: versions,
+            number: number,
+            span: span,
+            comments: comments,
+            end_comments: end_comments,
+        })
+    }
+
+    fn version(&mut self) -> Parsed<Version> {
+        let mut comments = self.comments_before();
+        let start = self.start();
+        try!(self.expect_keyword("version"));
+        let id = try!(self.identifier());
+        try!(self.expect_punct('{'));
+        let mut procedures = Vec::new();
+        while !self.is_punct('}') {
+            procedures.push(try!(self.procedure()));
+        }
+        let end_comments = self.end_comments();
+        self.bump();
+        let number = try!(self.numeric_id());
+        let span = self.span_from(start);
+        self.trailing_comment(&mut comments);
+        Ok(Version {
+            id: id,
+            procedures: procedures,
+            number: number,
+            span: span,
+            comments: comments,
+            end_comments: end_comments,
+        })
+    }
+
+    fn procedure(&mut self) -> Parsed<Procedure> {
+        let mut comments = self.comments_before();
+        let start = self.start();
+        let return_type = try!(self.type_or_void());
+        let id = try!(self.identifier());
+        try!(self.expect_punct('('));
+        // Several arguments are rpcgen's newstyle (`-N`), `void` stands alone
+        let mut arg_types = Vec::new();
+        let mut void = None;
+        loop {
+            let offset = self.start();
+            match try!(self.type_or_void()) {
+                Some(ty) => arg_types.push(ty),
+                None => void = Some(offset),
+            }
+            if !self.eat_punct(',') {
+                break;
+            }
+        }
+        if let Some(offset) = void {
+            if !arg_types.is_empty() {
+                return Err(self.diagnostic(offset, "`void` must be the only argument".to_string()));
+            }
+        }
+        try!(self.expect_punct(')'));
+        let number = try!(self.numeric_id());
+        let span = self.span_from(start);
+        self.trailing_comment(&mut comments);
+        Ok(Procedure {
+            id: id,
+            return_type: return_type,
+            arg_types: arg_types,
+            number: number,
+            span: span,
+            comments: comments,
+        })
+    }
+
+    fn numeric_id(&mut self) -> Parsed<i128> {
+        try!(self.expect_punct('='));
+        let id = match try!(self.constant()) {
+            Value::Constant(val, _) => val,
+            _ => unreachable!(),
+        };
+        try!(self.expect_punct(';'));
+        Ok(id)
+    }
+
+    fn declaration(&mut self) -> Parsed<Declaration> {
+        let start = self.start();
+        let kind = try!(self.declaration_kind());
+        Ok(Declaration { kind: kind, span: self.span_from(start), comments: Vec::new() })
+    }
+
+    fn declaration_kind(&mut self) -> Parsed<DeclarationKind> {
+        if self.eat_keyword("opaque") {
+            let id = try!(self.identifier());
+            if self.eat_punct('[') {
+                let size = try!(self.value());
+                try!(self.expect_punct(']'));
+                return Ok(DeclarationKind::Opaque { id: id, size: size });
+            }
+            if !self.is_punct('<') {
+                return Err(self.expected("`[` or `<`"));
+            }
+            return Ok(DeclarationKind::VarOpaque { id: id, size: try!(self.bound()) });
+        }
+        if self.eat_keyword("string") {
+            let id = try!(self.identifier());
+            return Ok(DeclarationKind::String { id: id, size: try!(self.bound()) });
+        }
+        if self.eat_keyword("void") {
+            return Ok(DeclarationKind::Void);
+        }
+
+        let ty = try!(self.type_specifier());
+        if self.eat_punct('*') {
+            let id = try!(self.identifier());
+            return Ok(DeclarationKind::Pointer { ty: ty, id: id });
+        }
+        let id = try!(self.identifier());
+        if self.eat_punct('[') {
+            let size = try!(self.value());
+            try!(self.expect_punct(']'));
+            Ok(DeclarationKind::Array { ty: ty, id: id, size: size })
+        } else if self.is_punct('<') {
+            Ok(DeclarationKind::VarArray { ty: ty, id: id, size: try!(self.bound()) })
+        } else {
+            Ok(DeclarationKind::Scalar { ty: ty, id: id })
+        }
+    }
+
+    /// The optional maximum size of a variable length declaration, `<>` or `<N>`
+    fn bound(&mut self) -> Parsed<Option<Value>> {
+        try!(self.expect_punct('<'));
+        if self.eat_punct('>') {
+            return Ok(None);
+        }
+        let size = try!(self.value());
+        try!(self.expect_punct('>'));
+        Ok(Some(size))
+    }
+
+    fn value(&mut self) -> Parsed<Value> {
+        self.expression(0)
+    }
+
+    /// The binary operator at the current token, with its C precedence and
+    /// how many tokens it's spelled with
+    fn binary_op(&mut self) -> Option<(BinaryOp, usize, usize)> {
+        let punct = match self.peek() {
+            Lexeme::Punct(punct) => punct,
+            _ => return None,
+        };
+        // Shifts are two adjacent `<` or `>`, which otherwise close bounds
+        let doubled = {
+            let next = &self.tokens[self.pos + 1];
+            next.lexeme == Lexeme::Punct(punct) && next.offset == self.tokens[self.pos].offset + 1
+        };
+        match punct {
+            '|' => Some((BinaryOp::Or, 1, 1)),
+            '^' => Some((BinaryOp::Xor, 2, 1)),
+            '&' => Some((BinaryOp::And, 3, 1)),
+            '<' if doubled => Some((BinaryOp::Shl, 4, 2)),
+            '>' if doubled => Some((BinaryOp::Shr, 4, 2)),
+            '+' => Some((BinaryOp::Add, 5, 1)),
+            '-' => Some((BinaryOp::Sub, 5, 1)),
+            '*' => Some((BinaryOp::Mul, 6, 1)),
+            '/' => Some((BinaryOp::Div, 6, 1)),
+            '%' => Some((BinaryOp::Rem, 6, 1)),
+            _ => None,
+        }
+    }
+
+    fn expression(&mut self, min_precedence: usize) -> Parsed<Value> {
+        let start = self.start();
+        let mut lhs = try!(self.unary());
+        loop {
+            let (op, precedence, len) = match self.binary_op() {
+                Some(op) if op.1 >= min_precedence => op,
+                _ => break,
+            };
+            let offset = self.tokens[self.pos].offset;
+            for _ in 0..len {
+                self.bump();
+            }
+            let rhs = try!(self.expression(precedence + 1));
+            lhs = try!(self.fold(offset, Value::Binary {
+                op: op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+                span: self.span_from(start),
+            }));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Parsed<Value> {
+        let op = match self.peek() {
+            Lexeme::Punct('-') => UnaryOp::Neg,
+            Lexeme::Punct('~') => UnaryOp::Not,
+            Lexeme::Punct('+') => {
+                self.bump();
+                return self.unary();
+            },
+            Lexeme::Punct('(') => {
+                self.bump();
+                let expr = try!(self.expression(0));
+                try!(self.expect_punct(')'));
+                return Ok(expr);
+            },
+            Lexeme::Number(_) => return self.constant(),
+            Lexeme::Ident(ref word) if !is_keyword(word) => return self.identifier().map(Value::Named),
+            _ => return Err(self.expected("a number or constant name")),
+        };
+        let offset = self.tokens[self.pos].offset;
+        self.bump();
+        let operand = try!(self.unary());
+        self.fold(offset, Value::Unary {
+            op: op,
+            operand: Box::new(operand),
+            span: self.span_from(offset),
+        })
+    }
+
+    /// Evaluate an operator right away when it's only applied to numbers
+    fn fold(&self, offset: usize, expr: Value) -> Parsed<Value> {
+        let (folded, span) = match expr {
+            Value::Unary{op, operand: ref val, span} => match **val {
+                Value::Constant(val, _) => (apply_unary(op, val), span),
+                _ => return Ok(expr.clone()),
+            },
+            Value::Binary{op, ref lhs, ref rhs, span} => match (&**lhs, &**rhs) {
+                (&Value::Constant(lhs, _), &Value::Constant(rhs, _)) => (apply_binary(op, lhs, rhs), span),
+                _ => return Ok(expr.clone()),
+            },
+            _ => return Ok(expr.clone()),
+        };
+        folded.map(|val| Value::Constant(val, span)).map_err(|message| self.diagnostic(offset, message))
+    }
+
+    fn constant(&mut self) -> Parsed<Value> {
+        let text = match self.peek() {
+            Lexeme::Number(text) => text,
+            _ => return Err(self.expected("a number")),
+        };
+        let offset = self.tokens[self.pos].offset;
+        self.bump();
+
+        let (digits, base) = if text.starts_with("0x") || text.starts_with("0X") {
+            (&text[2..], 16)
+        } else if text.len() > 1 && text.starts_with('0') {
+            (&text[1..], 8)
+        } else {
+            (&text[..], 10)
+        };
+        match u64::from_str_radix(digits, base) {
+            Ok(n) => Ok(Value::Constant(n as i128, self.span_from(offset))),
+            Err(_) if !digits.is_empty() && digits.chars().all(|c| c.is_digit(base)) => {
+                Err(self.diagnostic(offset, format!("number `{}` does not fit in 64 bits", text)))
+            },
+            Err(_) => Err(self.diagnostic(offset, format!("invalid number `{}`", text))),
+        }
+    }
+
+    fn identifier(&mut self) -> Parsed<Ident> {
+        match self.peek() {
+            Lexeme::Ident(ref word) if !is_keyword(word) => {
+                let start = self.start();
+                self.bump();
+                Ok(Ident { name: word.clone(), span: self.span_from(start) })
+            },
+            _ => Err(self.expected("an identifier")),
+        }
+    }
+}
+
+// Constants may be anything a `hyper` or an `unsigned hyper` can hold
+const MIN_CONSTANT: i128 = -(1 << 63);
+const MAX_CONSTANT: i128 = (1 << 64) - 1;
+
+fn check_range(val: Option<i128>) -> Result<i128, String> {
+    match val {
+        Some(val) if val >= MIN_CONSTANT && val <= MAX_CONSTANT => Ok(val),
+        _ => Err("constant expression overflows 64 bits".to_string()),
+    }
+}
+
+pub fn apply_unary(op: UnaryOp, val: i128) -> Result<i128, String> {
+    match op {
+        UnaryOp::Neg => check_range(val.checked_neg()),
+        UnaryOp::Not => check_range(Some(!val)),
+    }
+}
+
+pub fn apply_binary(op: BinaryOp, lhs: i128, rhs: i128) -> Result<i128, String> {
+    let val = match op {
+        BinaryOp::Add => lhs.checked_add(rhs),
+        BinaryOp::Sub => lhs.checked_sub(rhs),
+        BinaryOp::Mul => lhs.checked_mul(rhs),
+        BinaryOp::Div | BinaryOp::Rem if rhs == 0 => {
+            return Err("division by zero in constant expression".to_string());
+        },
+        BinaryOp::Div => lhs.checked_div(rhs),
+        BinaryOp::Rem => lhs.checked_rem(rhs),
+        BinaryOp::Shl | BinaryOp::Shr if rhs < 0 || rhs > 63 => {
+            return Err(format!("shift by {} is out of range", rhs));
+        },
+        BinaryOp::Shl => lhs.checked_shl(rhs as u32),
+        BinaryOp::Shr => lhs.checked_shr(rhs as u32),
+        BinaryOp::And => Some(lhs & rhs),
+        BinaryOp::Or => Some(lhs | rhs),
+        BinaryOp::Xor => Some(lhs ^ rhs),
+    };
+    check_range(val)
+}
+
+/// Evaluate a constant expression, `lookup` gives the value of named constants
+pub fn evaluate<F>(expr: &Value, lookup: &F) -> Result<i128, String>
+        where F: Fn(&str) -> Option<i128> {
+    match *expr {
+        Value::Constant(val, _) => Ok(val),
+        Value::Named(ref id) => lookup(&id.name).ok_or_else(|| format!("unknown constant `{}`", id)),
+        Value::Unary{op, ref operand, ..} => apply_unary(op, try!(evaluate(operand, lookup))),
+        Value::Binary{op, ref lhs, ref rhs, ..} => {
+            apply_binary(op, try!(evaluate(lhs, lookup)), try!(evaluate(rhs, lookup)))
+        },
+    }
+}
+
+/// Every comment in `source` with where it is, including those the parser
+/// doesn't attach to anything
+pub fn comments(source: &str) -> Vec<(Span, String)> {
+    lex(source).0.into_iter()
+        .filter_map(|token| match token.lexeme {
+            Lexeme::Comment(text) => Some((Span::new(token.offset, token.end), text)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse the XDR source of `file`, reporting every problem found in it
+pub fn parse(file: &str, source: &str, debug: bool) -> Result<Specification, ParseError> {
+    parse_preprocessed(&Preprocessed::verbatim(file, source), debug)
+}
+
+/// Parse preprocessed XDR, problems are reported against the file and line
+/// each piece of source originally came from
+pub fn parse_preprocessed(unit: &Preprocessed, debug: bool) -> Result<Specification, ParseError> {
+    let (tokens, lex_errors) = lex(&unit.source);
+    let mut parser = Parser {
+        unit: unit,
+        tokens: tokens,
+        pos: 0,
+        last_end: 0,
+        errors: Vec::new(),
+    };
+    let parsed = parser.specification();
+
+    let mut diagnostics: Vec<Diagnostic> = lex_errors.into_iter()
+        .map(|(offset, message)| parser.diagnostic(offset, message))
+        .collect();
+    diagnostics.extend(parser.errors);
+    // An unexpected character usually derails the parser on the same line
+    // too, only the first problem on each line is worth reporting
+    diagnostics.sort_by_key(|d| {
+        let index = unit.lines.iter().position(|o| o.file == d.file && o.line == d.line);
+        (index.unwrap_or(unit.lines.len()), d.column)
+    });
+    diagnostics.dedup_by(|a, b| a.file == b.file && a.line == b.line);
+
+    if debug {
+        println!("{:?}", parsed);
+    }
+    if diagnostics.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(ParseError { diagnostics: diagnostics })
+    }
+}
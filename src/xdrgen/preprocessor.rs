@@ -0,0 +1,590 @@
+// A small subset of the C preprocessor, enough for the .x files rpcgen
+// would have run through cpp: #include, object-like #define/#undef and
+// conditional blocks. `#if` takes integer expressions with `defined`, the
+// arithmetic, bitwise, comparison and logical operators, but not `?:`.
+// Lines starting with `%` are passed through untouched.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use ast::Span;
+use parser::{Diagnostic, ParseError, is_ident};
+
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+// Binary operators allowed in `#if`, grouped by C precedence, loosest first
+const BINARY_OPERATORS: &'static [&'static [&'static str]] = &[
+    &["||"], &["&&"], &["|"], &["^"], &["&"], &["==", "!="], &["<", ">", "<=", ">="],
+    &["<<", ">>"], &["+", "-"], &["*", "/", "%"],
+];
+
+const TWO_CHAR_OPERATORS: &'static [&'static str] = &["&&", "||", "==", "!=", "<=", ">=", "<<", ">>"];
+
+/// The file and line a line of preprocessed source came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineOrigin {
+    pub file: String,
+    pub line: usize,
+}
+
+/// Preprocessed source, ready for the parser
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preprocessed {
+    pub source: String,
+    /// Where each line of `source` came from
+    pub lines: Vec<LineOrigin>,
+    /// Every file `#include`d, in the order they were read
+    pub includes: Vec<String>,
+}
+
+impl Preprocessed {
+    /// Source that skips preprocessing, every line maps back to itself
+    pub fn verbatim(file: &str, source: &str) -> Preprocessed {
+        Preprocessed {
+            source: source.to_string(),
+            lines: (1..source.lines().count() + 1)
+                .map(|line| LineOrigin { file: file.to_string(), line: line })
+                .collect(),
+            includes: Vec::new(),
+        }
+    }
+
+    /// The file and line `offset` came from, `#include`d files included
+    pub fn origin(&self, offset: usize) -> LineOrigin {
+        let offset = offset.min(self.source.len());
+        let index = self.source[..offset].matches('\n').count();
+        match self.lines.get(index) {
+            Some(origin) => origin.clone(),
+            // Past the last line there is only the end of the file
+            None => self.lines.last().map_or(LineOrigin { file: String::new(), line: 1 }, |last| {
+                LineOrigin { file: last.file.clone(), line: last.line + 1 }
+            }),
+        }
+    }
+
+    /// Point at `span` in the file it came from
+    pub fn diagnostic(&self, span: Span, message: String) -> Diagnostic {
+        let offset = span.start.min(self.source.len());
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let snippet = self.source[line_start..].lines().next().unwrap_or("");
+        let origin = self.origin(offset);
+        Diagnostic {
+            file: origin.file,
+            line: origin.line,
+            column: self.source[line_start..offset].chars().count() + 1,
+            snippet: snippet.trim_end_matches('\r').to_string(),
+            message: message,
+        }
+    }
+
+    fn push_line(&mut self, file: &str, line: usize, text: &str) {
+        self.source.push_str(text);
+        self.source.push('\n');
+        self.lines.push(LineOrigin { file: file.to_string(), line: line });
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessor {
+    include_paths: Vec<PathBuf>,
+    defines: HashMap<String, String>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Preprocessor {
+        Preprocessor::default()
+    }
+
+    /// Search `path` for `#include`d files, after the including file's own directory
+    pub fn include_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.include_paths.push(path.as_ref().to_path_buf());
+    }
+
+    /// Predefine an object-like macro, as `-D NAME=VALUE` would
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.to_string(), value.to_string());
+    }
+
+    /// Preprocess the contents of `file`. Macros defined inside don't outlive the call.
+    pub fn preprocess(&self, file: &str, source: &str) -> Result<Preprocessed, ParseError> {
+        let mut run = Run {
+            include_paths: &self.include_paths,
+            defines: self.defines.clone(),
+            depth: 0,
+            out: Preprocessed { source: String::new(), lines: Vec::new(), includes: Vec::new() },
+            diagnostics: Vec::new(),
+        };
+        run.process(file, source);
+        if run.diagnostics.is_empty() {
+            Ok(run.out)
+        } else {
+            Err(ParseError { diagnostics: run.diagnostics })
+        }
+    }
+}
+
+struct Conditional {
+    directive: &'static str,
+    line: usize,
+    // Whether the enclosing block is being kept
+    parent: bool,
+    // Whether this branch is being kept
+    taking: bool,
+    // Whether any branch so far was true
+    taken: bool,
+    seen_else: bool,
+}
+
+struct Run<'a> {
+    include_paths: &'a [PathBuf],
+    defines: HashMap<String, String>,
+    depth: usize,
+    out: Preprocessed,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Run<'a> {
+    fn error(&mut self, file: &str, line: usize, text: &str, message: String) {
+        let column = text.len() - text.trim_start().len() + 1;
+        self.diagnostics.push(Diagnostic {
+            file: file.to_string(),
+            line: line,
+            column: column,
+            snippet: text.to_string(),
+            message: message,
+        });
+    }
+
+    fn process(&mut self, file: &str, source: &str) {
+        let lines: Vec<&str> = source.lines().map(|l| l.trim_end_matches('\r')).collect();
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut in_comment = false;
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = i + 1;
+            let text = lines[i];
+            let active = conditionals.last().map_or(true, |c| c.taking);
+
+            if !in_comment && text.trim_start().starts_with('#') {
+                // Directives may be continued with a trailing backslash
+                let mut directive = text.trim_start()[1..].to_string();
+                self.out.push_line(file, line, "");
+                while directive.ends_with('\\') && i + 1 < lines.len() {
+                    directive.pop();
+                    i += 1;
+                    directive.push_str(lines[i]);
+                    self.out.push_line(file, i + 1, "");
+                }
+                let directive = strip_comments(&directive);
+                self.directive(file, line, text, directive.trim(), active, &mut conditionals);
+            } else if active {
+                let expanded = self.expand_line(text, &mut in_comment);
+                self.out.push_line(file, line, &expanded);
+            } else {
+                skip_comments(text, &mut in_comment);
+                self.out.push_line(file, line, "");
+            }
+            i += 1;
+        }
+
+        for c in conditionals {
+            let text = lines[c.line - 1];
+            self.error(file, c.line, text, format!("unterminated `#{}`", c.directive));
+        }
+    }
+
+    fn directive(&mut self, file: &str, line: usize, text: &str, directive: &str,
+                 active: bool, conditionals: &mut Vec<Conditional>) {
+        let name_len = directive.bytes().take_while(|&c| is_ident(c)).count();
+        let (name, rest) = directive.split_at(name_len);
+        let rest = rest.trim();
+
+        match name {
+            "ifdef" | "ifndef" | "if" => {
+                let directive = match name {
+                    "ifdef" => "ifdef",
+                    "ifndef" => "ifndef",
+                    _ => "if",
+                };
+                // Conditions inside skipped blocks aren't evaluated at all
+                let cond = active && match name {
+                    "ifdef" => self.defines.contains_key(rest),
+                    "ifndef" => !self.defines.contains_key(rest),
+                    _ => self.condition(file, line, text, rest),
+                };
+                conditionals.push(Conditional {
+                    directive: directive,
+                    line: line,
+                    parent: active,
+                    taking: cond,
+                    taken: cond,
+                    seen_else: false,
+                });
+            },
+            "elif" | "else" => {
+                let evaluate = match conditionals.last() {
+                    None => {
+                        self.error(file, line, text, format!("`#{}` without `#if`", name));
+                        return;
+                    },
+                    Some(c) if c.seen_else => {
+                        self.error(file, line, text, format!("`#{}` after `#else`", name));
+                        return;
+                    },
+                    Some(c) => c.parent && !c.taken,
+                };
+                let cond = evaluate && (name == "else" || self.condition(file, line, text, rest));
+                let c = conditionals.last_mut().unwrap();
+                c.taking = cond;
+                c.taken = c.taken || cond;
+                c.seen_else = name == "else";
+            },
+            "endif" => {
+                if conditionals.pop().is_none() {
+                    self.error(file, line, text, "`#endif` without `#if`".to_string());
+                }
+            },
+            _ if !active => {},
+            "define" => {
+                let name_len = rest.bytes().take_while(|&c| is_ident(c)).count();
+                let (macro_name, value) = rest.split_at(name_len);
+                if macro_name.is_empty() {
+                    self.error(file, line, text, "expected a macro name after `#define`".to_string());
+                } else if value.starts_with('(') {
+                    self.error(file, line, text,
+                               format!("function-like macro `{}` is not supported", macro_name));
+                } else {
+                    self.defines.insert(macro_name.to_string(), value.trim().to_string());
+                }
+            },
+            "undef" => {
+                self.defines.remove(rest);
+            },
+            "include" => self.include(file, line, text, rest),
+            "error" => self.error(file, line, text, format!("#error {}", rest)),
+            // Nothing here cares about these
+            "" | "pragma" | "line" | "ident" => {},
+            _ => self.error(file, line, text,
+                            format!("unknown preprocessor directive `#{}`", name)),
+        }
+    }
+
+    fn include(&mut self, file: &str, line: usize, text: &str, target: &str) {
+        let (name, local) = if target.len() > 2 && target.starts_with('"') && target.ends_with('"') {
+            (&target[1..target.len() - 1], true)
+        } else if target.len() > 2 && target.starts_with('<') && target.ends_with('>') {
+            (&target[1..target.len() - 1], false)
+        } else {
+            self.error(file, line, text, "expected \"FILE\" or <FILE> after `#include`".to_string());
+            return;
+        };
+
+        // "file" is looked for next to the including file first, like cpp does
+        let mut candidates = Vec::new();
+        if local {
+            let dir = Path::new(file).parent().unwrap_or(Path::new(""));
+            candidates.push(dir.join(name));
+        }
+        candidates.extend(self.include_paths.iter().map(|dir| dir.join(name)));
+
+        let path = match candidates.into_iter().find(|path| path.is_file()) {
+            Some(path) => path,
+            None => {
+                self.error(file, line, text, format!("cannot find include file `{}`", name));
+                return;
+            },
+        };
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            self.error(file, line, text, format!("`#include` nested too deeply including `{}`", name));
+            return;
+        }
+
+        let mut source = String::new();
+        let read = File::open(&path).and_then(|mut fin| fin.read_to_string(&mut source));
+        if let Err(e) = read {
+            self.error(file, line, text, format!("cannot read include file `{}`: {}", name, e));
+            return;
+        }
+        let path = path.to_string_lossy().into_owned();
+        self.out.includes.push(path.clone());
+        self.depth += 1;
+        self.process(&path, &source);
+        self.depth -= 1;
+    }
+
+    /// Evaluate an `#if`/`#elif` condition
+    fn condition(&mut self, file: &str, line: usize, text: &str, expr: &str) -> bool {
+        let tokens = expr_tokens(expr);
+        let mut pos = 0;
+        let value = self.binary_expr(&tokens, &mut pos, 0).and_then(|value| match tokens.get(pos) {
+            None => Ok(value),
+            Some(token) => Err(format!("unexpected `{}`", token)),
+        });
+        match value {
+            Ok(value) => value != 0,
+            Err(message) => {
+                self.error(file, line, text, format!("{} in `#if {}`", message, expr));
+                false
+            },
+        }
+    }
+
+    // Operators of BINARY_OPERATORS[level] and tighter binding ones
+    fn binary_expr(&self, tokens: &[String], pos: &mut usize, level: usize) -> Result<i64, String> {
+        if level == BINARY_OPERATORS.len() {
+            return self.unary_expr(tokens, pos);
+        }
+        let mut value = self.binary_expr(tokens, pos, level + 1)?;
+        while let Some(op) = tokens.get(*pos).filter(|t| BINARY_OPERATORS[level].contains(&&t[..])) {
+            *pos += 1;
+            let rhs = self.binary_expr(tokens, pos, level + 1)?;
+            value = apply_binary(op, value, rhs)?;
+        }
+        Ok(value)
+    }
+
+    fn unary_expr(&self, tokens: &[String], pos: &mut usize) -> Result<i64, String> {
+        let token = match tokens.get(*pos) {
+            Some(token) => token,
+            None => return Err("unexpected end of expression".to_string()),
+        };
+        *pos += 1;
+        match &token[..] {
+            "!" => self.unary_expr(tokens, pos).map(|v| (v == 0) as i64),
+            "~" => self.unary_expr(tokens, pos).map(|v| !v),
+            "+" => self.unary_expr(tokens, pos),
+            "-" => self.unary_expr(tokens, pos)
+                .and_then(|v| v.checked_neg().ok_or_else(|| "overflow".to_string())),
+            "(" => {
+                let value = self.binary_expr(tokens, pos, 0)?;
+                expect(tokens, pos, ")")?;
+                Ok(value)
+            },
+            "defined" => {
+                let parens = tokens.get(*pos).map_or(false, |t| t == "(");
+                if parens {
+                    *pos += 1;
+                }
+                let name = match tokens.get(*pos) {
+                    Some(name) if is_ident(name.as_bytes()[0]) => name,
+                    _ => return Err("expected a macro name after `defined`".to_string()),
+                };
+                *pos += 1;
+                if parens {
+                    expect(tokens, pos, ")")?;
+                }
+                Ok(self.defines.contains_key(name) as i64)
+            },
+            _ if token.as_bytes()[0].is_ascii_digit() => {
+                parse_int(token).ok_or_else(|| format!("invalid number `{}`", token))
+            },
+            // Like cpp, an identifier that isn't a number once expanded is 0
+            _ if is_ident(token.as_bytes()[0]) => {
+                let expanded = self.substitute(token, &mut Vec::new());
+                Ok(parse_int(expanded.trim()).unwrap_or(0))
+            },
+            _ => Err(format!("unexpected `{}`", token)),
+        }
+    }
+
+    /// Replace macros in a line of XDR, leaving comments and `%` lines alone
+    fn expand_line(&self, text: &str, in_comment: &mut bool) -> String {
+        if !*in_comment && text.trim_start().starts_with('%') {
+            return text.to_string();
+        }
+
+        let bytes = text.as_bytes();
+        let mut out = String::with_capacity(text.len());
+        let mut start = 0;
+        let mut i = 0;
+        // Only ever sliced at ASCII bytes, so any UTF-8 text in between is kept whole
+        while i < bytes.len() {
+            if *in_comment {
+                match bytes[i..].windows(2).position(|w| w == b"*/") {
+                    Some(end) => {
+                        i += end + 2;
+                        *in_comment = false;
+                    },
+                    None => i = bytes.len(),
+                }
+            } else if bytes[i..].starts_with(b"//") {
+                i = bytes.len();
+            } else if bytes[i..].starts_with(b"/*") {
+                *in_comment = true;
+                i += 2;
+            } else if is_ident(bytes[i]) {
+                let word_start = i;
+                while i < bytes.len() && is_ident(bytes[i]) {
+                    i += 1;
+                }
+                let word = &text[word_start..i];
+                if self.defines.contains_key(word) && !bytes[word_start].is_ascii_digit() {
+                    out.push_str(&text[start..word_start]);
+                    out.push_str(&self.substitute(word, &mut Vec::new()));
+                    start = i;
+                }
+            } else {
+                i += 1;
+            }
+        }
+        out.push_str(&text[start..]);
+        out
+    }
+
+    /// Expand the macros in `text`. `expanding` holds the macros already being
+    /// expanded, which are left alone so self-referencing macros terminate.
+    fn substitute(&self, text: &str, expanding: &mut Vec<String>) -> String {
+        let bytes = text.as_bytes();
+        let mut out = String::with_capacity(text.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if !is_ident(bytes[i]) {
+                let next = text[i..].chars().next().unwrap();
+                out.push(next);
+                i += next.len_utf8();
+                continue;
+            }
+            let word_start = i;
+            while i < bytes.len() && is_ident(bytes[i]) {
+                i += 1;
+            }
+            let word = &text[word_start..i];
+            match self.defines.get(word) {
+                Some(value) if !expanding.iter().any(|w| w == word)
+                               && !bytes[word_start].is_ascii_digit() => {
+                    expanding.push(word.to_string());
+                    out.push_str(&self.substitute(value, expanding));
+                    expanding.pop();
+                },
+                _ => out.push_str(word),
+            }
+        }
+        out
+    }
+}
+
+/// Track block comments through a line that isn't otherwise looked at
+fn skip_comments(text: &str, in_comment: &mut bool) {
+    let mut rest = text;
+    loop {
+        if *in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    rest = &rest[end + 2..];
+                    *in_comment = false;
+                },
+                None => return,
+            }
+        } else {
+            let line_comment = rest.find("//");
+            match rest.find("/*") {
+                Some(start) if line_comment.map_or(true, |l| start < l) => {
+                    rest = &rest[start + 2..];
+                    *in_comment = true;
+                },
+                _ => return,
+            }
+        }
+    }
+}
+
+/// Remove comments from a directive, which lives on a single logical line
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        let block = rest.find("/*");
+        let line = rest.find("//");
+        match (block, line) {
+            (Some(b), l) if l.map_or(true, |l| b < l) => {
+                out.push_str(&rest[..b]);
+                out.push(' ');
+                match rest[b + 2..].find("*/") {
+                    Some(end) => rest = &rest[b + 2 + end + 2..],
+                    None => return out,
+                }
+            },
+            (_, Some(l)) => {
+                out.push_str(&rest[..l]);
+                return out;
+            },
+            _ => {
+                out.push_str(rest);
+                return out;
+            },
+        }
+    }
+}
+
+fn expr_tokens(expr: &str) -> Vec<String> {
+    let bytes = expr.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        if bytes[i].is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if is_ident(bytes[i]) {
+            while i < bytes.len() && is_ident(bytes[i]) {
+                i += 1;
+            }
+        } else if TWO_CHAR_OPERATORS.iter().any(|op| expr[i..].starts_with(op)) {
+            i += 2;
+        } else {
+            i += expr[i..].chars().next().unwrap().len_utf8();
+        }
+        tokens.push(expr[start..i].to_string());
+    }
+    tokens
+}
+
+fn expect(tokens: &[String], pos: &mut usize, token: &str) -> Result<(), String> {
+    match tokens.get(*pos) {
+        Some(t) if t == token => {
+            *pos += 1;
+            Ok(())
+        },
+        Some(t) => Err(format!("expected `{}`, found `{}`", token, t)),
+        None => Err(format!("expected `{}`", token)),
+    }
+}
+
+fn apply_binary(op: &str, lhs: i64, rhs: i64) -> Result<i64, String> {
+    let value = match op {
+        "||" => Some((lhs != 0 || rhs != 0) as i64),
+        "&&" => Some((lhs != 0 && rhs != 0) as i64),
+        "|" => Some(lhs | rhs),
+        "^" => Some(lhs ^ rhs),
+        "&" => Some(lhs & rhs),
+        "==" => Some((lhs == rhs) as i64),
+        "!=" => Some((lhs != rhs) as i64),
+        "<" => Some((lhs < rhs) as i64),
+        ">" => Some((lhs > rhs) as i64),
+        "<=" => Some((lhs <= rhs) as i64),
+        ">=" => Some((lhs >= rhs) as i64),
+        "<<" => if rhs < 0 { None } else { lhs.checked_shl(rhs as u32) },
+        ">>" => if rhs < 0 { None } else { lhs.checked_shr(rhs as u32) },
+        "+" => lhs.checked_add(rhs),
+        "-" => lhs.checked_sub(rhs),
+        "*" => lhs.checked_mul(rhs),
+        "/" | "%" if rhs == 0 => return Err("division by zero".to_string()),
+        "/" => lhs.checked_div(rhs),
+        "%" => lhs.checked_rem(rhs),
+        _ => unreachable!(),
+    };
+    value.ok_or_else(|| format!("`{} {} {}` overflows", lhs, op, rhs))
+}
+
+fn parse_int(text: &str) -> Option<i64> {
+    let text = text.trim_end_matches(|c| c == 'u' || c == 'U' || c == 'l' || c == 'L');
+    if text.starts_with("0x") || text.starts_with("0X") {
+        i64::from_str_radix(&text[2..], 16).ok()
+    } else if text.len() > 1 && text.starts_with('0') {
+        i64::from_str_radix(&text[1..], 8).ok()
+    } else {
+        text.parse().ok()
+    }
+}
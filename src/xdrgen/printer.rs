@@ -0,0 +1,853 @@
+// Print a parsed specification back out as .x source in one canonical
+// layout, for `xdrgen fmt`.
+use std::collections::HashSet;
+
+use ast::*;
+use parser::{self, ParseError};
+use preprocessor::Preprocessed;
+
+const INDENT: &'static str = "    ";
+
+// Marks where a trailing comment starts until they're lined up
+const TRAILING: char = '\0';
+
+/// Reformat the .x source of `file`: four space indentation, one definition,
+/// field, case or procedure per line, at most one blank line in a row and
+/// comments after consecutive lines lined up. Comments, `%` lines and `#`
+/// lines are kept, numbers and constant expressions are written as they were.
+///
+/// The source isn't preprocessed. It's an error for a comment to be where
+/// the parser doesn't keep one, or for the result to parse differently.
+pub fn format(file: &str, source: &str) -> Result<String, ParseError> {
+    let spec = try!(parser::parse(file, source, false));
+    let unit = Preprocessed::verbatim(file, source);
+
+    // Whatever `comments` finds the tree must have kept
+    let mut kept = Vec::new();
+    for def in spec.definitions.iter() {
+        definition_comments(def, &mut kept);
+    }
+    for (span, text) in parser::comments(source) {
+        match kept.iter().position(|comment| *comment == text) {
+            Some(i) => { kept.remove(i); },
+            None => {
+                let message = "comment would be lost, `fmt` keeps comments above or after a \
+                               definition, field, enum value, union arm, version or procedure";
+                return Err(ParseError { diagnostics: vec![unit.diagnostic(span, message.to_string())] });
+            },
+        }
+    }
+
+    let formatted = print(&spec, source);
+    let reparsed = try!(parser::parse(file, &formatted, false).map_err(|e| {
+        let message = format!("formatted source does not parse, this is a bug in `fmt`:\n{}", e);
+        ParseError { diagnostics: vec![unit.diagnostic(Span::new(0, 0), message)] }
+    }));
+    let after: Vec<Definition> = reparsed.definitions.into_iter().map(normalized_definition).collect();
+    for (i, def) in spec.definitions.iter().enumerate() {
+        if after.get(i) != Some(&normalized_definition(def.clone())) {
+            let message = "formatting changes what this means, this is a bug in `fmt`";
+            return Err(ParseError { diagnostics: vec![unit.diagnostic(def.span, message.to_string())] });
+        }
+    }
+    if after.len() != spec.definitions.len() {
+        let message = "formatting adds definitions, this is a bug in `fmt`";
+        return Err(ParseError { diagnostics: vec![unit.diagnostic(Span::new(0, 0), message.to_string())] });
+    }
+    Ok(formatted)
+}
+
+/// The canonical source of `spec`, which was parsed from `source`
+pub fn print(spec: &Specification, source: &str) -> String {
+    let line_comments = parser::comments(source).into_iter()
+        .filter(|&(span, _)| source[span.start..].starts_with("//"))
+        .map(|(_, text)| text)
+        .collect();
+    let mut printer = Printer {
+        source: source,
+        line_comments: line_comments,
+        out: String::new(),
+        level: 0,
+    };
+    let mut previous: Option<&Definition> = None;
+    for def in spec.definitions.iter() {
+        if let Some(prev) = previous {
+            if printer.blank_between(prev.span.end, def.span.start) || is_block(prev) || is_block(def) {
+                printer.out.push('\n');
+            }
+        }
+        printer.definition(def);
+        previous = Some(def);
+    }
+    align_trailing(&printer.out)
+}
+
+// Line up the comments after each run of consecutive, equally indented
+// lines that have one
+fn align_trailing(text: &str) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut aligned = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let run = lines[i..].iter()
+            .take_while(|line| line.contains(TRAILING) && indent(line) == indent(lines[i]))
+            .count();
+        if run == 0 {
+            aligned.push(lines[i].to_string());
+            i += 1;
+            continue;
+        }
+        let width = lines[i..i + run].iter()
+            .map(|line| line[..line.find(TRAILING).unwrap()].chars().count())
+            .max()
+            .unwrap();
+        for line in lines[i..i + run].iter() {
+            let at = line.find(TRAILING).unwrap();
+            let padding = width - line[..at].chars().count();
+            aligned.push(format!("{}{} {}", &line[..at], " ".repeat(padding), &line[at + 1..]));
+        }
+        i += run;
+    }
+    aligned.join("\n")
+}
+
+// Definitions spanning several lines get blank lines around them
+fn is_block(def: &Definition) -> bool {
+    match def.kind {
+        DefinitionKind::Enum{..} |
+        DefinitionKind::Struct{..} |
+        DefinitionKind::Union{..} |
+        DefinitionKind::Program(_) |
+        DefinitionKind::Namespace{..} => true,
+        DefinitionKind::Typedef(ref decl) => match decl.type_spec() {
+            Some(&TypeSpec::Primitive(..)) | Some(&TypeSpec::Named(_)) | None => false,
+            Some(_) => true,
+        },
+        DefinitionKind::Const{..} |
+        DefinitionKind::Comment(_) |
+        DefinitionKind::Snippet(_) |
+        DefinitionKind::Directive(_) => false,
+    }
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    // The text of every `//` comment, the rest are `/* */`
+    line_comments: HashSet<String>,
+    out: String,
+    level: usize,
+}
+
+impl<'a> Printer<'a> {
+    fn indent(&mut self) {
+        for _ in 0..self.level {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    /// Whether the source has an empty line between two nodes
+    fn blank_between(&self, end: usize, start: usize) -> bool {
+        let lines: Vec<&str> = self.source[end..start].split('\n').collect();
+        lines.len() > 2 && lines[1..lines.len() - 1].iter().any(|line| line.trim().is_empty())
+    }
+
+    /// Whether the last of a node's comments is on the same line, after it
+    fn has_trailing(&self, end: usize, comments: &[String]) -> bool {
+        let rest = self.source[end..].trim_start_matches(|c| c == ' ' || c == '\t' || c == ';' || c == ',');
+        !comments.is_empty() && (rest.starts_with("//") || rest.starts_with("/*"))
+    }
+
+    /// `text` as a comment at the current indentation
+    fn comment(&self, text: &str) -> String {
+        let lines: Vec<&str> = text.split('\n').collect();
+        if lines.len() == 1 {
+            if self.line_comments.contains(text) {
+                return format!("//{}", text.trim_end());
+            }
+            if text.trim().is_empty() {
+                return "/* */".to_string();
+            }
+            return format!("/* {} */", text.trim());
+        }
+
+        // Keep the lines' indentation relative to each other, and a column of
+        // `*`s one space in from the `/*`
+        let rest = &lines[1..];
+        let written = || rest.iter().filter(|line| !line.trim().is_empty());
+        let margin = written().map(|line| line.len() - line.trim_start().len()).min().unwrap_or(0);
+        let starred = written().all(|line| line.trim_start().starts_with('*'));
+        let mut indent = INDENT.repeat(self.level);
+        if starred {
+            indent.push(' ');
+        }
+
+        let mut comment = format!("/*{}", lines[0].trim_end());
+        for (i, line) in rest.iter().enumerate() {
+            comment.push('\n');
+            if line.trim().is_empty() {
+                if i == rest.len() - 1 {
+                    comment.push_str(&indent);
+                }
+            } else {
+                comment.push_str(&indent);
+                comment.push_str(line[margin..].trim_end());
+                if i == rest.len() - 1 {
+                    comment.push(' ');
+                }
+            }
+        }
+        comment.push_str("*/");
+        comment
+    }
+
+    /// Write the comments above a node, returning the one to put after it
+    fn leading(&mut self, comments: &'a [String], end: usize) -> Option<&'a String> {
+        let (above, after) = if self.has_trailing(end, comments) {
+            (&comments[..comments.len() - 1], comments.last())
+        } else {
+            (comments, None)
+        };
+        self.comment_lines(above);
+        after
+    }
+
+    /// Write comments on lines of their own, such as those closing a body
+    fn comment_lines(&mut self, comments: &[String]) {
+        for text in comments.iter() {
+            self.indent();
+            let comment = self.comment(text);
+            self.out.push_str(&comment);
+            self.out.push('\n');
+        }
+    }
+
+    /// End the line, with the comment following the node on it
+    fn end_line(&mut self, trailing: Option<&String>) {
+        if let Some(text) = trailing {
+            let comment = self.comment(text);
+            self.out.push(TRAILING);
+            self.out.push_str(&comment);
+        }
+        self.out.push('\n');
+    }
+
+    fn definition(&mut self, def: &'a Definition) {
+        let trailing = self.leading(&def.comments, def.span.end);
+        match def.kind {
+            DefinitionKind::Const{ref id, ref value} => {
+                let value = self.value(value, 0);
+                self.out.push_str(&format!("const {} = {};", id, value));
+            },
+            DefinitionKind::Typedef(ref decl) => {
+                self.out.push_str("typedef ");
+                self.declaration(decl);
+                self.out.push(';');
+            },
+            DefinitionKind::Enum{ref id, ref body} => {
+                self.out.push_str(&format!("enum {} ", id));
+                self.enum_body(body);
+                self.out.push(';');
+            },
+            DefinitionKind::Struct{ref id, ref body} => {
+                self.out.push_str(&format!("struct {} ", id));
+                self.struct_body(body);
+                self.out.push(';');
+            },
+            DefinitionKind::Union{ref id, ref body} => {
+                self.out.push_str(&format!("union {} ", id));
+                self.union_body(body);
+                self.out.push(';');
+            },
+            DefinitionKind::Program(ref prog) => return self.program(prog),
+            DefinitionKind::Namespace{ref id, ref programs} => {
+                self.out.push_str(&format!("namespace {} {{\n", id));
+                self.level += 1;
+                for (i, prog) in programs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push('\n');
+                    }
+                    self.program(prog);
+                }
+                self.level -= 1;
+                self.out.push_str("};");
+            },
+            DefinitionKind::Comment(ref text) => {
+                let comment = self.comment(text);
+                self.out.push_str(&comment);
+            },
+            DefinitionKind::Snippet(ref line) => self.out.push_str(&format!("%{}", line.trim_end())),
+            DefinitionKind::Directive(ref line) => self.out.push_str(&format!("#{}", line)),
+        }
+        self.end_line(trailing);
+    }
+
+    fn program(&mut self, prog: &'a Program) {
+        let trailing = self.leading(&prog.comments, prog.span.end);
+        self.indent();
+        self.out.push_str(&format!("program {} {{\n", prog.id));
+        self.level += 1;
+        for (i, version) in prog.versions.iter().enumerate() {
+            if i > 0 && self.blank_between(prog.versions[i - 1].span.end, version.span.start) {
+                self.out.push('\n');
+            }
+            let trailing = self.leading(&version.comments, version.span.end);
+            self.indent();
+            self.out.push_str(&format!("version {} {{\n", version.id));
+            self.level += 1;
+            for (j, procedure) in version.procedures.iter().enumerate() {
+                if j > 0 && self.blank_between(version.procedures[j - 1].span.end, procedure.span.start) {
+                    self.out.push('\n');
+                }
+                self.procedure(procedure);
+            }
+            self.comment_lines(&version.end_comments);
+            self.level -= 1;
+            self.indent();
+            let number = self.number(version.span, version.number);
+            self.out.push_str(&format!("}} = {};", number));
+            self.end_line(trailing);
+        }
+        self.comment_lines(&prog.end_comments);
+        self.level -= 1;
+        self.indent();
+        let number = self.number(prog.span, prog.number);
+        self.out.push_str(&format!("}} = {};", number));
+        self.end_line(trailing);
+    }
+
+    fn procedure(&mut self, procedure: &'a Procedure) {
+        let trailing = self.leading(&procedure.comments, procedure.span.end);
+        self.indent();
+        match procedure.return_type {
+            Some(ref ty) => self.type_spec(ty),
+            None => self.out.push_str("void"),
+        }
+        self.out.push_str(&format!(" {}(", procedure.id));
+        if procedure.arg_types.is_empty() {
+            self.out.push_str("void");
+        }
+        for (i, ty) in procedure.arg_types.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.type_spec(ty);
+        }
+        let number = self.number(procedure.span, procedure.number);
+        self.out.push_str(&format!(") = {};", number));
+        self.end_line(trailing);
+    }
+
+    /// A program, version or procedure number as written, the last thing in
+    /// its span
+    fn number(&self, span: Span, number: i128) -> String {
+        let text = &self.source[span.start..span.end];
+        let written = text.rfind('=').map_or("", |eq| text[eq + 1..].trim_end_matches(';').trim());
+        if !written.is_empty() && written.bytes().all(parser::is_ident) {
+            written.to_string()
+        } else {
+            number.to_string()
+        }
+    }
+
+    fn enum_body(&mut self, body: &'a EnumBody) {
+        self.out.push_str("{\n");
+        self.level += 1;
+        for (i, variant) in body.variants.iter().enumerate() {
+            if i > 0 && self.blank_between(body.variants[i - 1].span.end, variant.span.start) {
+                self.out.push('\n');
+            }
+            let trailing = self.leading(&variant.comments, variant.span.end);
+            self.indent();
+            self.out.push_str(&variant.id.name);
+            if let Some(ref value) = variant.value {
+                let value = self.value(value, 0);
+                self.out.push_str(&format!(" = {}", value));
+            }
+            if i < body.variants.len() - 1 {
+                self.out.push(',');
+            }
+            self.end_line(trailing);
+        }
+        self.comment_lines(&body.end_comments);
+        self.level -= 1;
+        self.indent();
+        self.out.push('}');
+    }
+
+    fn struct_body(&mut self, body: &'a StructBody) {
+        self.out.push_str("{\n");
+        self.level += 1;
+        for (i, field) in body.fields.iter().enumerate() {
+            if i > 0 && self.blank_between(body.fields[i - 1].span.end, field.span.start) {
+                self.out.push('\n');
+            }
+            self.member(field);
+        }
+        self.comment_lines(&body.end_comments);
+        self.level -= 1;
+        self.indent();
+        self.out.push('}');
+    }
+
+    fn union_body(&mut self, body: &'a UnionBody) {
+        self.out.push_str("switch (");
+        self.declaration(&body.discriminant);
+        self.out.push_str(") {\n");
+        self.level += 1;
+        for (i, case) in body.cases.iter().enumerate() {
+            if i > 0 && self.blank_between(body.cases[i - 1].span.end, case.span.start) {
+                self.out.push('\n');
+            }
+            let trailing = self.leading(&case.comments, case.span.end);
+            for value in case.values.iter() {
+                self.indent();
+                let value = self.value(value, 0);
+                self.out.push_str(&format!("case {}:\n", value));
+            }
+            self.level += 1;
+            self.indent();
+            self.declaration(&case.decl);
+            self.out.push(';');
+            self.end_line(trailing);
+            self.level -= 1;
+        }
+        if let Some(ref default) = body.default {
+            let trailing = self.leading(&default.comments, default.span.end);
+            self.indent();
+            self.out.push_str("default:\n");
+            self.level += 1;
+            self.indent();
+            self.declaration(default);
+            self.out.push(';');
+            self.end_line(trailing);
+            self.level -= 1;
+        }
+        self.comment_lines(&body.end_comments);
+        self.level -= 1;
+        self.indent();
+        self.out.push('}');
+    }
+
+    /// A struct field on its own line, with its comments
+    fn member(&mut self, decl: &'a Declaration) {
+        let trailing = self.leading(&decl.comments, decl.span.end);
+        self.indent();
+        self.declaration(decl);
+        self.out.push(';');
+        self.end_line(trailing);
+    }
+
+    fn declaration(&mut self, decl: &'a Declaration) {
+        match decl.kind {
+            DeclarationKind::Void => self.out.push_str("void"),
+            DeclarationKind::Scalar{ref ty, ref id} => {
+                self.type_spec(ty);
+                self.out.push_str(&format!(" {}", id));
+            },
+            DeclarationKind::Array{ref ty, ref id, ref size} => {
+                self.type_spec(ty);
+                let size = self.value(size, 0);
+                self.out.push_str(&format!(" {}[{}]", id, size));
+            },
+            DeclarationKind::VarArray{ref ty, ref id, ref size} => {
+                self.type_spec(ty);
+                let size = self.bound(size);
+                self.out.push_str(&format!(" {}<{}>", id, size));
+            },
+            DeclarationKind::Opaque{ref id, ref size} => {
+                let size = self.value(size, 0);
+                self.out.push_str(&format!("opaque {}[{}]", id, size));
+            },
+            DeclarationKind::VarOpaque{ref id, ref size} => {
+                let size = self.bound(size);
+                self.out.push_str(&format!("opaque {}<{}>", id, size));
+            },
+            DeclarationKind::String{ref id, ref size} => {
+                let size = self.bound(size);
+                self.out.push_str(&format!("string {}<{}>", id, size));
+            },
+            DeclarationKind::Pointer{ref ty, ref id} => {
+                self.type_spec(ty);
+                self.out.push_str(&format!(" *{}", id));
+            },
+        }
+    }
+
+    fn bound(&self, size: &Option<Value>) -> String {
+        size.as_ref().map_or(String::new(), |size| self.value(size, 0))
+    }
+
+    fn type_spec(&mut self, ty: &'a TypeSpec) {
+        match *ty {
+            // As written, `unsigned` and `uint32_t` stay as they are
+            TypeSpec::Primitive(_, span) => {
+                let words: Vec<&str> = self.source[span.start..span.end].split_whitespace().collect();
+                self.out.push_str(&words.join(" "));
+            },
+            TypeSpec::Named(ref id) => self.out.push_str(&id.name),
+            TypeSpec::Enum(ref body) => {
+                self.out.push_str("enum ");
+                self.enum_body(body);
+            },
+            TypeSpec::Struct(ref body) => {
+                self.out.push_str("struct ");
+                self.struct_body(body);
+            },
+            TypeSpec::Union(ref body) => {
+                self.out.push_str("union ");
+                self.union_body(body);
+            },
+        }
+    }
+
+    /// A constant expression, parenthesized if its operators bind less tightly
+    /// than `precedence`
+    fn value(&self, value: &Value, precedence: usize) -> String {
+        match *value {
+            // Folded while parsing, the source still has how it was written
+            Value::Constant(_, span) => {
+                let text = spaced(&expression_tokens(&self.source[span.start..span.end]));
+                // Only binary operators are spaced out
+                if text.contains(' ') && precedence > 0 {
+                    format!("({})", text)
+                } else {
+                    text
+                }
+            },
+            Value::Named(ref id) => id.name.clone(),
+            Value::Unary{op, ref operand, ..} => {
+                let op = match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "~",
+                };
+                format!("{}{}", op, self.value(operand, UNARY_PRECEDENCE))
+            },
+            Value::Binary{op, ref lhs, ref rhs, ..} => {
+                let (symbol, own) = binary_op(op);
+                let text = format!("{} {} {}", self.value(lhs, own), symbol, self.value(rhs, own + 1));
+                if own < precedence {
+                    format!("({})", text)
+                } else {
+                    text
+                }
+            },
+        }
+    }
+}
+
+// Binds tighter than any binary operator
+const UNARY_PRECEDENCE: usize = 7;
+
+/// How an operator is written and its C precedence, as the parser has them
+fn binary_op(op: BinaryOp) -> (&'static str, usize) {
+    match op {
+        BinaryOp::Or => ("|", 1),
+        BinaryOp::Xor => ("^", 2),
+        BinaryOp::And => ("&", 3),
+        BinaryOp::Shl => ("<<", 4),
+        BinaryOp::Shr => (">>", 4),
+        BinaryOp::Add => ("+", 5),
+        BinaryOp::Sub => ("-", 5),
+        BinaryOp::Mul => ("*", 6),
+        BinaryOp::Div => ("/", 6),
+        BinaryOp::Rem => ("%", 6),
+    }
+}
+
+// Split a constant expression into numbers, names, operators and parentheses
+fn expression_tokens(text: &str) -> Vec<String> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        if parser::is_ident(bytes[i]) {
+            while i < bytes.len() && parser::is_ident(bytes[i]) {
+                i += 1;
+            }
+        } else if (bytes[i] == b'<' || bytes[i] == b'>') && bytes.get(i + 1) == Some(&bytes[i]) {
+            i += 2;
+        } else if (bytes[i] as char).is_whitespace() {
+            i += 1;
+            continue;
+        } else {
+            i += 1;
+        }
+        tokens.push(text[start..i].to_string());
+    }
+    tokens
+}
+
+// Spaces around binary operators, none after unary ones or inside parentheses
+fn spaced(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut operand_next = true;
+    for token in tokens.iter() {
+        let first = token.as_bytes()[0];
+        if parser::is_ident(first) {
+            text.push_str(token);
+            operand_next = false;
+        } else if token == "(" {
+            text.push('(');
+            operand_next = true;
+        } else if token == ")" {
+            text.push(')');
+            operand_next = false;
+        } else if operand_next {
+            // Unary
+            text.push_str(token);
+        } else {
+            text.push_str(&format!(" {} ", token));
+            operand_next = true;
+        }
+    }
+    text
+}
+
+fn definition_comments(def: &Definition, comments: &mut Vec<String>) {
+    comments.extend(def.comments.iter().cloned());
+    match def.kind {
+        DefinitionKind::Comment(ref text) => comments.push(text.clone()),
+        DefinitionKind::Typedef(ref decl) => declaration_comments(decl, comments),
+        DefinitionKind::Enum{ref body, ..} => enum_comments(body, comments),
+        DefinitionKind::Struct{ref body, ..} => struct_comments(body, comments),
+        DefinitionKind::Union{ref body, ..} => union_comments(body, comments),
+        DefinitionKind::Program(ref prog) => program_comments(prog, comments),
+        DefinitionKind::Namespace{ref programs, ..} => {
+            for prog in programs.iter() {
+                program_comments(prog, comments);
+            }
+        },
+        DefinitionKind::Const{..} | DefinitionKind::Snippet(_) | DefinitionKind::Directive(_) => {},
+    }
+}
+
+fn declaration_comments(decl: &Declaration, comments: &mut Vec<String>) {
+    comments.extend(decl.comments.iter().cloned());
+    if let Some(ty) = decl.type_spec() {
+        type_comments(ty, comments);
+    }
+}
+
+fn type_comments(ty: &TypeSpec, comments: &mut Vec<String>) {
+    match *ty {
+        TypeSpec::Enum(ref body) => enum_comments(body, comments),
+        TypeSpec::Struct(ref body) => struct_comments(body, comments),
+        TypeSpec::Union(ref body) => union_comments(body, comments),
+        TypeSpec::Primitive(..) | TypeSpec::Named(_) => {},
+    }
+}
+
+fn enum_comments(body: &EnumBody, comments: &mut Vec<String>) {
+    for variant in body.variants.iter() {
+        comments.extend(variant.comments.iter().cloned());
+    }
+    comments.extend(body.end_comments.iter().cloned());
+}
+
+fn struct_comments(body: &StructBody, comments: &mut Vec<String>) {
+    for field in body.fields.iter() {
+        declaration_comments(field, comments);
+    }
+    comments.extend(body.end_comments.iter().cloned());
+}
+
+fn union_comments(body: &UnionBody, comments: &mut Vec<String>) {
+    declaration_comments(&body.discriminant, comments);
+    for case in body.cases.iter() {
+        comments.extend(case.comments.iter().cloned());
+        declaration_comments(&case.decl, comments);
+    }
+    if let Some(ref default) = body.default {
+        declaration_comments(default, comments);
+    }
+    comments.extend(body.end_comments.iter().cloned());
+}
+
+fn program_comments(prog: &Program, comments: &mut Vec<String>) {
+    comments.extend(prog.comments.iter().cloned());
+    for version in prog.versions.iter() {
+        comments.extend(version.comments.iter().cloned());
+        for procedure in version.procedures.iter() {
+            comments.extend(procedure.comments.iter().cloned());
+            for ty in procedure.return_type.iter().chain(procedure.arg_types.iter()) {
+                type_comments(ty, comments);
+            }
+        }
+        comments.extend(version.end_comments.iter().cloned());
+    }
+    comments.extend(prog.end_comments.iter().cloned());
+}
+
+// The tree without what formatting may change: spans, and the spacing and
+// markers of comments
+
+fn normalized_comments(comments: Vec<String>) -> Vec<String> {
+    comments.into_iter().map(|text| normalized_comment(&text)).collect()
+}
+
+fn normalized_comment(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn normalized_ident(id: Ident) -> Ident {
+    Ident { name: id.name, span: Span::default() }
+}
+
+fn normalized_value(value: Value) -> Value {
+    match value {
+        Value::Constant(val, _) => Value::Constant(val, Span::default()),
+        Value::Named(id) => Value::Named(normalized_ident(id)),
+        Value::Unary{op, operand, ..} => Value::Unary {
+            op: op,
+            operand: Box::new(normalized_value(*operand)),
+            span: Span::default(),
+        },
+        Value::Binary{op, lhs, rhs, ..} => Value::Binary {
+            op: op,
+            lhs: Box::new(normalized_value(*lhs)),
+            rhs: Box::new(normalized_value(*rhs)),
+            span: Span::default(),
+        },
+    }
+}
+
+fn normalized_type(ty: TypeSpec) -> TypeSpec {
+    match ty {
+        TypeSpec::Primitive(ty, _) => TypeSpec::Primitive(ty, Span::default()),
+        TypeSpec::Named(id) => TypeSpec::Named(normalized_ident(id)),
+        TypeSpec::Enum(body) => TypeSpec::Enum(normalized_enum(body)),
+        TypeSpec::Struct(body) => TypeSpec::Struct(normalized_struct(body)),
+        TypeSpec::Union(body) => TypeSpec::Union(Box::new(normalized_union(*body))),
+    }
+}
+
+fn normalized_enum(body: EnumBody) -> EnumBody {
+    EnumBody {
+        variants: body.variants.into_iter().map(|variant| EnumVariant {
+            id: normalized_ident(variant.id),
+            value: variant.value.map(normalized_value),
+            span: Span::default(),
+            comments: normalized_comments(variant.comments),
+        }).collect(),
+        span: Span::default(),
+        end_comments: normalized_comments(body.end_comments),
+    }
+}
+
+fn normalized_struct(body: StructBody) -> StructBody {
+    StructBody {
+        fields: body.fields.into_iter().map(normalized_declaration).collect(),
+        span: Span::default(),
+        end_comments: normalized_comments(body.end_comments),
+    }
+}
+
+fn normalized_union(body: UnionBody) -> UnionBody {
+    UnionBody {
+        discriminant: normalized_declaration(body.discriminant),
+        cases: body.cases.into_iter().map(|case| UnionCase {
+            values: case.values.into_iter().map(normalized_value).collect(),
+            decl: normalized_declaration(case.decl),
+            span: Span::default(),
+            comments: normalized_comments(case.comments),
+        }).collect(),
+        default: body.default.map(normalized_declaration),
+        span: Span::default(),
+        end_comments: normalized_comments(body.end_comments),
+    }
+}
+
+fn normalized_declaration(decl: Declaration) -> Declaration {
+    let kind = match decl.kind {
+        DeclarationKind::Void => DeclarationKind::Void,
+        DeclarationKind::Scalar{ty, id} => DeclarationKind::Scalar {
+            ty: normalized_type(ty),
+            id: normalized_ident(id),
+        },
+        DeclarationKind::Array{ty, id, size} => DeclarationKind::Array {
+            ty: normalized_type(ty),
+            id: normalized_ident(id),
+            size: normalized_value(size),
+        },
+        DeclarationKind::VarArray{ty, id, size} => DeclarationKind::VarArray {
+            ty: normalized_type(ty),
+            id: normalized_ident(id),
+            size: size.map(normalized_value),
+        },
+        DeclarationKind::Opaque{id, size} => DeclarationKind::Opaque {
+            id: normalized_ident(id),
+            size: normalized_value(size),
+        },
+        DeclarationKind::VarOpaque{id, size} => DeclarationKind::VarOpaque {
+            id: normalized_ident(id),
+            size: size.map(normalized_value),
+        },
+        DeclarationKind::String{id, size} => DeclarationKind::String {
+            id: normalized_ident(id),
+            size: size.map(normalized_value),
+        },
+        DeclarationKind::Pointer{ty, id} => DeclarationKind::Pointer {
+            ty: normalized_type(ty),
+            id: normalized_ident(id),
+        },
+    };
+    Declaration { kind: kind, span: Span::default(), comments: normalized_comments(decl.comments) }
+}
+
+fn normalized_program(prog: Program) -> Program {
+    Program {
+        id: normalized_ident(prog.id),
+        versions: prog.versions.into_iter().map(|version| Version {
+            id: normalized_ident(version.id),
+            procedures: version.procedures.into_iter().map(|procedure| Procedure {
+                id: normalized_ident(procedure.id),
+                return_type: procedure.return_type.map(normalized_type),
+                arg_types: procedure.arg_types.into_iter().map(normalized_type).collect(),
+                number: procedure.number,
+                span: Span::default(),
+                comments: normalized_comments(procedure.comments),
+            }).collect(),
+            number: version.number,
+            span: Span::default(),
+            comments: normalized_comments(version.comments),
+            end_comments: normalized_comments(version.end_comments),
+        }).collect(),
+        number: prog.number,
+        span: Span::default(),
+        comments: normalized_comments(prog.comments),
+        end_comments: normalized_comments(prog.end_comments),
+    }
+}
+
+fn normalized_definition(def: Definition) -> Definition {
+    let kind = match def.kind {
+        DefinitionKind::Const{id, value} => DefinitionKind::Const {
+            id: normalized_ident(id),
+            value: normalized_value(value),
+        },
+        DefinitionKind::Typedef(decl) => DefinitionKind::Typedef(normalized_declaration(decl)),
+        DefinitionKind::Enum{id, body} => DefinitionKind::Enum {
+            id: normalized_ident(id),
+            body: normalized_enum(body),
+        },
+        DefinitionKind::Struct{id, body} => DefinitionKind::Struct {
+            id: normalized_ident(id),
+            body: normalized_struct(body),
+        },
+        DefinitionKind::Union{id, body} => DefinitionKind::Union {
+            id: normalized_ident(id),
+            body: normalized_union(body),
+        },
+        DefinitionKind::Program(prog) => DefinitionKind::Program(normalized_program(prog)),
+        DefinitionKind::Namespace{id, programs} => DefinitionKind::Namespace {
+            id: normalized_ident(id),
+            programs: programs.into_iter().map(normalized_program).collect(),
+        },
+        DefinitionKind::Comment(text) => DefinitionKind::Comment(normalized_comment(&text)),
+        DefinitionKind::Snippet(line) => DefinitionKind::Snippet(line.trim_end().to_string()),
+        DefinitionKind::Directive(line) => DefinitionKind::Directive(line),
+    };
+    Definition { kind: kind, span: Span::default(), comments: normalized_comments(def.comments) }
+}
@@ -0,0 +1,630 @@
+// Name resolution and validation between parsing and codegen. Everything is
+// looked up across all the files being compiled, so definitions may be used
+// before they appear.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use ast::*;
+use parser::{self, Diagnostic, ParseError};
+use preprocessor::Preprocessed;
+
+/// What a type name refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    Enum,
+    Struct,
+    Union,
+    /// Any other typedef
+    Typedef,
+}
+
+/// The names defined by a set of files, with every constant evaluated
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    /// Every `const`, by name
+    pub constants: HashMap<String, i128>,
+    /// The values of every enum in declaration order, by enum name. Values
+    /// left out get the previous one plus one, like C.
+    pub enums: HashMap<String, Vec<(String, i128)>>,
+    /// Every type name, and what defines it
+    pub types: HashMap<String, TypeKind>,
+    // Enum values by name, including those of anonymous enums
+    values: HashMap<String, i128>,
+}
+
+impl Schema {
+    /// The value of a named constant or enum value. `TRUE` and `FALSE` are the
+    /// values of `bool`.
+    pub fn value(&self, name: &str) -> Option<i128> {
+        self.constants.get(name).or_else(|| self.values.get(name)).cloned().or_else(|| builtin(name))
+    }
+
+    /// Evaluate a constant expression against the resolved constants
+    pub fn evaluate(&self, value: &Value) -> Result<i128, String> {
+        parser::evaluate(value, &|name: &str| self.value(name))
+    }
+}
+
+fn builtin(name: &str) -> Option<i128> {
+    match name {
+        "FALSE" => Some(0),
+        "TRUE" => Some(1),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NameKind {
+    Constant,
+    EnumValue,
+    Type(TypeKind),
+    Program,
+    Namespace,
+}
+
+// Where the value of a constant or enum value comes from
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    Expr(&'a Value),
+    // An enum value without one, after `previous` or first in its enum
+    Implicit{previous: Option<&'a str>},
+}
+
+#[derive(Clone, Copy)]
+struct Item<'a> {
+    unit: usize,
+    span: Span,
+    source: Source<'a>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Busy,
+    Done(i128),
+    Failed,
+}
+
+// The type a union switches on, which decides what its case labels may be
+enum Discriminant {
+    Int,
+    Uint,
+    Bool,
+    Enum(String),
+}
+
+struct Resolver<'a> {
+    units: &'a [(&'a Preprocessed, &'a Specification)],
+    names: HashMap<String, (usize, Span, NameKind)>,
+    items: HashMap<String, Item<'a>>,
+    states: RefCell<HashMap<String, State>>,
+    // Named enums, and the names of their values in order
+    enums: Vec<(String, Vec<&'a str>)>,
+    typedefs: HashMap<String, &'a TypeSpec>,
+    schema: Schema,
+    // With the file and offset they were found at, to sort them by
+    diagnostics: RefCell<Vec<(usize, usize, Diagnostic)>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn error(&self, unit: usize, span: Span, message: String) {
+        let diagnostic = self.units[unit].0.diagnostic(span, message);
+        let mut diagnostics = self.diagnostics.borrow_mut();
+        if !diagnostics.iter().any(|d| d.2 == diagnostic) {
+            diagnostics.push((unit, span.start, diagnostic));
+        }
+    }
+
+    fn declare(&mut self, unit: usize, id: &Ident, kind: NameKind) -> bool {
+        if let Some(&(first_unit, first_span, _)) = self.names.get(&id.name) {
+            let first = self.units[first_unit].0.diagnostic(first_span, String::new());
+            self.error(unit, id.span, format!("`{}` is defined more than once, first at {}:{}",
+                                              id, first.file, first.line));
+            return false;
+        }
+        self.names.insert(id.name.clone(), (unit, id.span, kind));
+        true
+    }
+
+    fn collect(&mut self) {
+        let units = self.units;
+        for (unit, &(_, spec)) in units.iter().enumerate() {
+            for def in spec.definitions.iter() {
+                match def.kind {
+                    DefinitionKind::Const{ref id, ref value} => {
+                        if self.declare(unit, id, NameKind::Constant) {
+                            self.items.insert(id.name.clone(), Item {
+                                unit: unit,
+                                span: id.span,
+                                source: Source::Expr(value),
+                            });
+                        }
+                    },
+                    DefinitionKind::Typedef(ref decl) => {
+                        let kind = match decl.kind {
+                            DeclarationKind::Scalar{ty: TypeSpec::Enum(_), ..} => TypeKind::Enum,
+                            DeclarationKind::Scalar{ty: TypeSpec::Struct(_), ..} => TypeKind::Struct,
+                            DeclarationKind::Scalar{ty: TypeSpec::Union(_), ..} => TypeKind::Union,
+                            _ => TypeKind::Typedef,
+                        };
+                        if let Some(id) = decl.id() {
+                            if self.declare(unit, id, NameKind::Type(kind)) {
+                                self.schema.types.insert(id.name.clone(), kind);
+                                if let DeclarationKind::Scalar{ref ty, ..} = decl.kind {
+                                    self.typedefs.insert(id.name.clone(), ty);
+                                }
+                            }
+                            if let DeclarationKind::Scalar{ty: TypeSpec::Enum(ref body), ..} = decl.kind {
+                                self.collect_enum(unit, Some(id), body);
+                                continue;
+                            }
+                        }
+                        self.collect_nested(unit, decl);
+                    },
+                    DefinitionKind::Enum{ref id, ref body} => {
+                        if self.declare(unit, id, NameKind::Type(TypeKind::Enum)) {
+                            self.schema.types.insert(id.name.clone(), TypeKind::Enum);
+                        }
+                        self.collect_enum(unit, Some(id), body);
+                    },
+                    DefinitionKind::Struct{ref id, ref body} => {
+                        if self.declare(unit, id, NameKind::Type(TypeKind::Struct)) {
+                            self.schema.types.insert(id.name.clone(), TypeKind::Struct);
+                        }
+                        for field in body.fields.iter() {
+                            self.collect_nested(unit, field);
+                        }
+                    },
+                    DefinitionKind::Union{ref id, ref body} => {
+                        if self.declare(unit, id, NameKind::Type(TypeKind::Union)) {
+                            self.schema.types.insert(id.name.clone(), TypeKind::Union);
+                        }
+                        self.collect_union(unit, body);
+                    },
+                    DefinitionKind::Program(ref prog) => {
+                        self.declare(unit, &prog.id, NameKind::Program);
+                    },
+                    DefinitionKind::Namespace{ref id, ref programs} => {
+                        self.declare(unit, id, NameKind::Namespace);
+                        for prog in programs.iter() {
+                            self.declare(unit, &prog.id, NameKind::Program);
+                        }
+                    },
+                    DefinitionKind::Comment(_) | DefinitionKind::Snippet(_) | DefinitionKind::Directive(_) => {},
+                }
+            }
+        }
+    }
+
+    // Enums nested in a declaration still put their values in the global scope
+    fn collect_nested(&mut self, unit: usize, decl: &'a Declaration) {
+        match decl.type_spec() {
+            Some(&TypeSpec::Enum(ref body)) => self.collect_enum(unit, None, body),
+            Some(&TypeSpec::Struct(ref body)) => {
+                for field in body.fields.iter() {
+                    self.collect_nested(unit, field);
+                }
+            },
+            Some(&TypeSpec::Union(ref body)) => self.collect_union(unit, body),
+            _ => {},
+        }
+    }
+
+    fn collect_union(&mut self, unit: usize, body: &'a UnionBody) {
+        self.collect_nested(unit, &body.discriminant);
+        for arm in body.cases.iter() {
+            self.collect_nested(unit, &arm.decl);
+        }
+        if let Some(ref decl) = body.default {
+            self.collect_nested(unit, decl);
+        }
+    }
+
+    fn collect_enum(&mut self, unit: usize, id: Option<&Ident>, body: &'a EnumBody) {
+        let mut previous: Option<&'a str> = None;
+        let mut names = Vec::new();
+        for variant in body.variants.iter() {
+            if self.declare(unit, &variant.id, NameKind::EnumValue) {
+                let source = match variant.value {
+                    Some(ref value) => Source::Expr(value),
+                    None => Source::Implicit{previous: previous},
+                };
+                self.items.insert(variant.id.name.clone(), Item {
+                    unit: unit,
+                    span: variant.id.span,
+                    source: source,
+                });
+            }
+            previous = Some(&variant.id.name);
+            names.push(&variant.id.name[..]);
+        }
+        if let Some(id) = id {
+            self.enums.push((id.name.clone(), names));
+        }
+    }
+
+    /// The value of a constant or enum value, evaluating it on first use.
+    /// Problems are reported against the definition itself, not its users.
+    fn value_of(&self, name: &str) -> Option<i128> {
+        let item = match self.items.get(name) {
+            Some(item) => *item,
+            None => return builtin(name),
+        };
+        let state = self.states.borrow().get(name).cloned();
+        match state {
+            Some(State::Done(val)) => return Some(val),
+            Some(State::Failed) => return None,
+            Some(State::Busy) => {
+                self.error(item.unit, item.span, format!("`{}` is defined in terms of itself", name));
+                return None;
+            },
+            None => {},
+        }
+        self.states.borrow_mut().insert(name.to_string(), State::Busy);
+
+        // A dependency that failed has already been reported
+        let blocked = Cell::new(false);
+        let lookup = |dep: &str| {
+            let val = self.value_of(dep);
+            if val.is_none() && self.items.contains_key(dep) {
+                blocked.set(true);
+            }
+            val
+        };
+        let result = match item.source {
+            Source::Expr(value) => parser::evaluate(value, &lookup),
+            Source::Implicit{previous: None} => Ok(0),
+            Source::Implicit{previous: Some(previous)} => match lookup(previous) {
+                Some(val) => parser::apply_binary(BinaryOp::Add, val, 1),
+                None => Err(format!("`{}` has no value", previous)),
+            },
+        };
+
+        let state = match result {
+            Ok(val) => State::Done(val),
+            Err(message) => {
+                if !blocked.get() {
+                    self.error(item.unit, item.span, format!("invalid value for `{}`: {}", name, message));
+                }
+                State::Failed
+            },
+        };
+        self.states.borrow_mut().insert(name.to_string(), state);
+        match state {
+            State::Done(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    fn evaluate_all(&mut self) {
+        let mut names: Vec<String> = self.items.keys().cloned().collect();
+        names.sort();
+        for name in names.iter() {
+            self.value_of(name);
+        }
+
+        let mut constants = HashMap::new();
+        let mut values = HashMap::new();
+        for (name, state) in self.states.borrow().iter() {
+            if let State::Done(val) = *state {
+                match self.names.get(name) {
+                    Some(&(_, _, NameKind::Constant)) => { constants.insert(name.clone(), val); },
+                    _ => { values.insert(name.clone(), val); },
+                }
+            }
+        }
+        self.schema.constants = constants;
+        self.schema.values = values;
+
+        for &(ref enum_name, ref names) in self.enums.iter() {
+            let mut enum_values: Vec<(String, i128)> = Vec::new();
+            for name in names.iter() {
+                let val = match self.schema.values.get(*name) {
+                    Some(val) => *val,
+                    None => continue,
+                };
+                let item = self.items[*name];
+                if val < i32::min_value() as i128 || val > i32::max_value() as i128 {
+                    self.error(item.unit, item.span, format!(
+                        "value {} of `{}` does not fit in an enum, which is an int", val, name));
+                } else if let Some(&(ref other, _)) = enum_values.iter().find(|v| v.1 == val) {
+                    self.error(item.unit, item.span, format!(
+                        "`{}` has the same value as `{}` in enum `{}`", name, other, enum_name));
+                }
+                enum_values.push((name.to_string(), val));
+            }
+            self.schema.enums.insert(enum_name.clone(), enum_values);
+        }
+    }
+
+    fn check(&self) {
+        let mut programs: Vec<(i128, &Program, usize)> = Vec::new();
+        for (unit, &(_, spec)) in self.units.iter().enumerate() {
+            for def in spec.definitions.iter() {
+                match def.kind {
+                    DefinitionKind::Typedef(Declaration{kind: DeclarationKind::Void, span, ..}) => {
+                        self.error(unit, span, "`typedef void` does not name a type".to_string());
+                    },
+                    DefinitionKind::Typedef(ref decl) => self.check_declaration(unit, decl),
+                    DefinitionKind::Struct{ref id, ref body} => self.check_struct(unit, id, body),
+                    DefinitionKind::Union{ref id, ref body} => self.check_union(unit, id, body),
+                    DefinitionKind::Program(ref prog) => {
+                        self.check_program(unit, prog, &mut programs);
+                    },
+                    DefinitionKind::Namespace{programs: ref progs, ..} => {
+                        for prog in progs.iter() {
+                            self.check_program(unit, prog, &mut programs);
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    fn check_type(&self, unit: usize, ty: &TypeSpec) {
+        match *ty {
+            TypeSpec::Primitive(Type::Quadruple, span) => {
+                self.error(unit, span, "`quadruple` is not supported, Rust has no 128-bit float".to_string());
+            },
+            TypeSpec::Primitive(..) => {},
+            TypeSpec::Named(ref id) => match self.names.get(&id.name) {
+                Some(&(_, _, NameKind::Type(_))) => {},
+                Some(_) => self.error(unit, id.span, format!("`{}` is not a type", id)),
+                None => self.error(unit, id.span, format!("unknown type `{}`", id)),
+            },
+            TypeSpec::Enum(_) => {},
+            TypeSpec::Struct(ref body) => {
+                self.check_fields(unit, "struct", &body.fields);
+            },
+            TypeSpec::Union(ref body) => {
+                self.check_union_body(unit, "union", body);
+            },
+        }
+    }
+
+    // Arguments and results go in request and response variants, which
+    // need a type name
+    fn check_procedure_type(&self, unit: usize, ty: &TypeSpec) {
+        match *ty {
+            TypeSpec::Primitive(..) | TypeSpec::Named(_) => self.check_type(unit, ty),
+            _ => self.error(unit, ty.span(), "procedure arguments and results must name a type, \
+                                              not define one".to_string()),
+        }
+    }
+
+    fn check_size(&self, unit: usize, size: &Value) {
+        match self.schema.evaluate(size) {
+            Ok(val) if val < 0 || val > u32::max_value() as i128 => {
+                self.error(unit, size.span(), format!("size {} is out of range", val));
+            },
+            Ok(_) => {},
+            Err(message) => self.error(unit, size.span(), message),
+        }
+    }
+
+    fn check_declaration(&self, unit: usize, decl: &Declaration) {
+        if let Some(ty) = decl.type_spec() {
+            self.check_type(unit, ty);
+        }
+        match decl.kind {
+            DeclarationKind::Array{ref size, ..} |
+            DeclarationKind::Opaque{ref size, ..} => self.check_size(unit, size),
+            DeclarationKind::VarArray{size: Some(ref size), ..} |
+            DeclarationKind::VarOpaque{size: Some(ref size), ..} |
+            DeclarationKind::String{size: Some(ref size), ..} => self.check_size(unit, size),
+            _ => {},
+        }
+    }
+
+    fn check_fields(&self, unit: usize, owner: &str, fields: &[Declaration]) {
+        let mut seen: Vec<&Ident> = Vec::new();
+        for field in fields.iter() {
+            if field.kind == DeclarationKind::Void {
+                self.error(unit, field.span, "struct fields cannot be `void`, only union arms can".to_string());
+            }
+            self.check_declaration(unit, field);
+            if let Some(id) = field.id() {
+                if seen.iter().any(|other| other.name == id.name) {
+                    self.error(unit, id.span, format!("field `{}` is declared more than once in `{}`",
+                                                      id, owner));
+                }
+                seen.push(id);
+            }
+        }
+    }
+
+    fn check_struct(&self, unit: usize, id: &Ident, body: &StructBody) {
+        self.check_fields(unit, &id.name, &body.fields);
+    }
+
+    fn check_union(&self, unit: usize, id: &Ident, body: &UnionBody) {
+        self.check_union_body(unit, &id.name, body);
+    }
+
+    /// What the discriminant type boils down to, looking through typedefs
+    fn discriminant(&self, ty: &TypeSpec, depth: usize) -> Option<Discriminant> {
+        match *ty {
+            TypeSpec::Primitive(Type::Int, _) => Some(Discriminant::Int),
+            TypeSpec::Primitive(Type::Uint, _) => Some(Discriminant::Uint),
+            TypeSpec::Primitive(Type::Bool, _) => Some(Discriminant::Bool),
+            TypeSpec::Named(ref id) if depth < 64 => {
+                match self.names.get(&id.name) {
+                    Some(&(_, _, NameKind::Type(TypeKind::Enum))) => {
+                        Some(Discriminant::Enum(id.name.clone()))
+                    },
+                    Some(&(_, _, NameKind::Type(TypeKind::Typedef))) => {
+                        self.typedefs.get(&id.name).and_then(|ty| self.discriminant(ty, depth + 1))
+                    },
+                    _ => None,
+                }
+            },
+            _ => None,
+        }
+    }
+
+    fn check_union_body(&self, unit: usize, owner: &str, body: &UnionBody) {
+        self.check_declaration(unit, &body.discriminant);
+        let discriminant = match body.discriminant.kind {
+            DeclarationKind::Scalar{ref ty, ..} => {
+                let discriminant = self.discriminant(ty, 0);
+                if discriminant.is_none() {
+                    // Unknown types were already reported
+                    let known = match *ty {
+                        TypeSpec::Named(ref id) => self.names.contains_key(&id.name),
+                        _ => true,
+                    };
+                    if known {
+                        self.error(unit, ty.span(), format!(
+                            "union `{}` must switch on an int, unsigned int, bool or enum", owner));
+                    }
+                }
+                discriminant
+            },
+            _ => {
+                self.error(unit, body.discriminant.span, format!(
+                    "union `{}` must switch on an int, unsigned int, bool or enum", owner));
+                None
+            },
+        };
+
+        let mut seen: Vec<(i128, &Value)> = Vec::new();
+        for arm in body.cases.iter() {
+            for label in arm.values.iter() {
+                if let Some(val) = self.check_case(unit, owner, label, discriminant.as_ref()) {
+                    if seen.iter().any(|&(other, _)| other == val) {
+                        self.error(unit, label.span(), format!(
+                            "duplicate case value {} in union `{}`", val, owner));
+                    }
+                    seen.push((val, label));
+                }
+            }
+            self.check_declaration(unit, &arm.decl);
+        }
+        if let Some(ref decl) = body.default {
+            self.check_declaration(unit, decl);
+        }
+    }
+
+    fn check_case(&self, unit: usize, owner: &str, label: &Value,
+                  discriminant: Option<&Discriminant>) -> Option<i128> {
+        let val = match self.schema.evaluate(label) {
+            Ok(val) => val,
+            Err(message) => {
+                self.error(unit, label.span(), message);
+                return None;
+            },
+        };
+        let problem = match discriminant {
+            Some(&Discriminant::Int) if val < i32::min_value() as i128 || val > i32::max_value() as i128 => {
+                Some(format!("case value {} does not fit in an int", val))
+            },
+            Some(&Discriminant::Uint) if val < 0 || val > u32::max_value() as i128 => {
+                Some(format!("case value {} does not fit in an unsigned int", val))
+            },
+            Some(&Discriminant::Bool) if val != 0 && val != 1 => {
+                Some(format!("case value {} is not a bool, expected TRUE or FALSE", val))
+            },
+            Some(&Discriminant::Enum(ref enum_name)) => {
+                let values = self.schema.enums.get(enum_name);
+                let is_value = |name: &str| values.map_or(false, |values| {
+                    values.iter().any(|v| v.0 == name)
+                });
+                match *label {
+                    // Another enum's value is a mistake even when the numbers match
+                    Value::Named(ref id) if !is_value(&id.name) &&
+                        self.names.get(&id.name).map(|n| n.2) == Some(NameKind::EnumValue) => {
+                        Some(format!("`{}` is not a value of enum `{}`", id, enum_name))
+                    },
+                    _ if values.map_or(false, |values| !values.iter().any(|v| v.1 == val)) => {
+                        Some(format!("case value {} is not a value of enum `{}`", val, enum_name))
+                    },
+                    _ => None,
+                }
+            },
+            _ => None,
+        };
+        match problem {
+            Some(message) => {
+                self.error(unit, label.span(), format!("{} in union `{}`", message, owner));
+                None
+            },
+            None => Some(val),
+        }
+    }
+
+    fn check_number(&self, unit: usize, span: Span, what: &str, number: i128) {
+        if number > u32::max_value() as i128 {
+            self.error(unit, span, format!("{} number {} does not fit in an unsigned int", what, number));
+        }
+    }
+
+    fn check_program(&self, unit: usize, prog: &'a Program, programs: &mut Vec<(i128, &'a Program, usize)>) {
+        self.check_number(unit, prog.id.span, "program", prog.number);
+        if let Some(&(_, other, _)) = programs.iter().find(|p| p.0 == prog.number) {
+            self.error(unit, prog.id.span, format!(
+                "program `{}` has the same number {} as `{}`", prog.id, prog.number, other.id));
+        }
+        programs.push((prog.number, prog, unit));
+
+        let mut versions: Vec<&Version> = Vec::new();
+        for version in prog.versions.iter() {
+            self.check_number(unit, version.id.span, "version", version.number);
+            if let Some(other) = versions.iter().find(|v| v.number == version.number) {
+                self.error(unit, version.id.span, format!(
+                    "version `{}` has the same number {} as `{}` in program `{}`",
+                    version.id, version.number, other.id, prog.id));
+            } else if versions.iter().any(|v| v.id.name == version.id.name) {
+                self.error(unit, version.id.span, format!(
+                    "version `{}` is defined more than once in program `{}`", version.id, prog.id));
+            }
+            versions.push(version);
+
+            let mut procedures: Vec<&Procedure> = Vec::new();
+            for procedure in version.procedures.iter() {
+                self.check_number(unit, procedure.id.span, "procedure", procedure.number);
+                if let Some(other) = procedures.iter().find(|p| p.number == procedure.number) {
+                    self.error(unit, procedure.id.span, format!(
+                        "procedure `{}` has the same number {} as `{}` in version `{}`",
+                        procedure.id, procedure.number, other.id, version.id));
+                } else if procedures.iter().any(|p| p.id.name == procedure.id.name) {
+                    self.error(unit, procedure.id.span, format!(
+                        "procedure `{}` is defined more than once in version `{}`",
+                        procedure.id, version.id));
+                }
+                procedures.push(procedure);
+
+                for ty in procedure.return_type.iter().chain(procedure.arg_types.iter()) {
+                    self.check_procedure_type(unit, ty);
+                }
+            }
+        }
+    }
+}
+
+/// Resolve the names used by parsed files against each other and check that
+/// the definitions make sense together. Each file comes with the preprocessed
+/// source it was parsed from, for reporting problems.
+pub fn resolve(files: &[(&Preprocessed, &Specification)]) -> Result<Schema, ParseError> {
+    let mut resolver = Resolver {
+        units: files,
+        names: HashMap::new(),
+        items: HashMap::new(),
+        states: RefCell::new(HashMap::new()),
+        enums: Vec::new(),
+        typedefs: HashMap::new(),
+        schema: Schema::default(),
+        diagnostics: RefCell::new(Vec::new()),
+    };
+    resolver.collect();
+    resolver.evaluate_all();
+    resolver.check();
+
+    let mut diagnostics = resolver.diagnostics.into_inner();
+    diagnostics.sort_by_key(|d| (d.0, d.1));
+    let diagnostics: Vec<Diagnostic> = diagnostics.into_iter().map(|d| d.2).collect();
+    if diagnostics.is_empty() {
+        Ok(resolver.schema)
+    } else {
+        Err(ParseError { diagnostics: diagnostics })
+    }
+}
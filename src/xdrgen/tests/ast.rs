@@ -0,0 +1,94 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::parse;
+use xdrgen::ast::*;
+
+#[test]
+fn definitions_in_source_order() {
+    let spec = parse("const A = 1;\ntypedef int b_t;\nenum c { X };\nstruct d { int x; };\n\
+                      union e switch (int k) { case 1: void; };\n");
+    let ids: Vec<&str> = spec.definitions.iter().map(|d| d.id().unwrap().name.as_str()).collect();
+    assert_eq!(ids, vec!["A", "b_t", "c", "d", "e"]);
+}
+
+#[test]
+fn spans_cover_the_source() {
+    let source = "struct point {\n    hyper x;\n    string name<>;\n};\n";
+    let spec = parse(source);
+    let def = &spec.definitions[0];
+    assert_eq!(&source[def.span.start..def.span.end], "struct point {\n    hyper x;\n    string name<>;\n};");
+
+    let body = match def.kind {
+        DefinitionKind::Struct{ref id, ref body} => {
+            assert_eq!(&source[id.span.start..id.span.end], "point");
+            body
+        },
+        ref kind => panic!("expected a struct, found {:?}", kind),
+    };
+    let field = &body.fields[0];
+    assert_eq!(&source[field.span.start..field.span.end], "hyper x");
+    match field.kind {
+        DeclarationKind::Scalar{ty: TypeSpec::Primitive(Type::Hyper, span), ref id} => {
+            assert_eq!(&source[span.start..span.end], "hyper");
+            assert_eq!(id.name, "x");
+        },
+        ref kind => panic!("unexpected field {:?}", kind),
+    }
+    match body.fields[1].kind {
+        DeclarationKind::String{ref id, size: None} => assert_eq!(id.name, "name"),
+        ref kind => panic!("unexpected field {:?}", kind),
+    }
+}
+
+#[test]
+fn declarations() {
+    let spec = parse("struct s { opaque a[4]; opaque b<>; int c<8>; int d[N]; s *next; void; };\n");
+    let fields = match spec.definitions[0].kind {
+        DefinitionKind::Struct{ref body, ..} => &body.fields,
+        _ => unreachable!(),
+    };
+    let kinds: Vec<&str> = fields.iter().map(|f| match f.kind {
+        DeclarationKind::Opaque{..} => "opaque",
+        DeclarationKind::VarOpaque{..} => "var opaque",
+        DeclarationKind::VarArray{size: Some(Value::Constant(8, _)), ..} => "var array",
+        DeclarationKind::Array{size: Value::Named(_), ..} => "array",
+        DeclarationKind::Pointer{ty: TypeSpec::Named(_), ..} => "pointer",
+        DeclarationKind::Void => "void",
+        _ => "other",
+    }).collect();
+    assert_eq!(kinds, vec!["opaque", "var opaque", "var array", "array", "pointer", "void"]);
+}
+
+#[test]
+fn values_fold_numbers_and_keep_names() {
+    let spec = parse("const A = 2 * 3 + 1;\nconst B = A << 1;\n");
+    match spec.definitions[0].kind {
+        DefinitionKind::Const{value: Value::Constant(7, _), ..} => {},
+        ref kind => panic!("unexpected {:?}", kind),
+    }
+    match spec.definitions[1].kind {
+        DefinitionKind::Const{value: Value::Binary{op: BinaryOp::Shl, ref lhs, ..}, ..} => {
+            assert_eq!(**lhs, Value::Named(Ident { name: "A".to_string(), span: lhs.span() }));
+        },
+        ref kind => panic!("unexpected {:?}", kind),
+    }
+}
+
+#[test]
+fn programs() {
+    let spec = parse("program P {\n  version V1 {\n    void NULL(void) = 0;\n    \
+                      int ADD(int, int) = 1;\n  } = 1;\n} = 0x20000001;\n");
+    let prog = match spec.definitions[0].kind {
+        DefinitionKind::Program(ref prog) => prog,
+        ref kind => panic!("unexpected {:?}", kind),
+    };
+    assert_eq!(prog.number, 0x20000001);
+    assert_eq!(prog.versions[0].number, 1);
+    let procs = &prog.versions[0].procedures;
+    assert_eq!(procs[0].return_type, None);
+    assert!(procs[0].arg_types.is_empty());
+    assert_eq!(procs[1].id.name, "ADD");
+    assert_eq!(procs[1].arg_types.len(), 2);
+}
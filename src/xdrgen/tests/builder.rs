@@ -0,0 +1,103 @@
+extern crate xdrgen;
+
+mod common;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+
+use common::data;
+use xdrgen::{BuildError, Builder};
+
+fn read(path: PathBuf) -> String {
+    let mut contents = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut contents)).unwrap();
+    contents
+}
+
+#[test]
+fn compile_lists_inputs_and_includes_once() {
+    let generated = Builder::new()
+        .input(data("people.x"))
+        .input(data("places.x"))
+        .cargo_metadata(false)
+        .compile()
+        .unwrap();
+    let sources: Vec<String> = generated.sources.iter()
+        .map(|source| PathBuf::from(source).file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(sources, vec!["people.x", "common.x", "places.x"]);
+    assert!(generated.prot.contains("pub struct Person {"), "{}", generated.prot);
+}
+
+#[test]
+fn generate_writes_the_three_files() {
+    let out_dir = env::temp_dir().join(format!("xdrgen-builder-test-{}", std::process::id()));
+    fs::create_dir_all(&out_dir).unwrap();
+    let generated = Builder::new()
+        .input(data("people.x"))
+        .out_dir(&out_dir)
+        .cargo_metadata(false)
+        .generate()
+        .unwrap();
+    assert_eq!(read(out_dir.join("prot.rs")), generated.prot);
+    assert_eq!(read(out_dir.join("codec.rs")), generated.codec);
+    assert_eq!(read(out_dir.join("service.rs")), generated.service);
+    fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn generate_needs_an_out_dir() {
+    env::remove_var("OUT_DIR");
+    match Builder::new().input(data("people.x")).generate() {
+        Err(BuildError::NoOutDir) => {},
+        other => panic!("expected NoOutDir, got {:?}", other),
+    }
+}
+
+#[test]
+fn parse_errors_name_the_file() {
+    let err = match Builder::new().input(data("broken.x")).compile() {
+        Err(BuildError::Parse(err)) => err,
+        other => panic!("expected a parse error, got {:?}", other),
+    };
+    assert_eq!(err.diagnostics.len(), 1);
+    assert!(err.diagnostics[0].file.ends_with("broken.x"), "{}", err.diagnostics[0].file);
+    assert_eq!(err.diagnostics[0].line, 3);
+}
+
+#[test]
+fn missing_inputs_are_io_errors() {
+    match Builder::new().input(data("missing.x")).compile() {
+        Err(BuildError::Io(e)) => assert!(e.to_string().contains("missing.x"), "{}", e),
+        other => panic!("expected an i/o error, got {:?}", other),
+    }
+}
+
+#[test]
+fn include_paths_and_defines() {
+    let mut builder = Builder::new();
+    builder.input(data("with_extra.x")).include_path(data("include")).cargo_metadata(false);
+    let generated = builder.compile().unwrap();
+    assert!(generated.prot.contains("pub struct Extra {"), "{}", generated.prot);
+    assert!(!generated.prot.contains("pub struct Holder {"), "{}", generated.prot);
+
+    let generated = builder.define("WITH_HOLDER", "1").compile().unwrap();
+    assert!(generated.prot.contains("pub struct Holder {\n    pub inner: Extra,"), "{}", generated.prot);
+
+    assert!(Builder::new().input(data("with_extra.x")).compile().is_err());
+}
+
+#[test]
+fn module_and_service_paths() {
+    let generated = Builder::new()
+        .input(data("people.x"))
+        .module_path("crate::xdr")
+        .service_path("crate::handlers")
+        .cargo_metadata(false)
+        .compile()
+        .unwrap();
+    assert!(generated.codec.contains("use crate::xdr::prot::*;"), "{}", generated.codec);
+    assert!(generated.service.contains("use crate::handlers::*;"), "{}", generated.service);
+}
@@ -0,0 +1,57 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{generate, generates, parse, prot};
+use xdrgen::codegen::doc_lines;
+
+#[test]
+fn comment_styles_become_doc_lines() {
+    // As the parser keeps them, without `/*`, `*/` and `//`
+    let comments = vec!["*\n * Colours\n *   we know\n ".to_string(),
+                        "/ with a tag".to_string(),
+                        " plain".to_string(),
+                        " ".to_string()];
+    assert_eq!(doc_lines(&comments), vec!["Colours", "we know", "with a tag", "plain"]);
+}
+
+#[test]
+fn comments_above_and_after_definitions() {
+    generates("/* Maximum name length */\nconst MAXNAME = 32; /* in bytes */\n",
+              "  /// Maximum name length\n  /// in bytes\n  pub const MAXNAME: u32 = 32;");
+    generates("// A point\nstruct point { int x; };\n",
+              "  /// A point\n  #[derive(Serialize, Deserialize, PartialEq, Debug)]\n  pub struct Point {");
+    generates("typedef int ints<>; // many\n", "  /// many\n  pub type Ints = Vec<i32>;");
+}
+
+#[test]
+fn comments_on_members() {
+    generates("struct point {\n    int x; // horizontal\n    /* vertical */\n    int y;\n};\n",
+              "    /// horizontal\n    pub x: i32,\n    /// vertical\n    pub y: i32,");
+    generates("enum color {\n    RED = 1, /* warm */\n    // cool\n    BLUE = 2\n};\n",
+              "    /// warm\n    Red = 1,\n    /// cool\n    Blue = 2,");
+    generates("union r switch (int c) {\n    /* it worked */\n    case 0:\n        int code;\n    default:\n        void; // other\n};\n",
+              "    /// it worked\n    #[serde(rename = \"0\")]\n    Case0 {");
+    generates("union r switch (int c) {\n    case 0:\n        int code;\n    default:\n        void; // other\n};\n",
+              "    /// other\n    UnionDefault_ {");
+}
+
+#[test]
+fn comments_on_programs() {
+    let source = "// The program\nprogram P {\n    /* first */\n    version V {\n        /* nothing */\n        \
+                  void NUL(void) = 0;\n    } = 1;\n} = 1;\n";
+    let (prot, _, service) = generate(source).unwrap();
+    assert!(prot.contains("  /// The program\n  #[derive(Serialize, Deserialize, PartialEq, Debug)]\n  pub enum PRequest {"),
+            "{}", prot);
+    assert!(prot.contains("    /// first\n    V1(PRequestV1),"), "{}", prot);
+    assert!(prot.contains("    /// nothing\n    Nul,"), "{}", prot);
+    assert!(service.contains("// nothing"), "{}", service);
+}
+
+#[test]
+fn free_standing_comments_stay_out() {
+    let source = "/* About the file */\n\nstruct point { int x; };\n";
+    assert!(parse(source).definitions[1].comments.is_empty());
+    let out = prot(source);
+    assert!(!out.contains("About the file"), "{}", out);
+}
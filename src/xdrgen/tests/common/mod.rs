@@ -0,0 +1,66 @@
+// Shared by the integration tests, not every test uses every helper
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use xdrgen::{ParseError, Preprocessed, Schema, Specification, parse_preprocessed, resolve};
+use xdrgen::code_writer::CodeWriter;
+use xdrgen::codegen::CodeGen;
+
+pub fn parse(source: &str) -> Specification {
+    xdrgen::parse("test.x", source, false).unwrap()
+}
+
+pub fn schema(source: &str) -> Result<Schema, ParseError> {
+    let unit = Preprocessed::verbatim("test.x", source);
+    let spec = parse_preprocessed(&unit, false)?;
+    resolve(&[(&unit, &spec)])
+}
+
+pub fn messages(err: ParseError) -> Vec<String> {
+    err.diagnostics.into_iter().map(|d| d.message).collect()
+}
+
+/// A fixture file from tests/data
+pub fn data(file: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("data").join(file)
+}
+
+/// prot.rs, codec.rs and service.rs generated for `units`, after `setup`
+/// has configured the generator
+pub fn generate_with<F>(units: &[Preprocessed], setup: F) -> Result<(String, String, String), ParseError>
+    where F: FnOnce(&mut CodeGen)
+{
+    let mut prot = Vec::new();
+    let mut codec = Vec::new();
+    let mut service = Vec::new();
+    {
+        let mut types_wr = CodeWriter::new(&mut prot);
+        let mut codec_wr = CodeWriter::new(&mut codec);
+        let mut service_wr = CodeWriter::new(&mut service);
+        let mut cg = CodeGen::new(&mut types_wr, &mut codec_wr, &mut service_wr);
+        setup(&mut cg);
+        cg.compile(units, false)?;
+    }
+    Ok((String::from_utf8(prot).unwrap(),
+        String::from_utf8(codec).unwrap(),
+        String::from_utf8(service).unwrap()))
+}
+
+/// prot.rs, codec.rs and service.rs generated for `source`
+pub fn generate(source: &str) -> Result<(String, String, String), ParseError> {
+    generate_with(&[Preprocessed::verbatim("test.x", source)], |_| {})
+}
+
+pub fn prot(source: &str) -> String {
+    generate(source).unwrap().0
+}
+
+pub fn contains(code: &str, expected: &str) {
+    assert!(code.contains(expected), "expected `{}` in\n{}", expected, code);
+}
+
+/// Assert that the prot.rs generated for `source` contains `expected`
+pub fn generates(source: &str, expected: &str) {
+    contains(&prot(source), expected);
+}
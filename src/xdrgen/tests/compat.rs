@@ -0,0 +1,77 @@
+extern crate xdrgen;
+
+use xdrgen::{Preprocessed, parse_preprocessed, resolve};
+use xdrgen::compat::compare;
+
+fn changes(old: &str, new: &str) -> Vec<String> {
+    let old_unit = Preprocessed::verbatim("old.x", old);
+    let new_unit = Preprocessed::verbatim("new.x", new);
+    let old_spec = parse_preprocessed(&old_unit, false).unwrap();
+    let new_spec = parse_preprocessed(&new_unit, false).unwrap();
+    let old_schema = resolve(&[(&old_unit, &old_spec)]).unwrap();
+    let new_schema = resolve(&[(&new_unit, &new_spec)]).unwrap();
+    compare(&old_spec, &old_schema, &new_spec, &new_schema).iter().map(|c| c.to_string()).collect()
+}
+
+#[test]
+fn renamed_type_with_the_same_encoding() {
+    let old = "struct point { int x; int y; };\nstruct line { point a; point b; };\n";
+    let new = "struct point2d { int x; int y; };\nstruct line { point2d a; point2d b; };\n";
+    assert_eq!(changes(old, new), vec!["safe: type `point` was renamed to `point2d`"]);
+}
+
+#[test]
+fn renamed_enum_and_typedef() {
+    let old = "enum color { RED = 1, GREEN = 2 };\ntypedef string name<16>;\n";
+    let new = "enum colour { RED = 1, GREEN = 2 };\ntypedef string label<16>;\n";
+    assert_eq!(changes(old, new), vec!["safe: type `color` was renamed to `colour`",
+                                       "safe: type `name` was renamed to `label`"]);
+}
+
+#[test]
+fn replaced_type_with_a_different_encoding() {
+    let old = "struct point { int x; int y; };\n";
+    let new = "struct point3d { int x; int y; int z; };\n";
+    assert_eq!(changes(old, new), vec!["breaking: type `point` was removed",
+                                       "safe: type `point3d` was added"]);
+}
+
+#[test]
+fn each_new_type_is_one_rename() {
+    let old = "struct a { int x; };\nstruct b { int x; };\n";
+    let new = "struct c { int x; };\n";
+    assert_eq!(changes(old, new), vec!["safe: type `a` was renamed to `c`",
+                                       "breaking: type `b` was removed"]);
+}
+
+#[test]
+fn enum_discriminant_to_int_with_the_same_values() {
+    let old = "enum kind { NONE = 0, SOME = 1 };\n\
+               union maybe switch (kind k) { case NONE: void; case SOME: int value; };\n";
+    let new = "enum kind { NONE = 0, SOME = 1 };\n\
+               union maybe switch (int k) { case 0: void; case 1: int value; };\n";
+    assert_eq!(changes(old, new),
+               vec!["safe: discriminant of union `maybe` changed from kind to int"]);
+    assert_eq!(changes(new, old),
+               vec!["safe: discriminant of union `maybe` changed from int to kind"]);
+}
+
+#[test]
+fn enum_discriminant_to_int_with_other_values() {
+    let old = "enum kind { NONE = 0, SOME = 1 };\n\
+               union maybe switch (kind k) { case NONE: void; case SOME: int value; };\n";
+    let new = "enum kind { NONE = 0, SOME = 1 };\n\
+               union maybe switch (int k) { case 0: void; case 2: int value; };\n";
+    assert_eq!(changes(old, new),
+               vec!["safe: discriminant of union `maybe` changed from kind to int",
+                    "breaking: arm `SOME` was removed from union `maybe`",
+                    "safe: arm `2` was added to union `maybe`"]);
+}
+
+#[test]
+fn discriminant_to_another_type_is_breaking() {
+    let old = "union maybe switch (int k) { case 0: void; case 1: int value; };\n";
+    let new = "union maybe switch (unsigned int k) { case 0: void; case 1: int value; };\n";
+    assert_eq!(changes(old, new),
+               vec!["breaking: discriminant of union `maybe` changed from int to unsigned int"]);
+}
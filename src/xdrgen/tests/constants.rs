@@ -0,0 +1,88 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{messages, prot, schema};
+
+#[test]
+fn constants_become_rust_consts() {
+    let out = prot("const MAX = 0x10;\nconst NEG = -2;\nconst BIG = 0xffffffffffffffff;\n");
+    assert!(out.contains("pub const MAX: u32 = 16;"), "{}", out);
+    assert!(out.contains("pub const NEG: i64 = -2;"), "{}", out);
+    assert!(out.contains("pub const BIG: u64 = 18446744073709551615;"), "{}", out);
+}
+
+#[test]
+fn constants_resolve_in_enums_and_case_labels() {
+    let source = "const FIRST = 4;\n\
+                  enum kind { A = FIRST, B, C = FIRST * 4 };\n\
+                  union u switch (kind k) { case B: int b; case C: void; default: void; };\n\
+                  union v switch (int n) { case FIRST: int x; };\n\
+                  typedef opaque buf[FIRST];\n";
+    let schema = schema(source).unwrap();
+    assert_eq!(schema.value("FIRST"), Some(4));
+    assert_eq!(schema.enums["kind"], vec![("A".to_string(), 4), ("B".to_string(), 5), ("C".to_string(), 16)]);
+
+    let out = prot(source);
+    assert!(out.contains("A = 4,\n") && out.contains("B = 5,\n") && out.contains("C = 16,\n"), "{}", out);
+    assert!(out.contains("#[serde(rename = \"5\")]\n    B {"), "{}", out);
+    assert!(out.contains("#[serde(rename = \"16\")]\n    C {"), "{}", out);
+    assert!(out.contains("#[serde(rename = \"4\")]\n    First {"), "{}", out);
+    assert!(out.contains("[u8; FIRST as usize]"), "{}", out);
+}
+
+#[test]
+fn constants_may_be_used_before_they_are_defined() {
+    let schema = schema("enum e { X = LATER };\nconst LATER = 3;\n").unwrap();
+    assert_eq!(schema.value("X"), Some(3));
+}
+
+#[test]
+fn unknown_constant_is_reported() {
+    let err = schema("typedef int arr[MISSING];\n").unwrap_err();
+    assert_eq!(messages(err), vec!["unknown constant `MISSING`".to_string()]);
+}
+
+#[test]
+fn evaluates_expressions() {
+    let schema = schema("const A = 3;\n\
+                         const B = -A * 2;\n\
+                         const C = (1 << 10) | 0x0f;\n\
+                         const D = ~0 & 0xff;\n\
+                         const E = 17 / 5 + 17 % 5 - -1;\n\
+                         const F = 0x10 ^ 0x11;\n\
+                         const G = C >> 2;\n").unwrap();
+    assert_eq!(schema.value("B"), Some(-6));
+    assert_eq!(schema.value("C"), Some(1039));
+    assert_eq!(schema.value("D"), Some(255));
+    assert_eq!(schema.value("E"), Some(6));
+    assert_eq!(schema.value("F"), Some(1));
+    assert_eq!(schema.value("G"), Some(259));
+}
+
+#[test]
+fn holds_full_64_bit_range() {
+    let schema = schema("const MAX = 0xffffffffffffffff;\n\
+                         const MIN = -0x7fffffffffffffff - 1;\n").unwrap();
+    assert_eq!(schema.value("MAX"), Some(u64::max_value() as i128));
+    assert_eq!(schema.value("MIN"), Some(i64::min_value() as i128));
+}
+
+#[test]
+fn reports_evaluation_errors() {
+    let err = schema("const A = 1 / 0;\n").unwrap_err();
+    assert_eq!(messages(err), vec!["division by zero in constant expression".to_string()]);
+
+    let err = schema("const A = 0xffffffffffffffff + 1;\n").unwrap_err();
+    assert_eq!(messages(err), vec!["constant expression overflows 64 bits".to_string()]);
+
+    let err = schema("const A = 1 << 64;\n").unwrap_err();
+    assert_eq!(messages(err), vec!["shift by 64 is out of range".to_string()]);
+}
+
+#[test]
+fn reports_circular_constants() {
+    let err = schema("const A = B + 1;\nconst B = A;\n").unwrap_err();
+    assert!(!err.diagnostics.is_empty());
+    assert!(err.diagnostics[0].message.contains("`A`"), "{}", err);
+}
@@ -0,0 +1,6 @@
+/* Shared by people.x and places.x */
+const MAXNAME = 16;
+
+struct name {
+    string value<MAXNAME>;
+};
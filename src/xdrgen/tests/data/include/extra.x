@@ -0,0 +1,3 @@
+struct extra {
+    int value;
+};
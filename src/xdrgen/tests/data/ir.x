@@ -0,0 +1,8 @@
+/* Max */
+const MAX = 4;
+const BIG = 0xffffffffffffffff;
+enum color { RED = 1 };
+struct s { int a[MAX]; string n<>; s *next; };
+typedef opaque blob<MAX>;
+union u switch (color c) { case RED: int x; default: void; };
+namespace n { program P { version V { int F(s, hyper) = 1; } = 1; } = 9; };
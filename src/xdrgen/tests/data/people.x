@@ -0,0 +1,6 @@
+#include "common.x"
+
+struct person {
+    name first;
+    int age;
+};
@@ -0,0 +1,6 @@
+#include "common.x"
+
+struct place {
+    name label;
+    person *owner;
+};
@@ -0,0 +1,7 @@
+#include "extra.x"
+
+#ifdef WITH_HOLDER
+struct holder {
+    extra inner;
+};
+#endif
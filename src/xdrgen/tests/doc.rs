@@ -0,0 +1,95 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{contains, messages};
+use xdrgen::{ParseError, Preprocessed};
+use xdrgen::doc::{Format, generate};
+
+const SOURCE: &'static str = "\
+/* Maximum name length */
+const MAXNAME = 32;
+
+/* A point */
+struct point {
+    int x; /* horizontal */
+    struct { int a; } inner;
+};
+
+union result switch (int c) {
+case 0:
+    point where;
+default:
+    void;
+};
+
+/* The program */
+program DOC_PROG {
+    version DOC_V1 {
+        result DOC_GET(point) = 1; /* fetch */
+    } = 1;
+} = 0x20000001;
+";
+
+fn doc(source: &str, format: Format) -> Result<String, ParseError> {
+    generate(&[Preprocessed::verbatim("test.x", source)], format)
+}
+
+#[test]
+fn markdown_sections_in_order() {
+    let out = doc(SOURCE, Format::Markdown).unwrap();
+    assert!(out.starts_with("# test.x reference\n"), "{}", out);
+    let programs = out.find("## Programs").unwrap();
+    let types = out.find("## Types").unwrap();
+    let constants = out.find("## Constants").unwrap();
+    assert!(programs < types && types < constants, "{}", out);
+}
+
+#[test]
+fn markdown_programs_link_their_types() {
+    let out = doc(SOURCE, Format::Markdown).unwrap();
+    contains(&out, "<a id=\"program-DOC_PROG\"></a>\n### Program `DOC_PROG` = 536870913 (0x20000001)\n");
+    contains(&out, "\nThe program\n");
+    contains(&out, "| `DOC_GET` | 1 | [`point`](#type-point) | [`result`](#type-result) | fetch |");
+}
+
+#[test]
+fn markdown_types_and_constants() {
+    let out = doc(SOURCE, Format::Markdown).unwrap();
+    contains(&out, "<a id=\"type-point\"></a>\n### struct `point`\n\nDefined in `test.x`.\n\nA point\n");
+    contains(&out, "| `x` | `int` | horizontal |");
+    contains(&out, "| `inner` | [`struct {...}`](#type-point.inner) |  |");
+    contains(&out, "#### struct `point.inner`\n\nDeclared in [`point`](#type-point).\n");
+    contains(&out, "| `0` | `where` | [`point`](#type-point) |  |");
+    contains(&out, "| default | `void` | `void` |  |");
+    contains(&out, "| <a id=\"const-MAXNAME\"></a>`MAXNAME` | 32 | Maximum name length |");
+}
+
+#[test]
+fn markdown_escapes_comments() {
+    let out = doc("/* x | y <b> & `c` */\nstruct s { int a; };\n", Format::Markdown).unwrap();
+    contains(&out, "x \\| y \\<b\\> & \\`c\\`");
+}
+
+#[test]
+fn html_page() {
+    let out = doc(SOURCE, Format::Html).unwrap();
+    assert!(out.starts_with("<!DOCTYPE html>\n"), "{}", out);
+    assert!(out.ends_with("</body>\n</html>\n"), "{}", out);
+    contains(&out, "<title>test.x reference</title>");
+    contains(&out, "<h3 id=\"type-point\">struct <code>point</code></h3>");
+    contains(&out, "<td><a href=\"#type-point\"><code>point</code></a></td>");
+}
+
+#[test]
+fn html_escapes_comments_and_types() {
+    let out = doc("/* x | y <b> & `c` */\nstruct s { string a<>; };\n", Format::Html).unwrap();
+    contains(&out, "<p>x | y &lt;b&gt; &amp; `c`</p>");
+    contains(&out, "<td><code>string&lt;&gt;</code></td>");
+}
+
+#[test]
+fn invalid_schemas_are_errors() {
+    let err = doc("struct s { missing m; };\n", Format::Markdown).unwrap_err();
+    assert_eq!(messages(err), vec!["unknown type `missing`"]);
+}
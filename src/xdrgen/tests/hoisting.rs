@@ -0,0 +1,58 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{generates, prot};
+
+#[test]
+fn nested_struct_field() {
+    let source = "struct outer { struct { int a; int b; } pair; };\n";
+    generates(source, "pub pair: OuterPair,");
+    generates(source, "pub struct OuterPair {\n    pub a: i32,\n    pub b: i32,\n  }");
+}
+
+#[test]
+fn nested_union_and_enum_fields() {
+    let source = "struct outer {\n\
+                      union switch (int k) { case 0: int v; default: void; } choice;\n\
+                      enum { LOW = 1, HIGH = 2 } level;\n\
+                  };\n";
+    generates(source, "pub choice: OuterChoice,");
+    generates(source, "pub enum OuterChoice {");
+    generates(source, "pub level: OuterLevel,");
+    generates(source, "xdr_enum!(OuterLevel {\n    Low = 1,\n    High = 2,\n  });");
+}
+
+#[test]
+fn nested_types_behind_pointers_and_arrays() {
+    let source = "struct outer { struct { int z; } *maybe; struct { int c; } many<>; };\n";
+    generates(source, "pub maybe: Option<OuterMaybe>,");
+    generates(source, "pub many: Vec<OuterMany>,");
+    generates(source, "pub struct OuterMaybe {");
+    generates(source, "pub struct OuterMany {");
+}
+
+#[test]
+fn nested_in_union_arms() {
+    let source = "union top switch (int k) { case 1: struct { int q; } arm; default: void; };\n";
+    generates(source, "arm: TopArm,");
+    generates(source, "pub struct TopArm {\n    pub q: i32,\n  }");
+}
+
+#[test]
+fn nested_twice() {
+    let source = "struct outer {\n\
+                      union switch (int k) { case 0: struct { hyper h; } big; default: void; } choice;\n\
+                  };\n";
+    generates(source, "big: OuterChoiceBig,");
+    generates(source, "pub struct OuterChoiceBig {\n    pub h: i64,\n  }");
+}
+
+#[test]
+fn one_type_for_an_arm_with_several_labels() {
+    let source = "union u switch (int k) { case 1: case 2: struct { int a; } both; default: void; };\n";
+    let out = prot(source);
+    assert_eq!(out.matches("pub struct UBoth {").count(), 1, "{}", out);
+    assert!(!out.contains("UBoth2"), "{}", out);
+    assert_eq!(out.matches("both: UBoth,").count(), 2, "{}", out);
+}
@@ -0,0 +1,44 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::messages;
+use xdrgen::{ParseError, Preprocessed};
+use xdrgen::ir::emit;
+
+fn ir(source: &str) -> Result<String, ParseError> {
+    emit(&[Preprocessed::verbatim("test.x", source)])
+}
+
+#[test]
+fn every_kind_of_definition() {
+    let expected = include_str!("data/ir.json");
+    assert_eq!(ir(include_str!("data/ir.x")).unwrap(), expected);
+}
+
+#[test]
+fn strings_are_escaped() {
+    let out = ir("/* say \"hi\"\\\n   then\tleave */\nconst A = 1;\n").unwrap();
+    assert!(out.contains("\"doc\": \"say \\\"hi\\\"\\\\\\nthen\\tleave\""), "{}", out);
+}
+
+#[test]
+fn values_keep_their_sign_and_range() {
+    let out = ir("const NEG = -5;\nconst MIN = -0x8000000000000000;\nenum e { BELOW = -1 };\n").unwrap();
+    assert!(out.contains("\"value\": -5,"), "{}", out);
+    assert!(out.contains("\"value\": -9223372036854775808,"), "{}", out);
+    assert!(out.contains("\"value\": -1,"), "{}", out);
+}
+
+#[test]
+fn inline_types_are_nested() {
+    let out = ir("struct s { struct { int a; } inner; };\n").unwrap();
+    assert!(out.contains("\"name\": \"inner\",\n          \"type\": {\n            \"kind\": \"struct\",\n            \"fields\": ["),
+            "{}", out);
+}
+
+#[test]
+fn invalid_schemas_are_errors() {
+    let err = ir("struct s { missing m; };\n").unwrap_err();
+    assert_eq!(messages(err), vec!["unknown type `missing`"]);
+}
@@ -0,0 +1,54 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{contains, data, generate_with};
+use xdrgen::{Builder, Preprocessed};
+
+#[test]
+fn module_per_file_with_includes_once() {
+    let generated = Builder::new()
+        .input(data("people.x"))
+        .input(data("places.x"))
+        .cargo_metadata(false)
+        .compile()
+        .unwrap();
+    let prot = &generated.prot;
+    contains(prot, "pub use self::common::*;\npub use self::people::*;\npub use self::places::*;\n");
+    assert_eq!(prot.matches("pub mod common {").count(), 1);
+    assert_eq!(prot.matches("pub struct Name {").count(), 1);
+    contains(prot, "/// Generated from `people.x`\npub mod people {");
+    contains(prot, "/// Generated from `places.x`\npub mod places {");
+}
+
+#[test]
+fn modules_use_the_files_they_refer_to() {
+    let generated = Builder::new()
+        .input(data("people.x"))
+        .input(data("places.x"))
+        .cargo_metadata(false)
+        .compile()
+        .unwrap();
+    let prot = &generated.prot;
+    let people = &prot[prot.find("pub mod people").unwrap()..prot.find("pub mod places").unwrap()];
+    let places = &prot[prot.find("pub mod places").unwrap()..];
+    contains(people, "  use super::common::*;\n");
+    assert!(!people.contains("use super::places::*;"), "{}", people);
+    // places.x doesn't include people.x, the inputs still see each other
+    contains(places, "  use super::common::*;\n  use super::people::*;\n");
+    contains(places, "pub owner: Option<Person>,");
+}
+
+#[test]
+fn module_names_are_rust_identifiers() {
+    let units = [Preprocessed::verbatim("a/common.x", "struct a { int x; };\n"),
+                 Preprocessed::verbatim("b/common.x", "struct b { int x; };\n"),
+                 Preprocessed::verbatim("my-proto.x", "struct c { int x; };\n"),
+                 Preprocessed::verbatim("type.x", "struct d { int x; };\n"),
+                 Preprocessed::verbatim("9lives.x", "struct e { int x; };\n")];
+    let prot = generate_with(&units, |_| {}).unwrap().0;
+    for module in ["common", "common2", "my_proto", "xdr_type", "xdr_9lives"].iter() {
+        contains(&prot, &format!("pub use self::{}::*;", module));
+        contains(&prot, &format!("pub mod {} {{", module));
+    }
+}
@@ -0,0 +1,63 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{contains, generate};
+
+const SOURCE: &'static str = "\
+struct point { int x; int y; };
+
+/* Geometry services */
+namespace geo {
+    program GEO_PROG {
+        version GEO_V1 {
+            point GEO_ECHO(point) = 1;
+        } = 1;
+    } = 0x20000010;
+};
+
+namespace store {
+    program STORE_PROG {
+        version STORE_V1 {
+            void STORE_PUT(point) = 1;
+        } = 1;
+    } = 0x20000011;
+};
+";
+
+#[test]
+fn programs_live_in_a_module_per_namespace() {
+    let (prot, _, _) = generate(SOURCE).unwrap();
+    contains(&prot, "  /// Geometry services\n  pub mod geo {\n    use super::*;\n");
+    contains(&prot, "  pub mod store {\n    use super::*;\n");
+    contains(&prot, "    pub enum GeoProgRequest {");
+    contains(&prot, "    pub fn store_prog_decode(");
+    // Types outside a namespace stay in the file's module
+    contains(&prot, "  pub struct Point {");
+    assert!(prot.find("pub struct Point").unwrap() < prot.find("pub mod geo").unwrap());
+}
+
+#[test]
+fn codec_and_service_import_their_namespace() {
+    let (_, codec, service) = generate(SOURCE).unwrap();
+    for code in [&codec, &service].iter() {
+        contains(code, "/// Geometry services\npub mod geo {\n  use super::*;\n  use xdrgen::prot::geo::*;\n");
+        contains(code, "pub mod store {\n  use super::*;\n  use xdrgen::prot::store::*;\n");
+    }
+    contains(&codec, "  pub struct GeoAppCodec;");
+    contains(&service, "  impl Service for StoreProgService {");
+}
+
+#[test]
+fn namespace_of_several_programs() {
+    let source = "namespace both {\n\
+                      program A_PROG { version A_V1 { void A_NULL(void) = 0; } = 1; } = 1;\n\
+                      program B_PROG { version B_V1 { void B_NULL(void) = 0; } = 1; } = 2;\n\
+                  };\n";
+    let (prot, codec, _) = generate(source).unwrap();
+    contains(&prot, "  pub mod both {");
+    contains(&prot, "    pub enum AProgRequest {");
+    contains(&prot, "    pub enum BProgRequest {");
+    contains(&codec, "  pub struct AAppCodec;");
+    contains(&codec, "  pub struct BAppCodec;");
+}
@@ -0,0 +1,53 @@
+extern crate xdrgen;
+
+use xdrgen::parse;
+
+#[test]
+fn reports_file_line_and_column() {
+    let source = "const A = 1;\n\nstruct s {\n    int a\n    int b;\n};\n";
+    let err = parse("test.x", source, false).unwrap_err();
+    let diagnostic = &err.diagnostics[0];
+    assert_eq!(diagnostic.file, "test.x");
+    assert_eq!(diagnostic.line, 5);
+    assert_eq!(diagnostic.column, 5);
+    assert_eq!(diagnostic.snippet, "    int b;");
+}
+
+#[test]
+fn reports_every_error() {
+    let source = "struct a { int };\nstruct b { int x; };\nenum c { X = };\n";
+    let err = parse("test.x", source, false).unwrap_err();
+    let lines: Vec<usize> = err.diagnostics.iter().map(|d| d.line).collect();
+    assert_eq!(lines, vec![1, 3]);
+}
+
+#[test]
+fn display_points_at_the_column() {
+    let err = parse("test.x", "typedef int;\n", false).unwrap_err();
+    let text = err.to_string();
+    assert!(text.starts_with("error: "), "{}", text);
+    assert!(text.contains("--> test.x:1:12"), "{}", text);
+    assert!(text.ends_with("1 | typedef int;\n  |            ^"), "{}", text);
+}
+
+#[test]
+fn reports_unterminated_input() {
+    let err = parse("test.x", "struct s {\n    int a;\n", false).unwrap_err();
+    assert_eq!(err.diagnostics.len(), 1);
+    assert_eq!(err.diagnostics[0].line, 3);
+}
+
+#[test]
+fn doc_comment_before_the_first_definition() {
+    let spec = parse("test.x", "/* The first one */\nconst A = 1;\n", false).unwrap();
+    assert_eq!(spec.definitions.len(), 1);
+    assert_eq!(spec.definitions[0].comments, vec![" The first one ".to_string()]);
+}
+
+#[test]
+fn doc_comment_after_a_broken_first_definition() {
+    let source = "struct a { int }\n/* Skipped while recovering */\nconst B = 2;\nconst C = ;\n";
+    let err = parse("test.x", source, false).unwrap_err();
+    let lines: Vec<usize> = err.diagnostics.iter().map(|d| d.line).collect();
+    assert_eq!(lines, vec![1, 4]);
+}
@@ -0,0 +1,77 @@
+extern crate xdrgen;
+
+use xdrgen::Preprocessor;
+
+fn preprocess(source: &str) -> String {
+    let mut preprocessor = Preprocessor::new();
+    preprocessor.define("VERSION", "3");
+    preprocessor.preprocess("test.x", source).unwrap().source
+}
+
+fn kept(condition: &str) -> bool {
+    let source = format!("#if {}\nyes\n#endif\n", condition);
+    preprocess(&source).contains("yes")
+}
+
+#[test]
+fn expands_defines() {
+    let out = preprocess("#define SIZE (VERSION * 2)\nopaque data[SIZE];\n");
+    assert_eq!(out.lines().nth(1), Some("opaque data[(3 * 2)];"));
+}
+
+#[test]
+fn keeps_non_ascii_text() {
+    let out = preprocess("/* größe */ const MAX = VERSION; // ünïcode\nstruct s { int é; };\n");
+    assert_eq!(out.lines().next(), Some("/* größe */ const MAX = 3; // ünïcode"));
+    assert_eq!(out.lines().nth(1), Some("struct s { int é; };"));
+
+    let out = preprocess("/* ø\nVERSION ø */ VERSION ø\n");
+    assert_eq!(out.lines().nth(1), Some("VERSION ø */ 3 ø"));
+}
+
+#[test]
+fn if_comparisons() {
+    assert!(kept("VERSION >= 2"));
+    assert!(!kept("VERSION < 2"));
+    assert!(kept("VERSION == 3"));
+    assert!(kept("VERSION != 1 && defined(VERSION)"));
+    assert!(kept("UNDEFINED == 0"));
+    assert!(kept("!defined UNDEFINED || VERSION > 9"));
+}
+
+#[test]
+fn if_arithmetic_follows_c_precedence() {
+    assert!(kept("1 + 2 * 3 == 7"));
+    assert!(kept("(1 + 2) * 3 == 9"));
+    assert!(kept("1 << 4 == 16"));
+    assert!(kept("0x10 - 020 == 0"));
+    assert!(kept("-VERSION < 0"));
+    assert!(kept("(VERSION & 1) && ~0 == -1"));
+    assert!(!kept("VERSION % 3"));
+}
+
+#[test]
+fn if_errors_are_reported() {
+    let mut preprocessor = Preprocessor::new();
+    for &(condition, message) in [
+        ("1 / 0", "division by zero in `#if 1 / 0`"),
+        ("VERSION >", "unexpected end of expression in `#if VERSION >`"),
+        ("1 ? 2 : 3", "unexpected `?` in `#if 1 ? 2 : 3`"),
+        ("(1", "expected `)` in `#if (1`"),
+    ].iter() {
+        preprocessor.define("VERSION", "1");
+        let source = format!("#if {}\n#endif\n", condition);
+        let err = preprocessor.preprocess("test.x", &source).unwrap_err();
+        assert_eq!(err.diagnostics[0].message, message);
+        assert_eq!(err.diagnostics[0].line, 1);
+    }
+}
+
+#[test]
+fn elif_and_else() {
+    let source = "#if VERSION == 1\none\n#elif VERSION == 3\nthree\n#else\nother\n#endif\n";
+    let out = preprocess(source);
+    assert!(out.contains("three"));
+    assert!(!out.contains("one"));
+    assert!(!out.contains("other"));
+}
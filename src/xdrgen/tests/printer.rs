@@ -0,0 +1,92 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{generate, messages};
+use xdrgen::{Preprocessed, format, ir};
+
+// Every kind of definition, laid out badly
+const MESSY: &'static str = "\
+const A=0x10;
+const B = (1<<4)|2 ;
+const C = A*-2+(B - 1) ;
+typedef unsigned   int u;   // an alias
+typedef int ints<A * 2>;
+  /*
+   * Doc for s,
+   *   indented.
+   */
+struct s {
+        /* a field */
+        int a[4];
+    string name<>; /* trailing */
+
+    opaque blob<B+1>;
+    opaque digest[16];
+    s *next;
+    struct { int x; int y; } inline_pair;
+};
+union u2 switch (unsigned int k) {
+case 1: case 2: int x;
+ /* default doc */
+default: void;
+};
+enum e { X = 1, /* x */ Y = 2 /* y */ };
+typedef enum { ON = 1, OFF = 0 } toggle;
+%#[rust] impl S {}
+program P { version V {
+/* ping */
+void NUL(void) = 0; s GET(int, e) = 1; } = 1; } = 0x20000001;
+namespace n {
+  program Q { version W { int ECHO(int) = 1; } = 2; } = 0x20000002;
+};
+";
+
+fn formatted(source: &str) -> String {
+    format("test.x", source).unwrap()
+}
+
+fn ir_of(source: &str) -> String {
+    ir::emit(&[Preprocessed::verbatim("test.x", source)]).unwrap()
+}
+
+#[test]
+fn round_trip_keeps_the_meaning() {
+    let printed = formatted(MESSY);
+    assert_eq!(ir_of(&printed), ir_of(MESSY));
+    assert_eq!(generate(&printed).unwrap(), generate(MESSY).unwrap());
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let once = formatted(MESSY);
+    assert_eq!(formatted(&once), once);
+}
+
+#[test]
+fn formatted_rpc_protocol_is_idempotent() {
+    let source = include_str!("../../test/rpc.x");
+    let once = formatted(source);
+    assert_eq!(ir_of(&once), ir_of(source));
+    assert_eq!(formatted(&once), once);
+}
+
+#[test]
+fn canonical_layout() {
+    let once = formatted("struct  p{int x;int y;};const N=1;\n");
+    assert_eq!(once, "struct p {\n    int x;\n    int y;\n};\n\nconst N = 1;\n");
+}
+
+#[test]
+fn directives_inside_definitions_are_refused() {
+    let source = "struct s {\n    int a;\n#ifdef WITH_B\n    int b;\n#endif\n};\n";
+    let err = format("test.x", source).unwrap_err();
+    assert_eq!(messages(err), vec!["directives inside definitions are not supported"]);
+}
+
+#[test]
+fn directives_between_definitions_are_kept() {
+    let once = formatted("#ifdef WITH_S\nstruct s {\n    int a;\n};\n#endif\n");
+    assert_eq!(once, "#ifdef WITH_S\n\nstruct s {\n    int a;\n};\n\n#endif\n");
+    assert_eq!(formatted(&once), once);
+}
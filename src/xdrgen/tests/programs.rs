@@ -0,0 +1,86 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::generate;
+
+fn program(name: &str) -> String {
+    format!("program {} {{\n    version V1 {{\n        int PING(int) = 1;\n    }} = 1;\n}} = 100;\n", name)
+}
+
+fn codec_calls_prot(name: &str, decoder: &str, encoder: &str) {
+    let (prot, codec, _) = generate(&program(name)).unwrap();
+    assert!(prot.contains(&format!("pub fn {}(program: u32", decoder)), "{}", prot);
+    assert!(prot.contains(&format!("pub fn {}(", encoder)), "{}", prot);
+    assert!(codec.contains(&format!("{}(prog, version, procedure, buf)", decoder)), "{}", codec);
+    assert!(codec.contains(&format!("{}(msg, buf)", encoder)), "{}", codec);
+}
+
+#[test]
+fn codec_calls_the_program_decoder() {
+    codec_calls_prot("NFS_PROG", "nfs_prog_decode", "nfs_prog_encode");
+    codec_calls_prot("NFS_PROGRAM", "nfs_program_decode", "nfs_program_encode");
+    codec_calls_prot("MOUNT", "mount_decode", "mount_encode");
+}
+
+#[test]
+fn codec_type_names() {
+    let (_, codec, _) = generate(&program("NFS_PROG")).unwrap();
+    assert!(codec.contains("pub struct NfsAppCodec;"), "{}", codec);
+    assert!(codec.contains("pub type NfsCodec = XdrCodec<NfsAppCodec>;"), "{}", codec);
+    assert!(codec.contains("type In =   NfsProgRequest;"), "{}", codec);
+
+    let (prot, codec, _) = generate(&program("NFS_PROGRAM")).unwrap();
+    assert!(codec.contains("pub struct NfsProgramAppCodec;"), "{}", codec);
+    assert!(codec.contains("type In =   NfsProgramRequest;"), "{}", codec);
+    assert!(codec.contains("type Out =   NfsProgramResponse;"), "{}", codec);
+    assert!(prot.contains("pub enum NfsProgramRequest {"), "{}", prot);
+}
+
+#[test]
+fn generated_code_builds_on_any_edition() {
+    let (prot, codec, service) = generate(&program("NFS_PROG")).unwrap();
+    for code in [&prot, &codec, &service].iter() {
+        assert!(!code.contains("try!"), "{}", code);
+    }
+    assert!(prot.contains("serde_xdr::to_bytes(&r, buf)?;"), "{}", prot);
+}
+
+fn procedure(signature: &str) -> Result<String, Vec<String>> {
+    let source = format!("struct point {{ int x; int y; }};\n\
+                          program M_PROG {{ version M_V1 {{ {} = 1; }} = 1; }} = 1;\n", signature);
+    generate(&source)
+        .map(|(prot, _, _)| prot)
+        .map_err(|e| e.diagnostics.into_iter().map(|d| d.message).collect())
+}
+
+#[test]
+fn several_arguments() {
+    let prot = procedure("point M_MOVE(point, hyper, unsigned int)").unwrap();
+    assert!(prot.contains("    MMove(Point, i64, u32),"), "{}", prot);
+    let decoder = &prot[prot.find("pub fn m_prog_decode_v1_m_move").unwrap()..];
+    let order: Vec<usize> = ["from_bytes::<Point>", "from_bytes::<i64>", "from_bytes::<u32>"].iter()
+        .map(|call| decoder.find(call).unwrap())
+        .collect();
+    assert!(order[0] < order[1] && order[1] < order[2], "{}", decoder);
+    assert!(decoder.contains("Ok(Some(MProgRequestV1::MMove(arg0, arg1, arg2)))"), "{}", decoder);
+}
+
+#[test]
+fn void_arguments() {
+    let prot = procedure("void M_NOTHING(void)").unwrap();
+    assert!(prot.contains("    MNothing,"), "{}", prot);
+    assert!(prot.contains("Ok(Some(MProgRequestV1::MNothing))"), "{}", prot);
+}
+
+#[test]
+fn void_must_stand_alone() {
+    assert_eq!(procedure("void M_BAD(int, void)").unwrap_err(),
+               vec!["`void` must be the only argument"]);
+}
+
+#[test]
+fn arguments_must_name_a_type() {
+    assert_eq!(procedure("void M_BAD(int, struct { int a; })").unwrap_err(),
+               vec!["procedure arguments and results must name a type, not define one"]);
+}
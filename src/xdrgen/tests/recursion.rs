@@ -0,0 +1,40 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::generates;
+
+#[test]
+fn optional_data_is_boxed() {
+    generates("struct node { int value; node *next; };\n",
+              "pub next: Option<Box<Node>>,");
+}
+
+#[test]
+fn cycle_through_union_arm() {
+    let source = "union expr switch (int op) { case 0: int lit; case 1: binop b; };\n\
+                  struct binop { expr lhs; expr rhs; };\n";
+    generates(source, "b: Binop,");
+    generates(source, "pub lhs: Box<Expr>,\n    pub rhs: Box<Expr>,");
+}
+
+#[test]
+fn cycle_through_struct_fields() {
+    let source = "struct a { b inner; };\nstruct b { int x; a *more; c tail; };\nstruct c { a back; };\n";
+    generates(source, "pub inner: B,");
+    generates(source, "pub tail: C,");
+    generates(source, "pub back: Box<A>,");
+}
+
+#[test]
+fn cycle_through_fixed_array() {
+    generates("struct a { b items[2]; };\nstruct b { a owner; };\n",
+              "pub owner: Box<A>,");
+    generates("struct b { a owner; };\nstruct a { b items[2]; };\n",
+              "pub items: [Box<B>; 2],");
+}
+
+#[test]
+fn variable_arrays_break_cycles() {
+    generates("struct tree { tree children<>; };\n", "pub children: Vec<Tree>,");
+}
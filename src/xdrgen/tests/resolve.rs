@@ -0,0 +1,70 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{generate, messages, prot, schema};
+use xdrgen::resolve::TypeKind;
+
+fn errors(source: &str) -> Vec<String> {
+    messages(schema(source).unwrap_err())
+}
+
+#[test]
+fn types_may_be_used_before_they_are_defined() {
+    let schema = schema("struct a { b inner; };\nstruct b { int x; };\ntypedef a c;\n").unwrap();
+    assert_eq!(schema.types["a"], TypeKind::Struct);
+    assert_eq!(schema.types["c"], TypeKind::Typedef);
+}
+
+#[test]
+fn reports_undefined_and_duplicate_names() {
+    assert_eq!(errors("struct a { missing m; };\n"), vec!["unknown type `missing`"]);
+    let dup = errors("struct a { int x; int x; };\n");
+    assert_eq!(dup, vec!["field `x` is declared more than once in `a`"]);
+    assert_eq!(errors("const A = 1;\ntypedef A b;\n"), vec!["`A` is not a type"]);
+}
+
+#[test]
+fn reports_invalid_union_cases() {
+    let source = "enum e { X = 1, Y = 2 };\nenum f { Z = 3 };\n\
+                  union u switch (e d) { case X: int a; case Z: void; };\n";
+    assert_eq!(errors(source), vec!["`Z` is not a value of enum `e` in union `u`"]);
+
+    let source = "union u switch (int d) { case 1: int a; case 1: void; };\n";
+    assert_eq!(errors(source), vec!["duplicate case value 1 in union `u`"]);
+
+    let source = "union u switch (bool d) { case 2: int a; };\n";
+    assert_eq!(errors(source), vec!["case value 2 is not a bool, expected TRUE or FALSE in union `u`"]);
+}
+
+#[test]
+fn rejects_void_outside_union_arms() {
+    assert_eq!(errors("typedef void;\n"), vec!["`typedef void` does not name a type"]);
+    assert_eq!(errors("struct s { void; int a; };\n"),
+               vec!["struct fields cannot be `void`, only union arms can"]);
+    assert_eq!(errors("union u switch (int d) { case 1: struct { void; } s; };\n"),
+               vec!["struct fields cannot be `void`, only union arms can"]);
+
+    let err = generate("struct s { int a;\n  void; };\n").unwrap_err();
+    assert_eq!(err.diagnostics[0].line, 2);
+    assert_eq!(err.diagnostics[0].column, 3);
+}
+
+#[test]
+fn rejects_quadruple() {
+    let message = "`quadruple` is not supported, Rust has no 128-bit float";
+    assert_eq!(errors("typedef quadruple q;\n"), vec![message]);
+    assert_eq!(errors("struct s { quadruple q<>; };\n"), vec![message]);
+}
+
+#[test]
+fn inline_types_are_hoisted_from_any_declaration() {
+    let out = prot("struct t {\n  struct { int a; } *p;\n  \
+                    union switch (int k) { case 1: int v; } arr<>;\n  enum { A = 1 } fixed[2];\n};\n");
+    assert!(out.contains("pub p: Option<TP>,"), "{}", out);
+    assert!(out.contains("pub arr: Vec<TArr>,"), "{}", out);
+    assert!(out.contains("pub fixed: [TFixed; 2],"), "{}", out);
+    assert!(out.contains("pub struct TP {"), "{}", out);
+    assert!(out.contains("pub enum TArr {"), "{}", out);
+    assert!(out.contains("xdr_enum!(TFixed {"), "{}", out);
+}
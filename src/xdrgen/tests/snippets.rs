@@ -0,0 +1,41 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::{contains, generate_with, prot};
+use xdrgen::Preprocessed;
+
+const SOURCE: &'static str = "\
+%#include <stdio.h>
+%#[rust] use std::collections::HashMap;
+struct point { int x; int y; };
+%#[rust] impl Point {
+%#[rust]     pub fn origin() -> Point { Point { x: 0, y: 0 } }
+%#[rust] }
+% /* C only */
+%//rs const ORIGIN: i32 = 0;
+";
+
+#[test]
+fn rust_lines_are_copied_in_place() {
+    let out = prot(SOURCE);
+    contains(&out, "  use std::collections::HashMap;\n");
+    contains(&out, "  }\n  impl Point {\n      pub fn origin() -> Point { Point { x: 0, y: 0 } }\n  }\n");
+    assert!(out.find("HashMap").unwrap() < out.find("pub struct Point").unwrap());
+}
+
+#[test]
+fn other_lines_are_left_out() {
+    let out = prot(SOURCE);
+    assert!(!out.contains("stdio"), "{}", out);
+    assert!(!out.contains("C only"), "{}", out);
+    assert!(!out.contains("ORIGIN"), "{}", out);
+}
+
+#[test]
+fn custom_prefix() {
+    let unit = Preprocessed::verbatim("test.x", SOURCE);
+    let out = generate_with(&[unit], |cg| cg.snippet_prefix("//rs")).unwrap().0;
+    contains(&out, "  const ORIGIN: i32 = 0;\n");
+    assert!(!out.contains("HashMap"), "{}", out);
+}
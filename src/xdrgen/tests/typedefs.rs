@@ -0,0 +1,38 @@
+extern crate xdrgen;
+
+mod common;
+
+use common::generates;
+
+#[test]
+fn opaque_typedefs() {
+    generates("const N = 4;\ntypedef opaque fixed_t[N];\n",
+              "pub struct Fixed(#[serde(with = \"serde_xdr::opaque::fixed\")] pub [u8; N as usize]);");
+    generates("typedef opaque var_t<>;\n",
+              "pub struct Var(#[serde(with = \"serde_xdr::opaque\")] pub Vec<u8>);");
+    generates("typedef opaque bounded_t<16>;\n",
+              "pub struct Bounded(#[serde(with = \"serde_xdr::opaque\")] pub Vec<u8>);");
+}
+
+#[test]
+fn array_typedefs() {
+    generates("typedef int ints_t[3];\n",
+              "pub struct Ints(#[serde(with = \"serde_xdr::array\")] pub [i32; 3]);");
+    generates("typedef unsigned hyper many_t<4>;\n", "pub type Many = Vec<u64>;");
+    generates("typedef string name_t<255>;\n", "pub type Name = String;");
+}
+
+#[test]
+fn pointer_typedefs() {
+    generates("typedef int *maybe_t;\n", "pub type Maybe = Option<i32>;");
+}
+
+#[test]
+fn inline_type_typedefs() {
+    generates("typedef struct { int a; int b; } pair_t;\n",
+              "pub struct Pair {\n    pub a: i32,\n    pub b: i32,\n  }");
+    generates("typedef union switch (int ok) { case 1: int v; default: void; } outcome_t;\n",
+              "pub enum Outcome {\n    #[serde(rename = \"1\")]\n    Case1 {\n      v: i32,\n    },");
+    generates("typedef enum { ON = 1, OFF = 2 } switch_t;\n",
+              "xdr_enum!(Switch {\n    On = 1,\n    Off = 2,\n  });");
+}
@@ -0,0 +1,162 @@
+//! `xdr_include!`, which generates the types, codec and service of a .x
+//! file where it is invoked, instead of from a build script.
+extern crate proc_macro;
+extern crate xdrgen;
+
+use std::env;
+use std::path::PathBuf;
+
+use proc_macro::{Group, Span, TokenStream, TokenTree};
+use xdrgen::{BuildError, Builder};
+
+const USAGE: &'static str = "expected `xdr_include!(\"file.x\")`, optionally followed by \
+                             `include_path = \"dir\"`, `service = \"path\"`, \
+                             `snippet_prefix = \"prefix\"` or `opaque_bytes`";
+
+/// Expand to `prot`, `codec` and `service` modules generated from a .x file:
+///
+/// ```ignore
+/// mod exampledbd {
+///     xdr_include!("proto/exampledbd.x", include_path = "proto/include");
+///
+///     pub struct ExampledbdProgService;
+///     // impl ExampledbdProgService { ... }
+/// }
+/// ```
+///
+/// Paths are relative to the crate's Cargo.toml. service.rs calls the
+/// `<Program>Service` structs of the invoking module, `service = "path"`
+/// looks for them elsewhere. Problems in the .x files are compile errors
+/// naming the file, line and column.
+#[proc_macro]
+pub fn xdr_include(input: TokenStream) -> TokenStream {
+    // Errors point at the .x file's path in the invocation
+    let span = input.clone().into_iter().next().map_or(Span::call_site(), |token| token.span());
+    let messages = match expand(input) {
+        Ok(code) => match code.parse() {
+            Ok(tokens) => return tokens,
+            Err(e) => vec![format!("generated code does not parse: {}", e)],
+        },
+        Err(messages) => messages,
+    };
+    messages.iter()
+        .map(|message| {
+            let error = format!("compile_error!({:?});", message).parse().unwrap();
+            respan(error, span)
+        })
+        .collect()
+}
+
+fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens.into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) => {
+                let mut respanned = Group::new(group.delimiter(), respan(group.stream(), span));
+                respanned.set_span(span);
+                TokenTree::Group(respanned)
+            },
+            mut token => {
+                token.set_span(span);
+                token
+            },
+        })
+        .collect()
+}
+
+fn expand(input: TokenStream) -> Result<String, Vec<String>> {
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let file = try!(tokens.get(0).and_then(string_literal).ok_or(vec![USAGE.to_string()]));
+
+    let mut builder = Builder::new();
+    // The three modules sit side by side in the invoking module
+    builder.input(root.join(file))
+        .module_path("super")
+        .service_path("super")
+        .cargo_metadata(false);
+
+    let mut rest = &tokens[1..];
+    while !rest.is_empty() {
+        let name = match (&rest[0], rest.get(1)) {
+            (&TokenTree::Punct(ref comma), Some(&TokenTree::Ident(ref name))) if comma.as_char() == ',' => {
+                name.to_string()
+            },
+            (&TokenTree::Punct(ref comma), None) if comma.as_char() == ',' => break,
+            _ => return Err(vec![USAGE.to_string()]),
+        };
+        rest = &rest[2..];
+        let value = match (rest.get(0), rest.get(1)) {
+            (Some(&TokenTree::Punct(ref eq)), Some(value)) if eq.as_char() == '=' => {
+                rest = &rest[2..];
+                match string_literal(value) {
+                    Some(value) => Some(value),
+                    None => return Err(vec![format!("expected a string for `{}`", name)]),
+                }
+            },
+            _ => None,
+        };
+        match (&name[..], value) {
+            ("include_path", Some(dir)) => { builder.include_path(root.join(dir)); },
+            ("service", Some(path)) => { builder.service_path(&path); },
+            ("snippet_prefix", Some(prefix)) => { builder.snippet_prefix(&prefix); },
+            ("opaque_bytes", None) => { builder.opaque_bytes(true); },
+            _ => return Err(vec![USAGE.to_string()]),
+        }
+    }
+
+    let generated = match builder.compile() {
+        Ok(generated) => generated,
+        Err(BuildError::Parse(e)) => {
+            return Err(e.diagnostics.iter()
+                .map(|diagnostic| diagnostic.to_string().trim_start_matches("error: ").to_string())
+                .collect());
+        },
+        Err(e) => return Err(vec![e.to_string()]),
+    };
+
+    let mut code = String::new();
+    // rustc only rebuilds on changes to files the expansion includes
+    for source in generated.sources.iter() {
+        code.push_str(&format!("const _: &'static [u8] = include_bytes!({:?});\n", source));
+    }
+    code.push_str(&format!("pub mod prot {{\n{}}}\n", generated.prot));
+    code.push_str(&format!("pub mod codec {{\n{}}}\n", generated.codec));
+    code.push_str(&format!("pub mod service {{\n{}}}\n", generated.service));
+    Ok(code)
+}
+
+// The contents of a string literal token, `"..."` or `r#"..."#`
+fn string_literal(token: &TokenTree) -> Option<String> {
+    let text = match *token {
+        TokenTree::Literal(ref literal) => literal.to_string(),
+        _ => return None,
+    };
+    if text.starts_with('r') {
+        let hashes = text[1..].chars().take_while(|&c| c == '#').count();
+        let inner = &text[1 + hashes..text.len() - hashes];
+        if inner.len() >= 2 && inner.starts_with('"') && inner.ends_with('"') {
+            return Some(inner[1..inner.len() - 1].to_string());
+        }
+        return None;
+    }
+    if text.len() < 2 || !text.starts_with('"') || !text.ends_with('"') {
+        return None;
+    }
+    let mut value = String::new();
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('0') => value.push('\0'),
+            Some(c) => value.push(c),
+            None => {},
+        }
+    }
+    Some(value)
+}
@@ -0,0 +1,50 @@
+// Builds a crate whose .x file doesn't parse and checks the build fails
+// with an error at the file, line and column of the problem.
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn write(path: PathBuf, contents: &str) {
+    File::create(path).and_then(|mut f| f.write_all(contents.as_bytes())).unwrap();
+}
+
+#[test]
+fn parse_error_points_into_the_x_file() {
+    let macros_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let crate_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("broken");
+    fs::create_dir_all(crate_dir.join("src")).unwrap();
+    write(crate_dir.join("Cargo.toml"), &format!("\
+[package]
+name = \"broken\"
+version = \"0.0.0\"
+
+[lib]
+path = \"src/lib.rs\"
+
+[dependencies]
+xdrgen_macros = {{ path = {:?} }}
+", macros_dir));
+    write(crate_dir.join("src").join("lib.rs"), "\
+#[macro_use]
+extern crate xdrgen_macros;
+
+xdr_include!(\"broken.x\");
+");
+    fs::copy(macros_dir.join("tests").join("data").join("broken.x"), crate_dir.join("broken.x")).unwrap();
+
+    let cargo = env::var("CARGO").unwrap_or("cargo".to_string());
+    let output = Command::new(cargo)
+        .arg("build")
+        .arg("--manifest-path").arg(crate_dir.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", crate_dir.join("target"))
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{}", stderr);
+    assert!(stderr.contains("error: expected `;`, found keyword `int`"), "{}", stderr);
+    assert!(stderr.contains("broken.x:3:5"), "{}", stderr);
+    // The error is reported on the invocation's path argument
+    assert!(stderr.contains("src/lib.rs:4:14"), "{}", stderr);
+}
@@ -0,0 +1,11 @@
+enum color {
+    RED = 0,
+    GREEN = 1,
+    BLUE = 2
+};
+
+struct shape {
+    string name<>;
+    color fill;
+    int points<>;
+};
@@ -0,0 +1,168 @@
+// Code generated from the .x files in tests/generated is checked in next to
+// them and compiled as part of this test, so generated code that doesn't
+// build fails here. Regenerate it with `XDRGEN_BLESS=1 cargo test --test codegen`.
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_xdr;
+extern crate xdrgen;
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+// prot.rs imports these for program decoders and encoders, which the
+// schemas here don't have
+mod xdr_rpc {
+    pub mod xdr_rpc {}
+}
+mod tokio_core {
+    pub mod io {
+        use std::mem;
+
+        pub struct EasyBuf(pub Vec<u8>);
+
+        impl EasyBuf {
+            pub fn as_slice(&self) -> &[u8] {
+                &self.0
+            }
+
+            pub fn drain_to(&mut self, at: usize) -> EasyBuf {
+                let rest = self.0.split_off(at);
+                EasyBuf(mem::replace(&mut self.0, rest))
+            }
+        }
+    }
+}
+
+#[allow(unused_imports, dead_code)]
+mod recursive {
+    include!("generated/recursive.rs");
+}
+
+#[allow(unused_imports, dead_code)]
+mod nested {
+    include!("generated/nested.rs");
+}
+
+#[allow(unused_imports, unused_variables, unreachable_patterns, dead_code)]
+mod program {
+    include!("generated/program.rs");
+}
+
+fn check_generated(name: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("generated");
+    let generated = xdrgen::Builder::new()
+        .input(dir.join(format!("{}.x", name)))
+        .cargo_metadata(false)
+        .compile()
+        .unwrap();
+
+    let path = dir.join(format!("{}.rs", name));
+    if env::var_os("XDRGEN_BLESS").is_some() {
+        File::create(&path).and_then(|mut f| f.write_all(generated.prot.as_bytes())).unwrap();
+    }
+    let mut committed = String::new();
+    File::open(&path).and_then(|mut f| f.read_to_string(&mut committed)).unwrap();
+    assert!(committed == generated.prot,
+            "{} is out of date, regenerate it with XDRGEN_BLESS=1", path.display());
+}
+
+#[test]
+fn recursive_types_are_up_to_date() {
+    check_generated("recursive");
+}
+
+#[test]
+fn program_is_up_to_date() {
+    check_generated("program");
+}
+
+#[test]
+fn nested_is_up_to_date() {
+    check_generated("nested");
+}
+
+#[test]
+fn recursive_union_round_trip() {
+    use recursive::{Binop, Expr};
+
+    let expr = Expr::Case1 {
+        b: Binop {
+            lhs: Box::new(Expr::Case0 { lit: 1 }),
+            rhs: Box::new(Expr::Case1 {
+                b: Binop {
+                    lhs: Box::new(Expr::Case0 { lit: 2 }),
+                    rhs: Box::new(Expr::Case0 { lit: 3 }),
+                },
+            }),
+        },
+    };
+    let mut buf = Vec::new();
+    serde_xdr::to_bytes(&expr, &mut buf).unwrap();
+    assert_eq!(buf, vec![0, 0, 0, 1,
+                         0, 0, 0, 0, 0, 0, 0, 1,
+                         0, 0, 0, 1,
+                         0, 0, 0, 0, 0, 0, 0, 2,
+                         0, 0, 0, 0, 0, 0, 0, 3]);
+    assert_eq!(serde_xdr::from_slice_exact::<Expr>(&buf).unwrap(), expr);
+}
+
+#[test]
+fn recursive_optional_data_round_trip() {
+    use recursive::Node;
+
+    let list = Node { value: 1, next: Some(Box::new(Node { value: 2, next: None })) };
+    let mut buf = Vec::new();
+    serde_xdr::to_bytes(&list, &mut buf).unwrap();
+    assert_eq!(buf, vec![0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 0]);
+    assert_eq!(serde_xdr::from_slice_exact::<Node>(&buf).unwrap(), list);
+}
+
+#[test]
+fn program_decodes_arguments() {
+    use program::{Color, DrawProgRequest, DrawProgRequestV1, Point, draw_prog_decode};
+    use tokio_core::io::EasyBuf;
+
+    let mut buf = EasyBuf(vec![0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1, 9]);
+    let request = draw_prog_decode(0x20000040, 1, 1, &mut buf).unwrap();
+    assert_eq!(request, Some(DrawProgRequest::V1(DrawProgRequestV1::DrawPoint(Point { x: 1, y: 2 },
+                                                                              Color::Green))));
+    assert_eq!(buf.0, vec![9]);
+}
+
+#[test]
+fn program_rejects_garbage_arguments() {
+    use program::draw_prog_decode;
+    use std::io::ErrorKind;
+    use tokio_core::io::EasyBuf;
+
+    let mut buf = EasyBuf(vec![0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 7]);
+    let err = draw_prog_decode(0x20000040, 1, 1, &mut buf).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.to_string().starts_with("GARBAGE_ARGS: argument 1 parse failure"), "{}", err);
+
+    let mut buf = EasyBuf(vec![0, 0, 0, 1, 0, 0]);
+    assert!(draw_prog_decode(0x20000040, 1, 1, &mut buf).is_err());
+}
+
+#[test]
+fn nested_types_round_trip() {
+    use nested::{Outer, OuterChoice, OuterChoiceBig, OuterLevel, OuterMaybe, OuterPair};
+
+    let outer = Outer {
+        pair: OuterPair { a: 1, b: 2 },
+        choice: OuterChoice::Case0 { big: OuterChoiceBig { h: 3 } },
+        level: OuterLevel::High,
+        maybe: Some(OuterMaybe { z: 4 }),
+    };
+    let mut buf = Vec::new();
+    serde_xdr::to_bytes(&outer, &mut buf).unwrap();
+    assert_eq!(buf, vec![0, 0, 0, 1, 0, 0, 0, 2,
+                         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3,
+                         0, 0, 0, 2,
+                         0, 0, 0, 1, 0, 0, 0, 4]);
+    assert_eq!(serde_xdr::from_slice_exact::<Outer>(&buf).unwrap(), outer);
+}
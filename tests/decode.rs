@@ -0,0 +1,42 @@
+extern crate serde_xdr;
+
+use serde_xdr::{Deserializer, to_bytes, from_bytes, from_slice_exact};
+
+fn encode_pair(a: u32, b: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    to_bytes(&a, &mut buf).unwrap();
+    to_bytes(&String::from(b), &mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn from_bytes_reports_length_and_leaves_the_rest() {
+    let buf = encode_pair(5, "hello");
+    let (a, len): (u32, usize) = from_bytes(&buf).unwrap();
+    assert_eq!(a, 5);
+    assert_eq!(len, 4);
+    let (b, len): (String, usize) = from_bytes(&buf[len..]).unwrap();
+    assert_eq!(b, "hello");
+    assert_eq!(len, 12);
+}
+
+#[test]
+fn from_slice_exact_rejects_trailing_bytes() {
+    let buf = encode_pair(5, "hello");
+    assert!(from_slice_exact::<u32>(&buf).is_err());
+    assert_eq!(from_slice_exact::<u32>(&buf[..4]).unwrap(), 5);
+}
+
+#[test]
+fn deserializer_tracks_position() {
+    let buf = encode_pair(5, "hi");
+    let mut de = Deserializer::from_slice(&buf);
+    assert_eq!(de.decode::<u32>().unwrap(), 5);
+    assert_eq!(de.position(), 4);
+    assert_eq!(de.remaining(), 8);
+    assert!(de.end().is_err());
+    assert_eq!(de.decode::<String>().unwrap(), "hi");
+    assert_eq!(de.position(), 12);
+    assert_eq!(de.remaining(), 0);
+    assert!(de.end().is_ok());
+}
@@ -0,0 +1,54 @@
+// autogenerated by xdrust
+// translated XDR->Rust types and functions
+pub use self::nested::*;
+
+/// Generated from `nested.x`
+pub mod nested {
+  #[allow(dead_code)]
+  use std::{io, fmt};
+  use serde_xdr;
+  use xdr_rpc::xdr_rpc;
+  use tokio_core::io::EasyBuf;
+
+
+  /// Anonymous types, which get names from where they're declared
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct Outer {
+    pub pair: OuterPair,
+    pub choice: OuterChoice,
+    pub level: OuterLevel,
+    pub maybe: Option<OuterMaybe>,
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct OuterPair {
+    pub a: i32,
+    pub b: i32,
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  #[serde(rename(deserialize = "__UNION_SYMBOL__"))]
+  pub enum OuterChoice {
+    #[serde(rename = "0")]
+    Case0 {
+      big: OuterChoiceBig,
+    },
+    // Default case for the XDR Union
+    UnionDefault_ {
+    },
+  }
+  xdr_enum!(OuterLevel {
+    Low = 1,
+    High = 2,
+  });
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct OuterMaybe {
+    pub z: i32,
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct OuterChoiceBig {
+    pub h: i64,
+  }
+}
@@ -0,0 +1,12 @@
+/* Anonymous types, which get names from where they're declared */
+struct outer {
+    struct { int a; int b; } pair;
+    union switch (int k) {
+    case 0:
+        struct { hyper h; } big;
+    default:
+        void;
+    } choice;
+    enum { LOW = 1, HIGH = 2 } level;
+    struct { int z; } *maybe;
+};
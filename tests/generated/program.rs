@@ -0,0 +1,135 @@
+// autogenerated by xdrust
+// translated XDR->Rust types and functions
+pub use self::program::*;
+
+/// Generated from `program.x`
+pub mod program {
+  #[allow(dead_code)]
+  use std::{io, fmt};
+  use serde_xdr;
+  use xdr_rpc::xdr_rpc;
+  use tokio_core::io::EasyBuf;
+
+  xdr_enum!(Color {
+    Red = 0,
+    Green = 1,
+  });
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct Point {
+    pub x: i32,
+    pub y: i32,
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub enum DrawProgRequest {
+    V1(DrawProgRequestV1),
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub enum DrawProgResponse {
+    V1(DrawProgResponseV1),
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub enum DrawProgRequestV1 {
+    DrawNull,
+    DrawPoint(Point, Color),
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub enum DrawProgResponseV1 {
+    DrawNull,
+    DrawPoint(i32),
+  }
+  pub fn draw_prog_decode(program: u32, version: u32, procedure: u32, buf: &mut EasyBuf) ->
+    io::Result<Option<DrawProgRequest>> {
+    match version  {
+      1u32 => {
+        draw_prog_decode_v1(procedure, buf)
+      },
+      _ => {
+        return Err(io::Error::new(io::ErrorKind::Other, "unknown version"));
+      },
+    }
+  }
+  pub fn draw_prog_decode_v1(procedure: u32, buf: &mut EasyBuf) ->
+    io::Result<Option<DrawProgRequest>> {
+    let request = match procedure {
+      0u32 => {
+        draw_prog_decode_v1_draw_null(buf)
+      },
+      1u32 => {
+        draw_prog_decode_v1_draw_point(buf)
+      },
+      _ => {
+        return Err(io::Error::new(io::ErrorKind::Other, "unknown procedure"));
+      },
+    };
+    Ok(request?.map(DrawProgRequest::V1))
+  }
+  pub fn draw_prog_decode_v1_draw_null(buf: &mut EasyBuf) -> io::Result<Option<DrawProgRequestV1>> {
+    Ok(Some(DrawProgRequestV1::DrawNull))
+  }
+  pub fn draw_prog_decode_v1_draw_point(buf: &mut EasyBuf) -> io::Result<Option<DrawProgRequestV1>> {
+    let res0 = serde_xdr::from_bytes::<Point>(buf.as_slice());
+    let arg0 = match res0 {
+        Ok((arg, consumed)) => {
+            buf.drain_to(consumed);
+            arg
+        },
+        Err(e) => {
+            match e {
+                serde_xdr::EncoderError::Io(i) => {
+                    return Err(i);
+                },
+                serde_xdr::EncoderError::Unknown(s) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("GARBAGE_ARGS: argument 0 parse failure: {}", s)));
+                }
+            }
+        }
+    };
+
+    let res1 = serde_xdr::from_bytes::<Color>(buf.as_slice());
+    let arg1 = match res1 {
+        Ok((arg, consumed)) => {
+            buf.drain_to(consumed);
+            arg
+        },
+        Err(e) => {
+            match e {
+                serde_xdr::EncoderError::Io(i) => {
+                    return Err(i);
+                },
+                serde_xdr::EncoderError::Unknown(s) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("GARBAGE_ARGS: argument 1 parse failure: {}", s)));
+                }
+            }
+        }
+    };
+
+    Ok(Some(DrawProgRequestV1::DrawPoint(arg0, arg1)))
+  }
+  pub fn draw_prog_encode(msg: DrawProgResponse, buf: &mut Vec<u8>) -> io::Result<()> {
+    match msg  {
+      DrawProgResponse::V1(rsp) => {
+        match rsp  {
+          DrawProgResponseV1::DrawNull => {
+          },
+          DrawProgResponseV1::DrawPoint(r) => {
+            serde_xdr::to_bytes(&r, buf)?;
+          },
+          _ => {
+            return Err(io::Error::new(io::ErrorKind::Other, "unknown procedure"));
+          },
+        }
+        Ok(())
+      },
+      _ => {
+        return Err(io::Error::new(io::ErrorKind::Other, "unknown version"));
+      },
+    }
+  }
+}
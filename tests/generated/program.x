@@ -0,0 +1,16 @@
+enum color {
+    RED = 0,
+    GREEN = 1
+};
+
+struct point {
+    int x;
+    int y;
+};
+
+program DRAW_PROG {
+    version DRAW_V1 {
+        void DRAW_NULL(void) = 0;
+        int DRAW_POINT(point, color) = 1;
+    } = 1;
+} = 0x20000040;
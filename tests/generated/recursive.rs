@@ -0,0 +1,71 @@
+// autogenerated by xdrust
+// translated XDR->Rust types and functions
+pub use self::recursive::*;
+
+/// Generated from `recursive.x`
+pub mod recursive {
+  #[allow(dead_code)]
+  use std::{io, fmt};
+  use serde_xdr;
+  use xdr_rpc::xdr_rpc;
+  use tokio_core::io::EasyBuf;
+
+
+  /// Types that only have a size once a Box breaks their cycle
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  #[serde(rename(deserialize = "__UNION_SYMBOL__"))]
+  pub enum Expr {
+    #[serde(rename = "0")]
+    Case0 {
+      lit: i32,
+    },
+    #[serde(rename = "1")]
+    Case1 {
+      b: Binop,
+    },
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct Binop {
+    pub lhs: Box<Expr>,
+    pub rhs: Box<Expr>,
+  }
+
+  /// Optional data leading back to itself
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct Node {
+    pub value: i32,
+    pub next: Option<Box<Node>>,
+  }
+
+  /// A cycle through a typedef, a fixed array and a hoisted union
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct Forest(#[serde(with = "serde_xdr::array")] pub [Tree; 2]);
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct Tree {
+    pub kind: TreeKind,
+    pub sibling: Option<Box<TreeAlias>>,
+  }
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  #[serde(rename(deserialize = "__UNION_SYMBOL__"))]
+  pub enum TreeKind {
+    #[serde(rename = "1")]
+    True {
+      value: i32,
+    },
+    #[serde(rename = "0")]
+    False {
+      children: Box<Forest>,
+    },
+  }
+  pub type TreeAlias = Tree;
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  pub struct Wrapper {
+    pub inner: Vec<WrapperAlias>,
+    pub name: String,
+  }
+  pub type WrapperAlias = Wrapper;
+}
@@ -0,0 +1,40 @@
+/* Types that only have a size once a Box breaks their cycle */
+union expr switch (int op) {
+case 0:
+    int lit;
+case 1:
+    binop b;
+};
+
+struct binop {
+    expr lhs;
+    expr rhs;
+};
+
+/* Optional data leading back to itself */
+struct node {
+    int value;
+    node *next;
+};
+
+/* A cycle through a typedef, a fixed array and a hoisted union */
+typedef tree forest[2];
+
+struct tree {
+    union switch (bool leaf) {
+    case TRUE:
+        int value;
+    case FALSE:
+        forest children;
+    } kind;
+    tree_alias *sibling;
+};
+
+typedef tree tree_alias;
+
+struct wrapper {
+    wrapper_alias inner<>;
+    string name<>;
+};
+
+typedef wrapper wrapper_alias;
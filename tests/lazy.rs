@@ -0,0 +1,73 @@
+extern crate serde_xdr;
+#[macro_use]
+extern crate serde_derive;
+
+use serde_xdr::{Lazy, RawXdr, to_bytes, from_bytes, from_slice_exact};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Body {
+    id: u32,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Message {
+    kind: u32,
+    body: Lazy<Body>,
+    trailer: u32,
+}
+
+fn body() -> Body {
+    Body { id: 7, name: String::from("abc") }
+}
+
+#[test]
+fn lazy_captures_the_encoded_value() {
+    let mut expected = Vec::new();
+    to_bytes(&body(), &mut expected).unwrap();
+
+    let msg = Message { kind: 1, body: Lazy::new(&body()).unwrap(), trailer: 9 };
+    let mut buf = Vec::new();
+    to_bytes(&msg, &mut buf).unwrap();
+
+    let (decoded, len): (Message, usize) = from_bytes(&buf).unwrap();
+    assert_eq!(len, buf.len());
+    assert_eq!(decoded.kind, 1);
+    assert_eq!(decoded.trailer, 9);
+    assert_eq!(decoded.body.as_bytes(), &expected[..]);
+    assert_eq!(decoded.body.decode().unwrap(), body());
+}
+
+#[test]
+fn lazy_reencodes_verbatim() {
+    let msg = Message { kind: 1, body: Lazy::new(&body()).unwrap(), trailer: 9 };
+    let mut buf = Vec::new();
+    to_bytes(&msg, &mut buf).unwrap();
+
+    let decoded: Message = from_slice_exact(&buf).unwrap();
+    let mut again = Vec::new();
+    to_bytes(&decoded, &mut again).unwrap();
+    assert_eq!(again, buf);
+}
+
+#[test]
+fn lazy_rejects_truncated_value() {
+    let msg = Message { kind: 1, body: Lazy::new(&body()).unwrap(), trailer: 9 };
+    let mut buf = Vec::new();
+    to_bytes(&msg, &mut buf).unwrap();
+
+    assert!(from_bytes::<Message>(&buf[..10]).is_err());
+}
+
+#[test]
+fn raw_xdr_is_written_without_framing() {
+    let mut inner = Vec::new();
+    to_bytes(&body(), &mut inner).unwrap();
+
+    let mut raw = Vec::new();
+    to_bytes(&RawXdr(inner.clone()), &mut raw).unwrap();
+    assert_eq!(raw, inner);
+
+    let lazy: Lazy<Body> = Lazy::from_encoded(inner.clone());
+    assert_eq!(RawXdr::from(lazy), RawXdr(inner));
+}
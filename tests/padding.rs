@@ -0,0 +1,63 @@
+extern crate serde_xdr;
+#[macro_use]
+extern crate serde_derive;
+
+use serde_xdr::{Deserializer, to_bytes, from_slice_exact};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Padded {
+    name: String,
+    #[serde(with = "serde_xdr::opaque")]
+    data: Vec<u8>,
+    #[serde(with = "serde_xdr::opaque::fixed")]
+    fixed: [u8; 5],
+    after: u32,
+}
+
+fn padded() -> Padded {
+    Padded {
+        name: String::from("a"),
+        data: vec![1, 2, 3, 4, 5, 6],
+        fixed: [9, 8, 7, 6, 5],
+        after: 0xdeadbeef,
+    }
+}
+
+fn encoded() -> Vec<u8> {
+    let mut buf = Vec::new();
+    to_bytes(&padded(), &mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn padding_is_written_and_consumed() {
+    let buf = encoded();
+    assert_eq!(buf, vec![0, 0, 0, 1, b'a', 0, 0, 0,
+                         0, 0, 0, 6, 1, 2, 3, 4, 5, 6, 0, 0,
+                         9, 8, 7, 6, 5, 0, 0, 0,
+                         0xde, 0xad, 0xbe, 0xef]);
+    assert_eq!(from_slice_exact::<Padded>(&buf).unwrap(), padded());
+}
+
+#[test]
+fn lenient_padding_skips_non_zero_bytes() {
+    let mut buf = encoded();
+    buf[5] = 1;
+    buf[19] = 1;
+    buf[27] = 1;
+    assert_eq!(from_slice_exact::<Padded>(&buf).unwrap(), padded());
+}
+
+#[test]
+fn strict_padding_rejects_non_zero_bytes() {
+    for &offset in [5, 19, 27].iter() {
+        let mut buf = encoded();
+        buf[offset] = 1;
+        let mut de = Deserializer::from_slice(&buf[..]).strict_padding(true);
+        assert!(de.decode::<Padded>().is_err(), "padding at {} accepted", offset);
+    }
+
+    let buf = encoded();
+    let mut de = Deserializer::from_slice(&buf[..]).strict_padding(true);
+    assert_eq!(de.decode::<Padded>().unwrap(), padded());
+}
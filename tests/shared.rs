@@ -0,0 +1,81 @@
+#![cfg(feature = "bytes")]
+
+extern crate bytes;
+extern crate serde_xdr;
+#[macro_use]
+extern crate serde_derive;
+
+use bytes::{Bytes, BytesMut};
+use serde_xdr::{to_bytes, to_bytes_mut, to_chain, from_bytes, from_shared};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Write {
+    offset: u64,
+    #[serde(with = "serde_xdr::shared")]
+    data: Bytes,
+    #[serde(with = "serde_xdr::shared")]
+    more: Bytes,
+    stable: u32,
+}
+
+fn write() -> Write {
+    Write {
+        offset: 4096,
+        data: Bytes::from(vec![1u8; 70]),
+        more: Bytes::from(&b"xyz"[..]),
+        stable: 2,
+    }
+}
+
+fn encoded() -> Vec<u8> {
+    let mut buf = Vec::new();
+    to_bytes(&write(), &mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn shared_fields_encode_as_opaque() {
+    let buf = encoded();
+    assert_eq!(buf.len(), 8 + 4 + 72 + 4 + 4 + 4);
+    assert_eq!(&buf[8..12], &[0, 0, 0, 70]);
+    assert_eq!(from_bytes::<Write>(&buf).unwrap().0, write());
+}
+
+#[test]
+fn from_shared_slices_the_input() {
+    let buf = Bytes::from(encoded());
+    let (decoded, len): (Write, usize) = from_shared(&buf).unwrap();
+    assert_eq!(len, buf.len());
+    assert_eq!(decoded, write());
+
+    // The payload points into `buf` rather than a copy of it
+    let start = buf.as_ptr() as usize;
+    let data = decoded.data.as_ptr() as usize;
+    assert_eq!(data - start, 12);
+}
+
+#[test]
+fn from_shared_rejects_short_payload() {
+    let buf = Bytes::from(encoded());
+    assert!(from_shared::<Write>(&buf.slice(0, 40)).is_err());
+}
+
+#[test]
+fn to_chain_leaves_payloads_uncopied() {
+    let value = write();
+    let chain = to_chain(&value).unwrap();
+
+    let joined: Vec<u8> = chain.iter().flat_map(|b| b.iter().cloned()).collect();
+    assert_eq!(joined, encoded());
+    assert!(chain.iter().any(|b| b.as_ptr() == value.data.as_ptr() && b.len() == 70));
+}
+
+#[test]
+fn to_bytes_mut_appends() {
+    let mut buf = BytesMut::with_capacity(4);
+    to_bytes_mut(&write(), &mut buf).unwrap();
+    to_bytes_mut(&write(), &mut buf).unwrap();
+    let mut twice = encoded();
+    twice.extend(encoded());
+    assert_eq!(&buf[..], &twice[..]);
+}
@@ -0,0 +1,61 @@
+extern crate serde_xdr;
+#[macro_use]
+extern crate serde_derive;
+
+use std::hash::Hasher;
+use std::collections::hash_map::DefaultHasher;
+use serde_xdr::{to_bytes, to_hasher, to_sink, to_hex, from_hex, to_base64, from_base64};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Entry {
+    id: u32,
+    name: String,
+}
+
+fn entry() -> Entry {
+    Entry { id: 258, name: String::from("ab") }
+}
+
+#[test]
+fn hex_round_trip() {
+    let hex = to_hex(&entry()).unwrap();
+    assert_eq!(hex, "000001020000000261620000");
+    assert_eq!(from_hex::<Entry>(&hex).unwrap(), entry());
+}
+
+#[test]
+fn base64_round_trip() {
+    let text = to_base64(&entry()).unwrap();
+    assert_eq!(from_base64::<Entry>(&text).unwrap(), entry());
+}
+
+#[test]
+fn text_forms_reject_bad_input() {
+    assert!(from_hex::<Entry>("0000010g").is_err());
+    assert!(from_base64::<Entry>("!!!!").is_err());
+    // Valid hex, but with a trailing byte after the value
+    assert!(from_hex::<Entry>("00000102000000026162000000000000").is_err());
+}
+
+#[test]
+fn hasher_sees_the_encoding() {
+    let mut buf = Vec::new();
+    to_bytes(&entry(), &mut buf).unwrap();
+
+    let mut expected = DefaultHasher::new();
+    expected.write(&buf);
+
+    let mut hasher = DefaultHasher::new();
+    to_hasher(&entry(), &mut hasher).unwrap();
+    assert_eq!(hasher.finish(), expected.finish());
+}
+
+#[test]
+fn sink_receives_every_byte() {
+    let mut buf = Vec::new();
+    to_bytes(&entry(), &mut buf).unwrap();
+
+    let mut seen = Vec::new();
+    to_sink(&entry(), |b| seen.extend_from_slice(b)).unwrap();
+    assert_eq!(seen, buf);
+}